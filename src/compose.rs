@@ -0,0 +1,165 @@
+use std::fmt;
+
+use crate::vasm::{StateMachine, Transition};
+
+/// `ComposeError` is returned when a firing sequence cannot be folded into one transition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComposeError {
+    /// The sequence names no actions.
+    EmptySequence,
+    /// The sequence names an action the machine does not have.
+    UnknownAction(String),
+    /// Every marking that could start the sequence overfills this place before it ends.
+    NotFirable { place: String },
+}
+
+impl fmt::Display for ComposeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EmptySequence => write!(f, "cannot compose an empty sequence"),
+            Self::UnknownAction(action) => write!(f, "unknown action: {action}"),
+            Self::NotFirable { place } => {
+                write!(
+                    f,
+                    "sequence cannot fire without exceeding the capacity of {place}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ComposeError {}
+
+impl StateMachine {
+    /// Folds a firing sequence into one transition named `name`, for use as a macro-step.
+    ///
+    /// The composed delta is the sum of the sequence's deltas, and it consumes the smallest
+    /// marking from which the whole sequence fires in order, so tokens a later step needs can
+    /// not be borrowed from an earlier step's output before it is produced. The role is that of
+    /// the first action, and the offset is the next free one, so the result can be added to
+    /// `transitions` and `actions` as is.
+    ///
+    /// Only arcs and capacities are checked, using the `petriNet` firing rule. Guards are
+    /// dropped, so the composed transition may fire where an inhibitor or read arc would stop
+    /// the sequence part way through, and firing from a marking above the minimum may overfill
+    /// a place the sequence passes through.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ComposeError` if the sequence is empty, names an unknown action, or exceeds a
+    /// capacity even from its minimal starting marking.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the machine has more than `i32::MAX` actions.
+    pub fn compose(&self, name: &str, sequence: &[&str]) -> Result<Transition, ComposeError> {
+        let first = sequence.first().ok_or(ComposeError::EmptySequence)?;
+        let places = self.places.len();
+        let mut delta = vec![0; places];
+        let mut consume = vec![0; places];
+        let mut peak = vec![0; places];
+        for action in sequence {
+            let transition = self
+                .transitions
+                .get(*action)
+                .ok_or_else(|| ComposeError::UnknownAction((*action).to_string()))?;
+            for p in 0..places {
+                let step = transition.delta[p];
+                let consumed = transition.consume.get(p).copied().unwrap_or(0).max(-step);
+                consume[p] = consume[p].max(consumed - delta[p]);
+                delta[p] += step;
+                peak[p] = peak[p].max(delta[p]);
+            }
+        }
+
+        for p in 0..places {
+            if self.allow_negative.get(p).copied().unwrap_or(false) {
+                consume[p] = 0;
+            } else if self
+                .place_capacity(p)
+                .is_some_and(|c| consume[p] + peak[p] > c)
+            {
+                return Err(ComposeError::NotFirable {
+                    place: self.places[p].clone(),
+                });
+            }
+        }
+
+        Ok(Transition {
+            label: name.to_string(),
+            role: self.transitions[*first].role.clone(),
+            delta,
+            consume,
+            offset: i32::try_from(self.actions.len()).expect("offset conversion failed"),
+            weight: self.transitions[*first].weight,
+            ..Transition::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compose::ComposeError;
+    use crate::vasm::{StateMachine, Vasm};
+
+    #[test]
+    fn test_compose_boil_and_brew() {
+        let mut vm = StateMachine::new(crate::petri_net::tests::coffee_machine);
+        let offset = |label: &str| vm.places.iter().position(|p| p == label).expect(label);
+        let (water, boiled, ground, filter, pot) = (
+            offset("Water"),
+            offset("BoiledWater"),
+            offset("GroundCoffee"),
+            offset("Filter"),
+            offset("CoffeeInPot"),
+        );
+
+        let brew = vm
+            .compose("boil_and_brew", &["boil_water", "brew_coffee"])
+            .expect("sequence fires");
+        let mut expected = vec![0; vm.places.len()];
+        expected[water] = -1;
+        expected[ground] = -1;
+        expected[filter] = -1;
+        expected[pot] = 1;
+        assert_eq!(brew.delta(), &expected);
+        // the boiled water is made along the way, so none is needed up front
+        assert_eq!(brew.consume()[boiled], 0);
+        assert_eq!(brew.consume()[water], 1);
+
+        let ground_beans = vm.transform(&vm.initial_vector(), "grind_beans", 1).output;
+        let sequential = vm.transform(
+            &vm.transform(&ground_beans, "boil_water", 1).output,
+            "brew_coffee",
+            1,
+        );
+        vm.transitions.insert("boil_and_brew".to_string(), brew);
+        vm.actions.push("boil_and_brew".to_string());
+        let composed = vm.transform(&ground_beans, "boil_and_brew", 1);
+        assert!(composed.is_ok());
+        assert_eq!(composed.output, sequential.output);
+        assert!(vm
+            .transform(&vm.initial_vector(), "boil_and_brew", 1)
+            .is_err());
+    }
+
+    #[test]
+    fn test_compose_errors() {
+        let vm = StateMachine::new(crate::petri_net::tests::coffee_machine);
+        assert_eq!(
+            vm.compose("none", &[]).err(),
+            Some(ComposeError::EmptySequence)
+        );
+        assert_eq!(
+            vm.compose("tea", &["boil_water", "steep"]).err(),
+            Some(ComposeError::UnknownAction("steep".to_string()))
+        );
+        // boiling twice needs two tokens of water, but the place holds one
+        assert_eq!(
+            vm.compose("twice", &["boil_water", "boil_water"]).err(),
+            Some(ComposeError::NotFirable {
+                place: "Water".to_string()
+            })
+        );
+    }
+}
@@ -14,7 +14,7 @@ pub fn decompress_brotli_decode(encoded_data: &str) -> Result<String, Box<dyn Er
 
 /// Decodes the base64 string from the given URL into a zip file and then extracts the file with the given filename from the zip file.
 pub fn decompress_encoded_url(url: &str) -> Result<String, Box<dyn Error>> {
-    let query_string = url.split('?').collect::<Vec<&str>>()[1];
+    let query_string = url.split('?').nth(1).ok_or("failed to extract")?;
     let z = query_string
         .split('&')
         .find(|&param| param.starts_with("z="))
@@ -24,13 +24,60 @@ pub fn decompress_encoded_url(url: &str) -> Result<String, Box<dyn Error>> {
     decompress_brotli_decode(z)
 }
 
+/// Default brotli quality used when sharing models.
+pub const DEFAULT_QUALITY: u32 = 5;
+
+/// Default brotli window size (lgwin) used when sharing models.
+pub const DEFAULT_LGWIN: u32 = 22;
+
 /// Compresses the given string using brotli encoding and then encodes it in base64.
 pub fn compress_brotli_encode(data: &str) -> Result<String, Box<dyn Error>> {
+    compress_brotli_encode_with(data, DEFAULT_QUALITY, DEFAULT_LGWIN)
+}
+
+/// Compresses the given string using brotli with the given quality (0-11) and window size (10-24),
+/// then encodes it in base64.
+///
+/// Changing either parameter changes the compressed bytes and therefore the CID of the resulting blob,
+/// use `compress_brotli_encode` to produce blobs that match the CIDs shared on pflow.dev.
+pub fn compress_brotli_encode_with(
+    data: &str,
+    quality: u32,
+    lgwin: u32,
+) -> Result<String, Box<dyn Error>> {
     let mut compressed_data = Vec::new();
     {
-        let mut compressor = CompressorWriter::new(&mut compressed_data, 4096, 5, 22); // 4096 is the buffer size, 5 is quality, 22 is lgwin
+        let mut compressor = CompressorWriter::new(&mut compressed_data, 4096, quality, lgwin); // 4096 is the buffer size
         compressor.write_all(data.as_bytes())?;
     } // CompressorWriter is flushed and finished when it goes out of scope
 
     Ok(general_purpose::STANDARD.encode(compressed_data))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::petri_net::tests::DINING_PHILOSOPHERS;
+
+    #[test]
+    fn test_compression_quality() {
+        let fast = compress_brotli_encode_with(DINING_PHILOSOPHERS, 1, DEFAULT_LGWIN)
+            .expect("compression failed");
+        let best = compress_brotli_encode_with(DINING_PHILOSOPHERS, 11, DEFAULT_LGWIN)
+            .expect("compression failed");
+        assert!(best.len() < fast.len());
+        assert_eq!(
+            decompress_brotli_decode(&best).expect("decompression failed"),
+            DINING_PHILOSOPHERS
+        );
+    }
+
+    #[test]
+    fn test_default_compression() {
+        let default = compress_brotli_encode(DINING_PHILOSOPHERS).expect("compression failed");
+        let explicit =
+            compress_brotli_encode_with(DINING_PHILOSOPHERS, DEFAULT_QUALITY, DEFAULT_LGWIN)
+                .expect("compression failed");
+        assert_eq!(default, explicit);
+    }
+}
@@ -0,0 +1,145 @@
+use crate::dsl::{Builder, Dsl};
+use crate::error::ModelError;
+use crate::petri_net::PetriNet;
+use crate::vasm::{StateMachine, Vasm};
+
+/// `DfaBuilder` declares a deterministic finite automaton and compiles it to an elementary net.
+///
+/// Each state becomes a place and each transition becomes a func whose role is its input
+/// symbol, so a run is a sequence of `transform_role` calls. A self-loop is compiled to a read
+/// arc, which checks the token without moving it.
+#[derive(Debug, Clone, Default)]
+pub struct DfaBuilder {
+    states: Vec<String>,
+    start: Option<String>,
+    accepting: Vec<String>,
+    transitions: Vec<(String, String, String)>,
+}
+
+impl DfaBuilder {
+    /// Creates an automaton with no states.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares a state; states are numbered in declaration order.
+    pub fn state(&mut self, label: &str) -> &mut Self {
+        if !self.states.iter().any(|s| s == label) {
+            self.states.push(label.to_string());
+        }
+        self
+    }
+
+    /// Declares the state holding the token before any input is read.
+    pub fn start(&mut self, label: &str) -> &mut Self {
+        self.state(label);
+        self.start = Some(label.to_string());
+        self
+    }
+
+    /// Declares a state that accepts the input when the run ends there.
+    pub fn accept(&mut self, label: &str) -> &mut Self {
+        self.state(label);
+        self.accepting.push(label.to_string());
+        self
+    }
+
+    /// Declares a move from `from` to `to` on reading `symbol`.
+    pub fn transition(&mut self, from: &str, symbol: &str, to: &str) -> &mut Self {
+        self.state(from);
+        self.state(to);
+        self.transitions
+            .push((from.to_string(), symbol.to_string(), to.to_string()));
+        self
+    }
+
+    /// Compiles the automaton to an elementary `PetriNet` and its `StateMachine`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ModelError` if the compiled net is invalid, such as when two transitions
+    /// share a source, symbol, and target.
+    pub fn build(&self) -> Result<Dfa, ModelError> {
+        let mut net = PetriNet::new();
+        let mut builder = Builder::new(&mut net);
+        builder.model_type("elementary");
+        for state in &self.states {
+            let initial = self.start.as_ref().is_some_and(|s| s == state);
+            builder.cell_auto(state, Some(i32::from(initial)), Some(1));
+        }
+        for (from, symbol, to) in &self.transitions {
+            let label = format!("{from}_{symbol}_{to}");
+            builder.func_auto(&label, symbol);
+            if from == to {
+                builder.guard(&label, from, 1);
+            } else {
+                builder.arrow(from, &label, 1);
+                builder.arrow(&label, to, 1);
+            }
+        }
+        let vm = builder.finish()?;
+        Ok(Dfa {
+            net,
+            vm,
+            accepting: self.accepting.clone(),
+        })
+    }
+}
+
+/// `Dfa` is an automaton compiled by `DfaBuilder`.
+#[derive(Debug, Clone)]
+pub struct Dfa {
+    /// The elementary net the automaton compiles to.
+    pub net: PetriNet,
+    /// The state machine that runs the net.
+    pub vm: StateMachine,
+    accepting: Vec<String>,
+}
+
+impl Dfa {
+    /// Runs the input symbols from the start state and checks that the run ends on an accepting state.
+    ///
+    /// An input is rejected as soon as a symbol has no move from the current state.
+    pub fn accepts(&self, input: &[&str]) -> bool {
+        let mut state = self.vm.initial_vector();
+        for symbol in input {
+            let Some((_, tx)) = self.vm.transform_role(&state, symbol, 1) else {
+                return false;
+            };
+            state = tx.output;
+        }
+        self.vm
+            .places
+            .iter()
+            .zip(&state)
+            .any(|(place, &tokens)| tokens > 0 && self.accepting.contains(place))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_even_number_of_ones() {
+        let dfa = DfaBuilder::new()
+            .start("even")
+            .accept("even")
+            .state("odd")
+            .transition("even", "0", "even")
+            .transition("even", "1", "odd")
+            .transition("odd", "0", "odd")
+            .transition("odd", "1", "even")
+            .build()
+            .expect("valid automaton");
+        assert_eq!(dfa.net.model_type, "elementary");
+        assert_eq!(dfa.vm.is_deterministic(), Ok(true));
+
+        assert!(dfa.accepts(&[]));
+        assert!(dfa.accepts(&["1", "1"]));
+        assert!(dfa.accepts(&["0", "1", "0", "0", "1"]));
+        assert!(!dfa.accepts(&["1"]));
+        assert!(!dfa.accepts(&["1", "0", "0"]));
+        assert!(!dfa.accepts(&["2"]));
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::petri_net::{Arrow, NodeMap, PetriNet, Place, Transition};
+
+/// `Change` records how a single named element differs between two nets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change<T> {
+    /// The element only exists in the edited net.
+    Added(T),
+    /// The element only exists in the base net.
+    Removed(T),
+    /// The element exists in both nets with different contents.
+    Changed { from: T, to: T },
+}
+
+/// `NetDiff` lists the edits that turn one `PetriNet` into another.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NetDiff {
+    /// Place edits, keyed by label.
+    pub places: BTreeMap<String, Change<Place>>,
+    /// Transition edits, keyed by label.
+    pub transitions: BTreeMap<String, Change<Transition>>,
+    /// Arcs of the base net to remove, in order.
+    pub removed_arcs: Vec<Arrow>,
+    /// Arcs to insert, with their index in the edited net, in ascending order.
+    pub added_arcs: Vec<(usize, Arrow)>,
+}
+
+impl NetDiff {
+    /// Checks whether the two nets had the same places, transitions, and arcs.
+    pub fn is_empty(&self) -> bool {
+        self.places.is_empty()
+            && self.transitions.is_empty()
+            && self.removed_arcs.is_empty()
+            && self.added_arcs.is_empty()
+    }
+}
+
+/// `MergeConflict` is returned when a `NetDiff` no longer matches the net it is applied to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeConflict {
+    /// The diff adds a place or transition that is already present with other contents.
+    AlreadyExists(String),
+    /// The diff removes or changes a place or transition that is not present.
+    Missing(String),
+    /// The diff changes or removes a place or transition that was edited since the diff was taken.
+    Modified(String),
+    /// The diff removes an arc that is not present.
+    MissingArc { source: String, target: String },
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlreadyExists(label) => write!(f, "{label} already exists"),
+            Self::Missing(label) => write!(f, "{label} does not exist"),
+            Self::Modified(label) => write!(f, "{label} was modified"),
+            Self::MissingArc { source, target } => {
+                write!(f, "arc {source} -> {target} does not exist")
+            }
+        }
+    }
+}
+
+impl std::error::Error for MergeConflict {}
+
+fn diff_nodes<T: Clone + PartialEq>(
+    from: &NodeMap<T>,
+    to: &NodeMap<T>,
+) -> BTreeMap<String, Change<T>> {
+    let mut changes = BTreeMap::new();
+    for (label, old) in from {
+        match to.get(label) {
+            None => {
+                changes.insert(label.clone(), Change::Removed(old.clone()));
+            }
+            Some(new) if new != old => {
+                let change = Change::Changed {
+                    from: old.clone(),
+                    to: new.clone(),
+                };
+                changes.insert(label.clone(), change);
+            }
+            Some(_) => {}
+        }
+    }
+    for (label, new) in to {
+        if !from.contains_key(label) {
+            changes.insert(label.clone(), Change::Added(new.clone()));
+        }
+    }
+    changes
+}
+
+fn apply_nodes<T: Clone + PartialEq>(
+    nodes: &mut NodeMap<T>,
+    changes: &BTreeMap<String, Change<T>>,
+) -> Result<(), MergeConflict> {
+    for (label, change) in changes {
+        match (change, nodes.get(label)) {
+            (Change::Added(new), Some(current)) if current != new => {
+                return Err(MergeConflict::AlreadyExists(label.clone()));
+            }
+            (Change::Added(new), _) => {
+                nodes.insert(label.clone(), new.clone());
+            }
+            (Change::Removed(_) | Change::Changed { .. }, None) => {
+                return Err(MergeConflict::Missing(label.clone()));
+            }
+            (Change::Removed(old) | Change::Changed { from: old, .. }, Some(current))
+                if current != old =>
+            {
+                return Err(MergeConflict::Modified(label.clone()));
+            }
+            (Change::Removed(_), Some(_)) => {
+                nodes.retain(|node, _| node != label);
+            }
+            (Change::Changed { to, .. }, Some(_)) => {
+                nodes.insert(label.clone(), to.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Marks the arcs of the longest common subsequence of `from` and `to`.
+fn common_arcs(from: &[Arrow], to: &[Arrow]) -> (Vec<bool>, Vec<bool>) {
+    let (n, m) = (from.len(), to.len());
+    let mut lengths = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if from[i] == to[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let (mut kept_from, mut kept_to) = (vec![false; n], vec![false; m]);
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if from[i] == to[j] {
+            kept_from[i] = true;
+            kept_to[j] = true;
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    (kept_from, kept_to)
+}
+
+impl PetriNet {
+    /// Lists the edits that turn this net into `other`.
+    ///
+    /// Places and transitions are compared by label. Arcs are compared as an ordered list, so
+    /// applying the diff reproduces the arc order of `other` and with it the same CID.
+    pub fn diff(&self, other: &Self) -> NetDiff {
+        let (kept_from, kept_to) = common_arcs(&self.arcs, &other.arcs);
+        NetDiff {
+            places: diff_nodes(&self.places, &other.places),
+            transitions: diff_nodes(&self.transitions, &other.transitions),
+            removed_arcs: self
+                .arcs
+                .iter()
+                .zip(kept_from)
+                .filter(|(_, kept)| !kept)
+                .map(|(arc, _)| arc.clone())
+                .collect(),
+            added_arcs: other
+                .arcs
+                .iter()
+                .enumerate()
+                .zip(kept_to)
+                .filter(|(_, kept)| !kept)
+                .map(|((i, arc), _)| (i, arc.clone()))
+                .collect(),
+        }
+    }
+
+    /// Applies a diff taken against another copy of this net, as in a three-way merge.
+    ///
+    /// Edits made here since the diff was taken are kept unless the diff touches the same
+    /// place or transition. Nothing is changed if any conflict is found.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `MergeConflict` for the first edit that no longer applies.
+    pub fn apply_diff(&mut self, diff: &NetDiff) -> Result<(), MergeConflict> {
+        let mut merged = self.clone();
+        apply_nodes(&mut merged.places, &diff.places)?;
+        apply_nodes(&mut merged.transitions, &diff.transitions)?;
+        for arc in &diff.removed_arcs {
+            let index = merged.arcs.iter().position(|a| a == arc).ok_or_else(|| {
+                MergeConflict::MissingArc {
+                    source: arc.source.clone(),
+                    target: arc.target.clone(),
+                }
+            })?;
+            merged.arcs.remove(index);
+        }
+        for (index, arc) in &diff.added_arcs {
+            let index = (*index).min(merged.arcs.len());
+            merged.arcs.insert(index, arc.clone());
+        }
+        *self = merged;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::petri_net::tests::coffee_machine;
+
+    fn edited_coffee_machine() -> (PetriNet, PetriNet) {
+        let mut base = PetriNet::new();
+        base.declare(coffee_machine);
+        let mut edited = base.clone();
+        edited.places.retain(|label, _| label != "Cup");
+        edited.arcs.retain(|arc| arc.source != "Cup");
+        edited.places.get_mut("Water").expect("water place").initial = Some(3);
+        edited.add_place("Milk", 6, Some(1), None, 900, 300);
+        edited.arcs.insert(
+            1,
+            Arrow {
+                source: "Milk".to_string(),
+                target: "pour_coffee".to_string(),
+                weight: Some(1),
+                consume: Some(true),
+                produce: Some(false),
+                inhibit: None,
+                read: Some(false),
+                group: None,
+            },
+        );
+        (base, edited)
+    }
+
+    #[test]
+    fn test_apply_diff_reproduces_edit() {
+        let (base, edited) = edited_coffee_machine();
+        let diff = base.diff(&edited);
+        assert_eq!(diff.places.len(), 3);
+        assert_eq!(diff.removed_arcs.len(), 1);
+        assert_eq!(diff.added_arcs.len(), 1);
+
+        let mut merged = base;
+        merged.apply_diff(&diff).expect("diff applies cleanly");
+        assert_eq!(merged, edited);
+        assert_eq!(merged.to_zblob().ipfs_cid, edited.to_zblob().ipfs_cid);
+        assert!(merged.diff(&edited).is_empty());
+    }
+
+    #[test]
+    fn test_apply_diff_conflicts() {
+        let (base, edited) = edited_coffee_machine();
+        let diff = base.diff(&edited);
+
+        let mut concurrent = base.clone();
+        concurrent.places.retain(|label, _| label != "Cup");
+        assert_eq!(
+            concurrent.apply_diff(&diff),
+            Err(MergeConflict::Missing("Cup".to_string()))
+        );
+        assert!(concurrent.places.contains_key("Water"));
+        assert_eq!(concurrent.places["Water"].initial, Some(1));
+
+        let mut concurrent = base;
+        concurrent
+            .places
+            .get_mut("Water")
+            .expect("water place")
+            .initial = Some(2);
+        assert_eq!(
+            concurrent.apply_diff(&diff),
+            Err(MergeConflict::Modified("Water".to_string()))
+        );
+    }
+}
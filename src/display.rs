@@ -1,10 +1,40 @@
 use crate::petri_net::{Arrow, PetriNet, Place, Transition};
+use crate::validate::ModelIssue;
 use crate::zblob::Zblob;
-use crate::{Model, Vector};
+use crate::{Model, Vasm, Vector};
 use base64::Engine;
+use std::fmt;
 use std::io::Write;
 use std::sync::{Arc, Mutex};
 
+/// `DisplayError` is returned when a marking cannot be drawn on the model or the SVG cannot be written.
+#[derive(Debug)]
+pub enum DisplayError {
+    /// The marking does not have one entry per place.
+    MarkingLength { expected: usize, actual: usize },
+    /// The output sink failed.
+    Io(std::io::Error),
+}
+
+impl fmt::Display for DisplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MarkingLength { expected, actual } => {
+                write!(f, "marking has {actual} entries, expected {expected}")
+            }
+            Self::Io(err) => write!(f, "failed to write svg: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for DisplayError {}
+
+impl From<std::io::Error> for DisplayError {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
 // Define the ImageBuilder trait
 pub trait ImageBuilder {
     fn new_svg_image(&self, width: Option<i32>, height: Option<i32>);
@@ -16,8 +46,8 @@ pub trait ImageBuilder {
     fn group(&self);
     fn gend(&self);
     fn write_element(&self, element: String);
-    fn render(&self, initial_vectors: Vec<Vector>);
-    fn place(&self, label: String, place: &Place);
+    fn render(&self, initial_vectors: Vec<Vector>) -> Result<Vec<ModelIssue>, DisplayError>;
+    fn place(&self, label: String, place: &Place, tokens: i32);
     fn arc(&self, net: &PetriNet, arc: &Arrow);
     fn transition(&self, label: String, transition: &Transition);
     fn end(&self);
@@ -28,6 +58,8 @@ pub trait ImageOutput {
     fn encode_url_component(component: &str) -> String;
     fn to_base64_url(&self) -> String;
     fn to_data_url(&self) -> String;
+    /// Returns the SVG markup written to the buffer so far.
+    fn to_svg_string(&self) -> String;
     fn to_img_tag(&self) -> String;
     fn to_zblob(&self) -> Zblob;
     fn to_html(&self) -> String;
@@ -36,64 +68,448 @@ pub trait ImageOutput {
 pub struct Display {
     buffer: Arc<Mutex<Vec<u8>>>,
     model: Model,
+    legend: bool,
 }
 
 impl Display {
-    fn new(model: Model) -> Self {
+    /// Creates a display for the model with an empty buffer.
+    pub fn new(model: Model) -> Self {
         Self {
             model,
             buffer: Arc::new(Mutex::new(Vec::new())),
+            legend: false,
+        }
+    }
+
+    /// Turns on a boxed key to the right of the net explaining the place, transition, arc,
+    /// inhibitor, and token symbols.
+    #[must_use]
+    pub const fn with_legend(mut self, legend: bool) -> Self {
+        self.legend = legend;
+        self
+    }
+
+    /// Writes a complete SVG image of the net straight to `w`, without using the internal buffer.
+    ///
+    /// Tokens are drawn from `marking`, or the initial marking if `None`. Arcs that do not join
+    /// a place and a transition are left out and returned as issues.
+    pub fn render_to<W: Write>(
+        &self,
+        w: &mut W,
+        marking: Option<&Vector>,
+    ) -> Result<Vec<ModelIssue>, DisplayError> {
+        let marking = self.resolve_marking(marking.cloned())?;
+        write_svg_open(w, 400, 400)?;
+        let issues = write_net(w, &self.model.net, &marking)?;
+        write!(w, "</svg>")?;
+        Ok(issues)
+    }
+
+    fn resolve_marking(&self, marking: Option<Vector>) -> Result<Vector, DisplayError> {
+        let marking = marking.unwrap_or_else(|| self.model.vm.initial_vector());
+        let expected = self.model.vm.places.len();
+        if marking.len() == expected {
+            Ok(marking)
+        } else {
+            Err(DisplayError::MarkingLength {
+                expected,
+                actual: marking.len(),
+            })
+        }
+    }
+
+    /// Renders several models into one SVG, tiled `cols` to a row, each under a numbered title.
+    ///
+    /// Every model is drawn at its initial marking and shifted into a cell sized to fit the largest net.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the grid is too large to measure in `i32` pixels.
+    pub fn render_grid(models: &[Model], cols: usize) -> Result<String, DisplayError> {
+        const PADDING: i32 = 40;
+        const TITLE_HEIGHT: i32 = 20;
+
+        let bounds: Vec<(i32, i32, i32, i32)> = models.iter().map(|m| net_bounds(&m.net)).collect();
+        let cell_width = bounds.iter().map(|b| b.2 - b.0).max().unwrap_or(0) + 2 * PADDING;
+        let cell_height =
+            bounds.iter().map(|b| b.3 - b.1).max().unwrap_or(0) + 2 * PADDING + TITLE_HEIGHT;
+        let cols = cols.clamp(1, models.len().max(1));
+        let rows = models.len().div_ceil(cols);
+        let width = cell_width * i32::try_from(cols).expect("too many columns");
+        let height = cell_height * i32::try_from(rows).expect("too many rows");
+
+        let mut w: Vec<u8> = Vec::new();
+        write!(
+            w,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">"
+        )?;
+        write_defs(&mut w)?;
+        for (i, (model, (min_x, min_y, _, _))) in models.iter().zip(bounds).enumerate() {
+            let col = i32::try_from(i % cols).expect("too many columns");
+            let row = i32::try_from(i / cols).expect("too many rows");
+            let cell_x = col * cell_width;
+            let cell_y = row * cell_height;
+            let title = format!(
+                "{}: {} {}",
+                i + 1,
+                model.net.model_type,
+                model.net.to_zblob().ipfs_cid
+            );
+            write_text(
+                &mut w,
+                cell_x + 4,
+                cell_y + 16,
+                &title,
+                "font-size=\"small\" class=\"title\"",
+            )?;
+            let dx = cell_x + PADDING - min_x;
+            let dy = cell_y + PADDING + TITLE_HEIGHT - min_y;
+            write!(w, "<g transform=\"translate({dx},{dy})\">")?;
+            write_net(&mut w, &model.net, &model.vm.initial_vector())?;
+            write!(w, "</g>")?;
+        }
+        write!(w, "</svg>")?;
+        Ok(String::from_utf8(w).expect("svg is utf8"))
+    }
+}
+
+/// Returns the `(min_x, min_y, max_x, max_y)` box around the places and transitions of a net.
+fn net_bounds(net: &PetriNet) -> (i32, i32, i32, i32) {
+    let points: Vec<(i32, i32)> = net
+        .places
+        .values()
+        .map(|p| (p.x, p.y))
+        .chain(net.transitions.values().map(|t| (t.x, t.y)))
+        .collect();
+    let min_x = points.iter().map(|p| p.0).min().unwrap_or(0);
+    let min_y = points.iter().map(|p| p.1).min().unwrap_or(0);
+    let max_x = points.iter().map(|p| p.0).max().unwrap_or(0);
+    let max_y = points.iter().map(|p| p.1).max().unwrap_or(0);
+    (min_x, min_y, max_x, max_y)
+}
+
+/// Draws the symbol key in a box whose top-left corner is at `(x, y)`.
+fn write_legend<W: Write>(w: &mut W, x: i32, y: i32) -> std::io::Result<()> {
+    const ROW: i32 = 24;
+    write!(w, "<g class=\"legend\">")?;
+    write_rect(
+        w,
+        x,
+        y,
+        140,
+        5 * ROW + 12,
+        "stroke=\"#000000\" fill=\"#ffffff\" rx=\"4\"",
+    )?;
+    let (sx, tx) = (x + 20, x + 50);
+    let row = |i: i32| y + 18 + i * ROW;
+    write_circle(w, sx, row(0), 8, "stroke=\"#000000\" fill=\"#ffffff\"")?;
+    write_text(w, tx, row(0) + 4, "place", "font-size=\"small\"")?;
+    write_rect(
+        w,
+        sx - 8,
+        row(1) - 8,
+        16,
+        16,
+        "stroke=\"#000000\" fill=\"#ffffff\" rx=\"2\"",
+    )?;
+    write_text(w, tx, row(1) + 4, "transition", "font-size=\"small\"")?;
+    write_line(w, sx - 12, row(2), sx + 12, row(2), "stroke=\"#000000\"")?;
+    write!(
+        w,
+        "<path d=\"M{},{} L{},{} L{},{} Z\" fill=\"#000000\" />",
+        sx + 12,
+        row(2) - 4,
+        sx + 18,
+        row(2),
+        sx + 12,
+        row(2) + 4
+    )?;
+    write_text(w, tx, row(2) + 4, "arc", "font-size=\"small\"")?;
+    write_line(w, sx - 12, row(3), sx + 10, row(3), "stroke=\"#000000\"")?;
+    write_circle(w, sx + 14, row(3), 4, "stroke=\"#000000\" fill=\"#ffffff\"")?;
+    write_text(w, tx, row(3) + 4, "inhibitor", "font-size=\"small\"")?;
+    write_circle(w, sx, row(4), 8, "stroke=\"#000000\" fill=\"#ffffff\"")?;
+    write_circle(w, sx, row(4), 2, "fill=\"#000000\" stroke=\"#000000\"")?;
+    write_text(w, tx, row(4) + 4, "token", "font-size=\"small\"")?;
+    write!(w, "</g>")
+}
+
+/// Draws every arc, place, and transition in canonical order, returning the arcs that could not
+/// be drawn.
+fn write_net<W: Write>(
+    w: &mut W,
+    net: &PetriNet,
+    marking: &Vector,
+) -> std::io::Result<Vec<ModelIssue>> {
+    let mut issues = Vec::new();
+    for (index, arc) in net.canonical_arcs() {
+        if !write_arc(w, net, arc)? {
+            issues.push(ModelIssue::BadArcEndpoint {
+                index,
+                source: arc.source.clone(),
+                target: arc.target.clone(),
+            });
         }
     }
+    for (label, place) in net.canonical_places() {
+        let offset: usize = place.offset.try_into().expect("invalid offset");
+        write_place(w, label, place, marking[offset])?;
+    }
+    for (label, transition) in net.canonical_transitions() {
+        write_transition(w, label, transition)?;
+    }
+    Ok(issues)
+}
+
+fn write_svg_open<W: Write>(w: &mut W, width: i32, height: i32) -> std::io::Result<()> {
+    write!(
+        w,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\">"
+    )?;
+    write_defs(w)
+}
+
+fn write_defs<W: Write>(w: &mut W) -> std::io::Result<()> {
+    write!(
+        w,
+        "<defs><marker id=\"markerArrow1\" markerWidth=\"23\" markerHeight=\"13\" refX=\"31\" refY=\"6\" orient=\"auto\"><rect width=\"28\" height=\"3\" fill=\"white\" stroke=\"white\" x=\"3\" y=\"5\"/><path d=\"M2,2 L2,11 L10,6 L2,2\"/></marker><marker id=\"markerInhibit1\" markerWidth=\"23\" markerHeight=\"13\" refX=\"31\" refY=\"6\" orient=\"auto\"><rect width=\"28\" height=\"3\" fill=\"white\" stroke=\"white\" x=\"3\" y=\"5\"/><circle cx=\"5\" cy=\"6.5\" r=\"4\"/></marker></defs>"
+    )
+}
+
+fn write_rect<W: Write>(
+    w: &mut W,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    extra: &str,
+) -> std::io::Result<()> {
+    write!(
+        w,
+        "<rect x=\"{x}\" y=\"{y}\" width=\"{width}\" height=\"{height}\" {extra} />"
+    )
+}
+
+fn write_circle<W: Write>(
+    w: &mut W,
+    x: i32,
+    y: i32,
+    radius: i32,
+    extra: &str,
+) -> std::io::Result<()> {
+    write!(w, "<circle cx=\"{x}\" cy=\"{y}\" r=\"{radius}\" {extra} />")
+}
+
+fn write_text<W: Write>(w: &mut W, x: i32, y: i32, text: &str, extra: &str) -> std::io::Result<()> {
+    write!(w, "<text x=\"{x}\" y=\"{y}\" {extra}>{text}</text>")
+}
+
+fn write_line<W: Write>(
+    w: &mut W,
+    x1: i32,
+    y1: i32,
+    x2: i32,
+    y2: i32,
+    extra: &str,
+) -> std::io::Result<()> {
+    write!(
+        w,
+        "<line x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\" {extra} />"
+    )
+}
+
+fn write_place<W: Write>(
+    w: &mut W,
+    label: &str,
+    place: &Place,
+    tokens: i32,
+) -> std::io::Result<()> {
+    write!(w, "<g>")?;
+    write_circle(w, place.x, place.y, 16, "stroke-width=\"1.5\" fill=\"#ffffff\" stroke=\"#000000\" orient=\"0\" shapeRendering=\"auto\"")?;
+    write_text(w, place.x - 18, place.y - 20, label, "font-size=\"small\"")?;
+    // bounded places are marked with their capacity below the circle
+    if let Some(capacity) = place.capacity.filter(|&c| c > 0) {
+        write_text(
+            w,
+            place.x - 8,
+            place.y + 30,
+            &format!("c:{capacity}"),
+            "font-size=\"small\" class=\"capacity\"",
+        )?;
+    }
+    match tokens {
+        0 => {}
+        1 => write_circle(
+            w,
+            place.x,
+            place.y,
+            2,
+            "fill=\"#000000\" stroke=\"#000000\" orient=\"0\" class=\"tokens\"",
+        )?,
+        _ => write_text(
+            w,
+            place.x - 4,
+            place.y + 5,
+            &tokens.to_string(),
+            "font-size=\"large\" class=\"tokens\"",
+        )?,
+    }
+    write!(w, "</g>")
+}
+
+/// The gap in pixels between parallel arcs joining the same place and transition.
+const PARALLEL_ARC_SPACING: f64 = 8.0;
+
+/// Returns the place and transition an arc joins, in that order.
+fn arc_pair<'a>(net: &PetriNet, arc: &'a Arrow) -> (&'a str, &'a str) {
+    let (from, to) = net.arc_orientation(arc);
+    if net.places.contains_key(from) {
+        (from, to)
+    } else {
+        (to, from)
+    }
+}
+
+/// Finds an arc's lane among the arcs joining the same place and transition, in canonical order.
+///
+/// Returns the lane and how many lanes there are.
+fn arc_lane(net: &PetriNet, arc: &Arrow) -> (usize, usize) {
+    let pair = arc_pair(net, arc);
+    let parallel: Vec<&Arrow> = net
+        .canonical_arcs()
+        .into_iter()
+        .map(|(_, a)| a)
+        .filter(|a| arc_pair(net, a) == pair)
+        .collect();
+    let lane = parallel
+        .iter()
+        .position(|a| std::ptr::eq(*a, arc))
+        .or_else(|| parallel.iter().position(|a| *a == arc))
+        .unwrap_or(0);
+    (lane, parallel.len().max(1))
+}
+
+/// Shifts a lane perpendicular to the line from place to transition, centring the lanes on it.
+#[allow(clippy::cast_possible_truncation)]
+fn lane_offset(
+    place: (i32, i32),
+    transition: (i32, i32),
+    (lane, lanes): (usize, usize),
+) -> (i32, i32) {
+    let dx = f64::from(transition.0 - place.0);
+    let dy = f64::from(transition.1 - place.1);
+    let length = dx.hypot(dy);
+    let to_f64 = |n: usize| f64::from(u32::try_from(n).unwrap_or(u32::MAX));
+    if lanes < 2 || length < f64::EPSILON {
+        return (0, 0);
+    }
+    let shift = PARALLEL_ARC_SPACING * (to_f64(lane) - to_f64(lanes - 1) / 2.0);
+    // the shift is a few pixels, so rounding it always fits
+    (
+        (-dy / length * shift).round() as i32,
+        (dx / length * shift).round() as i32,
+    )
+}
+
+/// Draws an arc between its place and transition, or nothing if the endpoints don't resolve.
+///
+/// The arc follows `PetriNet::arc_orientation`, so guards point from place to transition.
+/// Arcs joining the same place and transition, such as a flow arc and a guard, are drawn side
+/// by side rather than on top of each other. Returns whether the arc was drawn.
+fn write_arc<W: Write>(w: &mut W, net: &PetriNet, arc: &Arrow) -> std::io::Result<bool> {
+    let (places, transitions) = (&net.places, &net.transitions);
+    let (place, transition) = arc_pair(net, arc);
+    let (Some(p), Some(t)) = (places.get(place), transitions.get(transition)) else {
+        return Ok(false);
+    };
+    let (ox, oy) = lane_offset((p.x, p.y), (t.x, t.y), arc_lane(net, arc));
+    let (p, t) = ((p.x + ox, p.y + oy), (t.x + ox, t.y + oy));
+    let ((x1, y1), (x2, y2)) = if net.arc_orientation(arc).0 == place {
+        (p, t)
+    } else {
+        (t, p)
+    };
+
+    write!(w, "<g>")?;
+    let inhibit = arc.inhibit.unwrap_or(false);
+    let marker = if inhibit {
+        "url(#markerInhibit1)"
+    } else {
+        "url(#markerArrow1)"
+    };
+    let extra = format!("stroke=\"#000000\" fill=\"#000000\" marker-end=\"{marker}\"");
+
+    write_line(w, x1, y1, x2, y2, &extra)?;
+    // a guard's weight is its threshold, so it is always shown; flow arcs only when above 1
+    let weight = arc.weight.unwrap_or(1);
+    if inhibit || weight > 1 {
+        write_text(
+            w,
+            i32::midpoint(x1, x2) + 4,
+            i32::midpoint(y1, y2) - 4,
+            &weight.to_string(),
+            "font-size=\"small\" class=\"weight\"",
+        )?;
+    }
+    write!(w, "</g>")?;
+    Ok(true)
+}
+
+fn write_transition<W: Write>(
+    w: &mut W,
+    label: &str,
+    transition: &Transition,
+) -> std::io::Result<()> {
+    write!(w, "<g>")?;
+    let x = transition.x - 17;
+    let y = transition.y - 17;
+    write_rect(
+        w,
+        x,
+        y,
+        30,
+        30,
+        "stroke=\"#000000\" fill=\"#ffffff\" rx=\"4\"",
+    )?;
+    write_text(w, x, y - 8, label, "font-size=\"small\"")?;
+    write!(w, "</g>")
 }
 
 // Implement the ImageBuilder trait for Display
 impl ImageBuilder for Display {
     fn new_svg_image(&self, width: Option<i32>, height: Option<i32>) {
-        let w = width.unwrap_or(400);
-        let h = height.unwrap_or(400);
-        let mut buffer = self.buffer.lock().unwrap();
-        write!(
-            buffer,
-            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\">"
-        )
-        .unwrap();
-        self.write_defs(&mut buffer);
+        let mut buffer = self.buffer.lock().expect("lock failed");
+        write_svg_open(&mut *buffer, width.unwrap_or(400), height.unwrap_or(400))
+            .expect("write failed");
+        drop(buffer);
     }
 
     fn write_defs(&self, buffer: &mut Vec<u8>) {
-        write!(
-            buffer,
-            "<defs><marker id=\"markerArrow1\" markerWidth=\"23\" markerHeight=\"13\" refX=\"31\" refY=\"6\" orient=\"auto\"><rect width=\"28\" height=\"3\" fill=\"white\" stroke=\"white\" x=\"3\" y=\"5\"/><path d=\"M2,2 L2,11 L10,6 L2,2\"/></marker><marker id=\"markerInhibit1\" markerWidth=\"23\" markerHeight=\"13\" refX=\"31\" refY=\"6\" orient=\"auto\"><rect width=\"28\" height=\"3\" fill=\"white\" stroke=\"white\" x=\"3\" y=\"5\"/><circle cx=\"5\" cy=\"6.5\" r=\"4\"/></marker></defs>"
-        ).unwrap();
+        write_defs(buffer).expect("write failed");
     }
 
     fn rect(&self, x: i32, y: i32, width: i32, height: i32, extra: &str) {
-        self.write_element(format!(
-            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" {} />",
-            x, y, width, height, extra
-        ));
+        let mut buffer = self.buffer.lock().expect("lock failed");
+        write_rect(&mut *buffer, x, y, width, height, extra).expect("write failed");
+        drop(buffer);
     }
 
     fn circle(&self, x: i32, y: i32, radius: i32, extra: &str) {
-        self.write_element(format!(
-            "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" {} />",
-            x, y, radius, extra
-        ));
+        let mut buffer = self.buffer.lock().expect("lock failed");
+        write_circle(&mut *buffer, x, y, radius, extra).expect("write failed");
+        drop(buffer);
     }
 
     fn text(&self, x: i32, y: i32, text: &str, extra: &str) {
-        self.write_element(format!(
-            "<text x=\"{}\" y=\"{}\" {}>{}</text>",
-            x, y, extra, text
-        ));
+        let mut buffer = self.buffer.lock().expect("lock failed");
+        write_text(&mut *buffer, x, y, text, extra).expect("write failed");
+        drop(buffer);
     }
 
     fn line(&self, x1: i32, y1: i32, x2: i32, y2: i32, extra: &str) {
-        self.write_element(format!(
-            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" {} />",
-            x1, y1, x2, y2, extra
-        ));
+        let mut buffer = self.buffer.lock().expect("lock failed");
+        write_line(&mut *buffer, x1, y1, x2, y2, extra).expect("write failed");
+        drop(buffer);
     }
 
     fn group(&self) {
@@ -105,72 +521,49 @@ impl ImageBuilder for Display {
     }
 
     fn write_element(&self, element: String) {
-        let mut buffer = self.buffer.lock().unwrap();
-        write!(buffer, "{}", element).unwrap();
+        let mut buffer = self.buffer.lock().expect("lock failed");
+        write!(buffer, "{element}").expect("write failed");
+        drop(buffer);
     }
 
-    fn render(&self, _initial_vectors: Vec<Vector>) {
-        let net = &self.model.net;
-        for arc in &net.arcs {
-            self.arc(&net, arc);
-        }
-        for (label, place) in &net.places {
-            self.place(label.to_string(), place);
-        }
-        for (label, transition) in &net.transitions {
-            self.transition(label.to_string(), transition);
+    /// Renders the net with tokens drawn from the first vector, or the initial marking if none is given.
+    ///
+    /// Arcs that do not join a place and a transition are left out and returned as issues.
+    fn render(&self, initial_vectors: Vec<Vector>) -> Result<Vec<ModelIssue>, DisplayError> {
+        let marking = self.resolve_marking(initial_vectors.into_iter().next())?;
+        let mut buffer = self.buffer.lock().expect("lock failed");
+        let issues = write_net(&mut *buffer, &self.model.net, &marking)?;
+        if self.legend {
+            let (_, min_y, max_x, _) = net_bounds(&self.model.net);
+            write_legend(&mut *buffer, max_x + 40, min_y - 20)?;
         }
+        drop(buffer);
         self.end();
+        Ok(issues)
     }
 
-    fn place(&self, label: String, place: &Place) {
-        self.group();
-        self.circle(place.x, place.y, 16, "stroke-width=\"1.5\" fill=\"#ffffff\" stroke=\"#000000\" orient=\"0\" shapeRendering=\"auto\"");
-        self.text(place.x - 18, place.y - 20, &label, "font-size=\"small\"");
-        self.gend();
+    fn place(&self, label: String, place: &Place, tokens: i32) {
+        let mut buffer = self.buffer.lock().expect("lock failed");
+        write_place(&mut *buffer, &label, place, tokens).expect("write failed");
+        drop(buffer);
     }
 
     fn arc(&self, net: &PetriNet, arc: &Arrow) {
-        self.group();
-        let marker = if arc.inhibit.unwrap_or(false) {
-            "url(#markerInhibit1)"
-        } else {
-            "url(#markerArrow1)"
-        };
-        let extra = format!(
-            "stroke=\"#000000\" fill=\"#000000\" marker-end=\"{}\"",
-            marker
-        );
-
-        let place = net.places.get(&arc.source);
-        let transition = net.transitions.get(&arc.target);
-        if place.is_none() {
-            let p = net.places.get(&arc.target).expect("Place not found");
-            let t = net
-                .transitions
-                .get(&arc.source)
-                .expect("Transition not found");
-            self.line(t.x, t.y, p.x, p.y, &extra);
-        } else {
-            let p = place.expect("Place not found");
-            let t = transition.expect("Transition not found");
-            self.line(p.x, p.y, t.x, t.y, &extra);
-        }
-        self.gend();
+        let mut buffer = self.buffer.lock().expect("lock failed");
+        write_arc(&mut *buffer, net, arc).expect("write failed");
+        drop(buffer);
     }
 
     fn transition(&self, label: String, transition: &Transition) {
-        self.group();
-        let x = transition.x - 17;
-        let y = transition.y - 17;
-        self.rect(x, y, 30, 30, "stroke=\"#000000\" fill=\"#ffffff\" rx=\"4\"");
-        self.text(x, y - 8, &label, "font-size=\"small\"");
-        self.gend();
+        let mut buffer = self.buffer.lock().expect("lock failed");
+        write_transition(&mut *buffer, &label, transition).expect("write failed");
+        drop(buffer);
     }
 
     fn end(&self) {
-        let mut buffer = self.buffer.lock().unwrap();
-        write!(buffer, "</svg>").unwrap();
+        let mut buffer = self.buffer.lock().expect("lock failed");
+        write!(buffer, "</svg>").expect("write failed");
+        drop(buffer);
     }
 }
 
@@ -216,15 +609,20 @@ impl ImageOutput for Display {
     }
 
     fn to_base64_url(&self) -> String {
-        let buffer = self.buffer.lock().unwrap().clone();
-        let base64_svg = base64::engine::general_purpose::STANDARD.encode(&buffer);
-        format!("data:image/svg+xml;base64,{}", base64_svg)
+        let buffer = self.buffer.lock().expect("lock failed").clone();
+        let base64_svg = base64::engine::general_purpose::STANDARD.encode(buffer);
+        format!("data:image/svg+xml;base64,{base64_svg}")
     }
 
     fn to_data_url(&self) -> String {
-        let buffer = self.buffer.lock().unwrap();
+        let buffer = self.buffer.lock().expect("lock failed").clone();
         let encoded = Self::encode_url_component(&String::from_utf8_lossy(&buffer));
-        format!("data:image/svg+xml,{}", encoded)
+        format!("data:image/svg+xml,{encoded}")
+    }
+
+    fn to_svg_string(&self) -> String {
+        let buffer = self.buffer.lock().expect("lock failed");
+        String::from_utf8_lossy(&buffer).into_owned()
     }
 
     fn to_img_tag(&self) -> String {
@@ -232,7 +630,7 @@ impl ImageOutput for Display {
     }
 
     fn to_zblob(&self) -> Zblob {
-        self.model.net.to_zblob()
+        self.model.to_zblob()
     }
 
     fn to_html(&self) -> String {
@@ -240,7 +638,7 @@ impl ImageOutput for Display {
         let cid = zblob.ipfs_cid;
         let zipped_data = zblob.base64_zipped;
         let image = self.to_img_tag();
-        let pretty_json = serde_json::to_string_pretty(&self.model.net.to_json().unwrap()).unwrap();
+        let pretty_json = self.model.net.to_json_pretty().expect("json fault");
         let model_type = self.model.net.model_type.clone();
 
         format!(
@@ -289,12 +687,243 @@ mod tests {
 
         let svg = Display::new(model);
         svg.new_svg_image(None, None);
-        svg.render(Vec::new());
+        svg.render(Vec::new()).expect("render failed");
         println!("{}", svg.to_html());
 
         if std::env::var("WRITE_TO_FILE").is_ok() {
-            let mut file = std::fs::File::create("/tmp/test.html").unwrap();
-            file.write_all(svg.to_html().as_bytes()).unwrap();
+            let mut file = std::fs::File::create("/tmp/test.html").expect("create failed");
+            file.write_all(svg.to_html().as_bytes())
+                .expect("write failed");
+        }
+    }
+
+    #[test]
+    fn test_render_marking() {
+        let model = Model::new(|p| {
+            p.cell("start", Option::from(1), None, 100, 100);
+            p.cell("middle", None, None, 200, 100);
+            p.cell("end", None, None, 300, 100);
+            p.func("step", "default", 150, 100);
+            p.func("double", "default", 250, 100);
+            p.arrow("start", "step", 1);
+            p.arrow("step", "middle", 1);
+            p.arrow("middle", "double", 1);
+            p.arrow("double", "end", 2);
+        });
+        let vm = &model.vm;
+        let state = vm.transform(&vm.initial_vector(), "step", 1).output;
+        assert_eq!(state, vec![0, 1, 0]);
+
+        let svg = Display::new(model.clone());
+        svg.render(vec![state.clone()]).expect("render failed");
+        let out =
+            String::from_utf8(svg.buffer.lock().expect("lock failed").clone()).expect("valid utf8");
+        assert!(!out.contains("<circle cx=\"100\" cy=\"100\" r=\"2\""));
+        assert!(out.contains("<circle cx=\"200\" cy=\"100\" r=\"2\""));
+
+        let svg = Display::new(model.clone());
+        let state = vm.transform(&state, "double", 1).output;
+        svg.render(vec![state]).expect("render failed");
+        let out =
+            String::from_utf8(svg.buffer.lock().expect("lock failed").clone()).expect("valid utf8");
+        assert!(!out.contains("<circle cx=\"200\" cy=\"100\" r=\"2\""));
+        assert!(
+            out.contains("<text x=\"296\" y=\"105\" font-size=\"large\" class=\"tokens\">2</text>")
+        );
+
+        let svg = Display::new(model);
+        assert!(matches!(
+            svg.render(vec![vec![1, 0]]),
+            Err(DisplayError::MarkingLength {
+                expected: 3,
+                actual: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_render_capacity() {
+        let model = Model::new(|p| {
+            p.cell("bounded", Option::from(1), Option::from(3), 100, 100);
+            p.cell("unbounded", None, None, 200, 100);
+            p.func("fill", "default", 150, 100);
+            p.arrow("fill", "bounded", 1);
+            p.arrow("fill", "unbounded", 1);
+        });
+        let svg = Display::new(model);
+        svg.render(vec![]).expect("render failed");
+        let out =
+            String::from_utf8(svg.buffer.lock().expect("lock failed").clone()).expect("valid utf8");
+        let group = |label: &str| {
+            out.split("<g>")
+                .find(|g| g.contains(&format!(">{label}</text>")))
+                .expect(label)
+                .to_string()
+        };
+        assert!(group("bounded").contains(
+            "<text x=\"92\" y=\"130\" font-size=\"small\" class=\"capacity\">c:3</text>"
+        ));
+        assert!(!group("unbounded").contains("class=\"capacity\""));
+    }
+
+    #[test]
+    fn test_render_legend() {
+        let model = Model::new(|p| {
+            p.cell("start", Option::from(1), None, 100, 100);
+            p.func("step", "default", 150, 100);
+            p.arrow("start", "step", 1);
+        });
+
+        let plain = Display::new(model.clone());
+        plain.render(Vec::new()).expect("render failed");
+        let out = String::from_utf8(plain.buffer.lock().expect("lock failed").clone())
+            .expect("valid utf8");
+        assert!(!out.contains("legend"));
+
+        let keyed = Display::new(model).with_legend(true);
+        keyed.render(Vec::new()).expect("render failed");
+        let out = String::from_utf8(keyed.buffer.lock().expect("lock failed").clone())
+            .expect("valid utf8");
+        assert!(out.contains("<g class=\"legend\"><rect x=\"190\" y=\"80\""));
+        for label in ["place", "transition", "arc", "inhibitor", "token"] {
+            assert!(out.contains(&format!(">{label}</text>")), "missing {label}");
+        }
+    }
+
+    #[test]
+    fn test_render_dangling_arc() {
+        let mut model = Model::new(|p| {
+            p.cell("start", Option::from(1), None, 100, 100);
+            p.func("step", "default", 150, 100);
+            p.arrow("start", "step", 1);
+        });
+        model.net.arcs.push(Arrow {
+            source: "step".to_string(),
+            target: "nowhere".to_string(),
+            weight: Some(1),
+            consume: None,
+            produce: None,
+            inhibit: None,
+            read: None,
+            group: None,
+        });
+
+        let svg = Display::new(model);
+        let issues = svg.render(Vec::new()).expect("render failed");
+        assert_eq!(
+            issues,
+            vec![ModelIssue::BadArcEndpoint {
+                index: 1,
+                source: "step".to_string(),
+                target: "nowhere".to_string(),
+            }]
+        );
+        let out =
+            String::from_utf8(svg.buffer.lock().expect("lock failed").clone()).expect("valid utf8");
+        assert_eq!(out.matches("<line").count(), 1);
+    }
+
+    #[test]
+    fn test_render_arc_weights() {
+        let model = Model::new(|p| {
+            p.cell("place0", Option::from(0), Option::from(3), 100, 180);
+            p.func("txn0", "default", 20, 100);
+            p.func("txn2", "default", 20, 260);
+            p.arrow("txn0", "place0", 1);
+            p.guard("txn2", "place0", 3);
+        });
+        let svg = Display::new(model);
+        svg.render(Vec::new()).expect("render failed");
+        let out =
+            String::from_utf8(svg.buffer.lock().expect("lock failed").clone()).expect("valid utf8");
+        assert!(out.contains(
+            "url(#markerInhibit1)\" /><text x=\"64\" y=\"216\" font-size=\"small\" class=\"weight\">3</text>"
+        ));
+        assert_eq!(out.matches("class=\"weight\"").count(), 1);
+    }
+
+    #[test]
+    fn test_parallel_arcs_are_offset() {
+        let model = Model::new(|p| {
+            p.cell("place0", Option::from(1), None, 100, 100);
+            p.func("txn0", "default", 200, 100);
+            p.arrow("place0", "txn0", 1);
+            p.guard("place0", "txn0", 2);
+        });
+        let svg = model.to_svg(None);
+        let lines: Vec<&str> = svg
+            .split("<line ")
+            .skip(1)
+            .map(|line| line.split(" stroke=").next().expect("coordinates"))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "x1=\"100\" y1=\"96\" x2=\"200\" y2=\"96\"",
+                "x1=\"100\" y1=\"104\" x2=\"200\" y2=\"104\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_exports_are_deterministic() {
+        let export = || {
+            let model = Model::from_json_str(crate::petri_net::tests::DINING_PHILOSOPHERS);
+            let mut svg: Vec<u8> = Vec::new();
+            Display::new(model.clone())
+                .render_to(&mut svg, None)
+                .expect("render failed");
+            (svg, model.net.to_dsl_source())
+        };
+        let first = export();
+        for _ in 0..8 {
+            assert_eq!(export(), first);
+        }
+    }
+
+    #[test]
+    fn test_render_to_writer() {
+        let model = Model::from_json_str(crate::petri_net::tests::DINING_PHILOSOPHERS);
+        let marking = model
+            .vm
+            .transform(&model.vm.initial_vector(), "eat1", 1)
+            .output;
+
+        let svg = Display::new(model);
+        svg.new_svg_image(None, None);
+        svg.render(vec![marking.clone()]).expect("render failed");
+        let buffered = svg.buffer.lock().expect("lock failed").clone();
+
+        let mut sink: Vec<u8> = Vec::new();
+        svg.render_to(&mut sink, Some(&marking))
+            .expect("render failed");
+        assert_eq!(sink, buffered);
+    }
+
+    #[test]
+    fn test_render_grid() {
+        let coffee = || {
+            Model::new(|p| {
+                p.cell("Water", Option::from(1), Option::from(1), 100, 200);
+                p.cell("BoiledWater", None, Option::from(1), 260, 200);
+                p.cell("Cup", Option::from(1), Option::from(1), 900, 200);
+                p.func("boil_water", "default", 191, 489);
+                p.func("pour_coffee", "default", 820, 200);
+                p.arrow("Water", "boil_water", 1);
+                p.arrow("boil_water", "BoiledWater", 1);
+                p.arrow("BoiledWater", "pour_coffee", 1);
+                p.arrow("Cup", "pour_coffee", 1);
+            })
+        };
+        let svg = Display::render_grid(&[coffee(), coffee()], 2).expect("render failed");
+
+        assert!(svg.contains("viewBox=\"0 0 1760 389\""));
+        assert!(svg.contains(">1: petriNet zb2"));
+        assert!(svg.contains(">2: petriNet zb2"));
+        for place in ["Water", "BoiledWater", "Cup"] {
+            assert_eq!(svg.matches(&format!(">{place}</text>")).count(), 2);
         }
+        assert!(svg.contains("<g transform=\"translate(-60,-140)\">"));
+        assert!(svg.contains("<g transform=\"translate(820,-140)\">"));
     }
 }
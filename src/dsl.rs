@@ -1,3 +1,4 @@
+use crate::error::ModelError;
 use crate::petri_net::PetriNet;
 use crate::vasm::StateMachine;
 
@@ -7,10 +8,19 @@ use crate::vasm::StateMachine;
 /// # Methods
 ///
 /// * `model_type` - Sets the model type of the Petri net.
+/// * `version` - Sets the JSON format version the Petri net declares.
 /// * `cell` - Adds a cell (place) to the Petri net.
 /// * `func` - Adds a function (transition) to the Petri net.
 /// * `arrow` - Adds an arrow (arc) from a source to a target in the Petri net.
 /// * `guard` - Adds a guard (inhibitor arc) from a source to a target in the Petri net.
+/// * `guard_any` - Adds a guard that only needs one of its named group to be satisfied.
+/// * `cell_auto` - Adds a cell (place) with automatically assigned coordinates.
+/// * `func_auto` - Adds a function (transition) with automatically assigned coordinates.
+/// * `cell_unbounded` - Adds a cell (place) with no capacity limit.
+/// * `cell_bounded` - Adds a cell (place) with an explicit capacity, which may be zero.
+/// * `fan_in` - Adds an arrow from each of several cells into one function.
+/// * `fan_out` - Adds an arrow from one function into each of several cells.
+/// * `servers` - Limits how many firings of a function may be in flight at once.
 ///
 /// # Example
 ///
@@ -34,7 +44,13 @@ use crate::vasm::StateMachine;
 pub trait Dsl {
     /// Sets the model type of the Petri net.
     fn model_type(&mut self, model_type: &str);
+    /// Sets the JSON format version the Petri net declares, such as `"v1"`.
+    ///
+    /// The default ignores the version.
+    fn version(&mut self, _version: &str) {}
     /// Adds a cell (place) to the Petri net.
+    ///
+    /// A capacity of `None` or `Some(0)` means unbounded; use `cell_bounded` for a zero capacity.
     fn cell<'a>(
         &mut self,
         label: &'a str,
@@ -49,8 +65,63 @@ pub trait Dsl {
     fn arrow(&mut self, source: &str, target: &str, weight: i32);
     /// Adds a guard (inhibitor arc) from a source to a target in the Petri net.
     fn guard(&mut self, source: &str, target: &str, weight: i32);
+    /// Adds a guard that belongs to a named OR-group.
+    ///
+    /// A transition's guards sharing a group are satisfied when any one of them is, while
+    /// ungrouped guards must all be satisfied. The default ignores the group and adds a plain
+    /// `guard`, for implementors that cannot store groups.
+    fn guard_any(&mut self, _group: &str, source: &str, target: &str, weight: i32) {
+        self.guard(source, target, weight);
+    }
+    /// Adds a cell (place) to the Petri net, placed at the next grid position.
+    ///
+    /// The default places every cell at the origin; implementors that track a layout override it.
+    fn cell_auto<'a>(
+        &mut self,
+        label: &'a str,
+        initial: Option<i32>,
+        capacity: Option<i32>,
+    ) -> &'a str {
+        self.cell(label, initial, capacity, 0, 0)
+    }
+    /// Adds a function (transition) to the Petri net, placed at the next grid position.
+    ///
+    /// The default places every function at the origin; implementors that track a layout override it.
+    fn func_auto<'a>(&mut self, label: &'a str, role: &str) -> &'a str {
+        self.func(label, role, 0, 0)
+    }
+    /// Adds a cell (place) that can hold any number of tokens, placed at the next grid position.
+    fn cell_unbounded<'a>(&mut self, label: &'a str, initial: Option<i32>) -> &'a str;
+    /// Adds a cell (place) holding at most `capacity` tokens, placed at the next grid position.
+    ///
+    /// Unlike `cell`, a capacity of 0 is kept as a place that can never receive tokens.
+    fn cell_bounded<'a>(&mut self, label: &'a str, initial: Option<i32>, capacity: i32) -> &'a str;
+    /// Gives a function (transition) `count` servers, each held from its firing until
+    /// `completion` fires.
+    ///
+    /// This is distinct from place capacity: the servers live in an implicit place of the state
+    /// machine. The default leaves the function unlimited, for implementors that cannot store
+    /// servers.
+    fn servers(&mut self, _transition: &str, _count: u32, _completion: &str) {}
+    /// Adds an arrow from each `(source, weight)` pair into `target`, in order.
+    fn fan_in(&mut self, sources: &[(&str, i32)], target: &str) {
+        for &(source, weight) in sources {
+            self.arrow(source, target, weight);
+        }
+    }
+    /// Adds an arrow from `source` into each `(target, weight)` pair, in order.
+    fn fan_out(&mut self, source: &str, targets: &[(&str, i32)]) {
+        for &(target, weight) in targets {
+            self.arrow(source, target, weight);
+        }
+    }
 }
 
+/// Horizontal spacing between nodes placed by `cell_auto` and `func_auto`.
+const AUTO_GRID: i32 = 80;
+/// Row used for nodes placed by `cell_auto` and `func_auto`.
+const AUTO_ROW: i32 = 200;
+
 /// `Builder` is a struct that implements the `FlowDsl` trait and is used to build a Petri net.
 /// It contains a mutable reference to a `PetriNet` object which it modifies as methods are called on it.
 ///
@@ -58,9 +129,11 @@ pub trait Dsl {
 ///
 /// * `new` - Creates a new `Builder` object.
 /// * `as_vasm` - Converts the `PetriNet` object into a `StateMachine` object.
+/// * `finish` - Checks every arc endpoint was declared, then converts like `as_vasm`.
 ///
 pub struct Builder<'a> {
     pub net: &'a mut PetriNet,
+    cursor: i32,
 }
 
 impl<'a> Builder<'a> {
@@ -74,8 +147,14 @@ impl<'a> Builder<'a> {
     ///
     /// * A new `Builder` object.
     ///
-    pub fn new(net: &'a mut PetriNet) -> Self {
-        Self { net }
+    pub const fn new(net: &'a mut PetriNet) -> Self {
+        Self { net, cursor: 20 }
+    }
+
+    /// Advances the layout cursor and returns the next free coordinates.
+    const fn next_position(&mut self) -> (i32, i32) {
+        self.cursor += AUTO_GRID;
+        (self.cursor, AUTO_ROW)
     }
 
     /// Converts the `PetriNet` object into a `StateMachine` object.
@@ -87,6 +166,20 @@ impl<'a> Builder<'a> {
     pub fn as_vasm(&mut self) -> StateMachine {
         StateMachine::from_model(self.net)
     }
+
+    /// Converts the `PetriNet` object into a `StateMachine` object, reporting mistakes instead of panicking.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ModelError::UndeclaredNode` naming the first arc endpoint that is neither a
+    /// declared cell nor a declared func, `ModelError::InvalidArc` for an arc that does not join
+    /// a cell and a func, or any error from `StateMachine::try_from_model`.
+    pub fn finish(&mut self) -> Result<StateMachine, ModelError> {
+        for arc in &self.net.arcs {
+            self.net.check_arc_endpoints(arc)?;
+        }
+        StateMachine::try_from_model(self.net)
+    }
 }
 
 impl Dsl for Builder<'_> {
@@ -99,6 +192,12 @@ impl Dsl for Builder<'_> {
         }
     }
 
+    fn version(&mut self, version: &str) {
+        if let Err(err) = self.net.set_version(version) {
+            panic!("{err}");
+        }
+    }
+
     fn cell<'b>(
         &mut self,
         label: &'b str,
@@ -127,6 +226,7 @@ impl Dsl for Builder<'_> {
             produce: None,
             inhibit: None,
             read: None,
+            group: None,
         });
     }
 
@@ -140,8 +240,64 @@ impl Dsl for Builder<'_> {
             produce: None,
             inhibit: Some(true),
             read: None,
+            group: None,
         });
     }
+
+    fn guard_any(&mut self, group: &str, source: &str, target: &str, weight: i32) {
+        assert!(weight > 0, "weight must be positive");
+        self.net.add_arc(ArcParams {
+            source,
+            target,
+            weight: Some(weight),
+            consume: None,
+            produce: None,
+            inhibit: Some(true),
+            read: None,
+            group: Some(group),
+        });
+    }
+
+    fn cell_auto<'b>(
+        &mut self,
+        label: &'b str,
+        initial: Option<i32>,
+        capacity: Option<i32>,
+    ) -> &'b str {
+        let (x, y) = self.next_position();
+        self.cell(label, initial, capacity, x, y)
+    }
+
+    fn func_auto<'b>(&mut self, label: &'b str, role: &str) -> &'b str {
+        let (x, y) = self.next_position();
+        self.func(label, role, x, y)
+    }
+
+    fn cell_unbounded<'b>(&mut self, label: &'b str, initial: Option<i32>) -> &'b str {
+        self.cell_auto(label, initial, None)
+    }
+
+    fn cell_bounded<'b>(&mut self, label: &'b str, initial: Option<i32>, capacity: i32) -> &'b str {
+        assert!(capacity >= 0, "capacity must not be negative");
+        let (x, y) = self.next_position();
+        let offset: i32 = self.net.places.len().try_into().expect("too many places");
+        self.net
+            .add_place(label, offset, initial, Some(capacity), x, y);
+        if let Some(place) = self.net.places.get_mut(label) {
+            place.zero_capacity = capacity == 0;
+        }
+        label
+    }
+
+    fn servers(&mut self, transition: &str, count: u32, completion: &str) {
+        let t = self
+            .net
+            .transitions
+            .get_mut(transition)
+            .expect("servers must follow the func they limit");
+        t.server_count = Some(count);
+        t.completed_by = Some(completion.to_string());
+    }
 }
 
 pub struct ArcParams<'a> {
@@ -152,10 +308,11 @@ pub struct ArcParams<'a> {
     pub produce: Option<bool>,
     pub inhibit: Option<bool>,
     pub read: Option<bool>,
+    pub group: Option<&'a str>,
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use crate::model::Model;
     use crate::vasm::{Tx, Vasm};
 
@@ -213,7 +370,7 @@ mod tests {
         }
     }
 
-    fn model_test_code(p: &mut dyn Dsl) {
+    pub fn model_test_code(p: &mut dyn Dsl) {
         p.model_type("petriNet");
 
         let r = "default";
@@ -229,6 +386,29 @@ mod tests {
         p.guard(foo, baz, 1);
     }
 
+    #[test]
+    fn test_auto_coordinates() {
+        let model = Model::new(|p| {
+            let water = p.cell_auto("water", Option::from(1), None);
+            let boil = p.func_auto("boil", "default");
+            let steam = p.cell_auto("steam", None, None);
+            p.arrow(water, boil, 1);
+            p.arrow(boil, steam, 1);
+        });
+        let net = &model.net;
+        let mut coords: Vec<(i32, i32)> = net.places.values().map(|p| (p.x, p.y)).collect();
+        coords.extend(net.transitions.values().map(|t| (t.x, t.y)));
+        coords.sort_unstable();
+        assert_eq!(coords.len(), 3);
+        for pair in coords.windows(2) {
+            assert!(
+                pair[1].0 - pair[0].0 >= AUTO_GRID,
+                "nodes overlap: {pair:?}"
+            );
+        }
+        assert_eq!(model.vm.initial_vector(), vec![1, 0]);
+    }
+
     #[test]
     fn test_loading_dsl() {
         let m = &mut TestModel::new();
@@ -248,4 +428,142 @@ mod tests {
         m.assert_underflow("dec");
         m.assert_pass("baz"); // enabled
     }
+
+    #[test]
+    fn test_finish_reports_undeclared_cell() {
+        let mut net = PetriNet::new();
+        let mut builder = Builder::new(&mut net);
+        builder.cell("Water", Option::from(1), None, 100, 100);
+        builder.func("boil", "default", 200, 100);
+        builder.arrow("Water", "boil", 1);
+        builder.arrow("boil", "BoiledWatter", 1);
+
+        let err = builder.finish().expect_err("typo should be caught");
+        assert_eq!(
+            err,
+            ModelError::UndeclaredNode {
+                source: "boil".to_string(),
+                target: "BoiledWatter".to_string(),
+                missing: "BoiledWatter".to_string(),
+            }
+        );
+        assert!(err.to_string().contains("BoiledWatter"));
+
+        builder.cell("BoiledWatter", None, None, 300, 100);
+        assert!(builder.finish().is_ok());
+    }
+
+    fn brew_cells(p: &mut dyn Dsl) {
+        p.cell("BoiledWater", Option::from(1), None, 260, 200);
+        p.cell("GroundCoffee", Option::from(1), None, 541, 469);
+        p.cell("Filter", Option::from(1), None, 660, 200);
+        p.cell("CoffeeInPot", None, None, 740, 200);
+        p.cell("Grounds", None, None, 740, 300);
+        p.func("brew_coffee", "default", 548, 118);
+    }
+
+    #[test]
+    fn test_version() {
+        let m = Model::new(|p| {
+            p.version("v1");
+            p.cell("p", Option::from(1), None, 0, 0);
+        });
+        let json = m.net.to_json().expect("json");
+        assert_eq!(json["version"], "v1");
+        let read = PetriNet::from_json_value(json).expect("reads back");
+        assert_eq!(read.version, "v1");
+        assert_eq!(read.places["p"].initial, Some(1));
+
+        let mut net = m.net;
+        assert_eq!(
+            net.set_version("v2"),
+            Err(ModelError::UnsupportedVersion("v2".to_string()))
+        );
+        assert_eq!(net.version, "v1");
+        assert_eq!(net.set_version("v0"), Ok(()));
+        assert_eq!(net.version, "v0");
+    }
+
+    #[test]
+    fn test_fan_in_and_fan_out() {
+        let explicit = Model::new(|p| {
+            brew_cells(p);
+            p.arrow("BoiledWater", "brew_coffee", 1);
+            p.arrow("GroundCoffee", "brew_coffee", 1);
+            p.arrow("Filter", "brew_coffee", 1);
+            p.arrow("brew_coffee", "CoffeeInPot", 1);
+            p.arrow("brew_coffee", "Grounds", 2);
+        });
+        let sugared = Model::new(|p| {
+            brew_cells(p);
+            p.fan_in(
+                &[("BoiledWater", 1), ("GroundCoffee", 1), ("Filter", 1)],
+                "brew_coffee",
+            );
+            p.fan_out("brew_coffee", &[("CoffeeInPot", 1), ("Grounds", 2)]);
+        });
+        assert_eq!(sugared.net.in_arcs("brew_coffee").len(), 3);
+        assert_eq!(sugared.net.arcs, explicit.net.arcs);
+    }
+
+    #[test]
+    fn test_guard_any_group() {
+        let m = Model::new(|p| {
+            p.cell("sensorA", None, None, 100, 50);
+            p.cell("sensorB", None, None, 100, 150);
+            p.cell("door", Option::from(1), None, 200, 100);
+            p.cell("alarms", None, None, 400, 100);
+            p.func("alarm", "default", 300, 100);
+            p.func("trip_b", "default", 50, 150);
+            p.arrow("door", "alarm", 1);
+            p.arrow("alarm", "alarms", 1);
+            p.arrow("trip_b", "sensorB", 1);
+            p.guard_any("sensor", "alarm", "sensorA", 1);
+            p.guard_any("sensor", "alarm", "sensorB", 1);
+        });
+        let vm = &m.vm;
+        let state = vm.initial_vector();
+
+        let res = vm.transform(&state, "alarm", 1);
+        assert!(res.is_err());
+        assert!(res.inhibited);
+
+        let state = vm.transform(&state, "trip_b", 1).output;
+        let res = vm.transform(&state, "alarm", 1);
+        assert!(res.is_ok(), "one satisfied sensor should enable alarm");
+        assert_eq!(res.output, vec![0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_unbounded_and_bounded_zero_cells() {
+        let model = Model::new(|p| {
+            let open = p.cell_unbounded("open", None);
+            let closed = p.cell_bounded("closed", None, 0);
+            let legacy = p.cell_auto("legacy", None, Option::from(0));
+            let fill = p.func_auto("fill", "default");
+            let block = p.func_auto("block", "default");
+            p.arrow(fill, open, 1);
+            p.arrow(fill, legacy, 1);
+            p.arrow(block, closed, 1);
+        });
+        assert_eq!(model.net.places["closed"].capacity, Some(0));
+        assert!(model.net.places["closed"].zero_capacity);
+        assert_eq!(model.net.places["legacy"].capacity, Some(0));
+        assert!(!model.net.places["legacy"].zero_capacity);
+        assert_eq!(model.vm.capacity, vec![0, 0, 0]);
+        assert_eq!(model.vm.zero_capacity, vec![false, true, false]);
+
+        let vm = &model.vm;
+        let mut state = vm.initial_vector();
+        for _ in 0..1000 {
+            let res = vm.transform(&state, "fill", 1);
+            assert!(res.is_ok(), "unbounded place overflowed");
+            state = res.output;
+        }
+        assert_eq!(state, vec![1000, 0, 1000]);
+
+        let res = vm.transform(&state, "block", 1);
+        assert!(res.is_err());
+        assert!(res.overflow);
+    }
 }
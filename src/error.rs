@@ -0,0 +1,91 @@
+use std::fmt;
+
+use crate::vasm::ModelTypeError;
+
+/// `ModelError` is returned when a `PetriNet` cannot be compiled into a `StateMachine`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelError {
+    /// The net's model type is not one of the known `ModelType` values.
+    UnknownModelType(ModelTypeError),
+    /// An arc carries a contradictory combination of consume, produce, inhibit, and read flags.
+    InvalidArc {
+        source: String,
+        target: String,
+        reason: String,
+    },
+    /// The JSON could not be parsed as a `PetriNet`.
+    Parse(String),
+    /// A place or transition offset is negative or not less than the number of its kind.
+    InvalidOffset { label: String, offset: i32 },
+    /// Two places, or two transitions, share an offset.
+    DuplicateOffset {
+        label: String,
+        other: String,
+        offset: i32,
+    },
+    /// A place starts with a negative token count.
+    NegativeInitial { label: String, initial: i32 },
+    /// An arc names a place or transition that was never declared.
+    UndeclaredNode {
+        source: String,
+        target: String,
+        missing: String,
+    },
+    /// A string is not a valid content identifier.
+    InvalidCid(String),
+    /// A fetcher passed to `Model::from_cid` failed to return the blob.
+    Fetch(String),
+    /// Fetched content does not hash to the content identifier it was requested by.
+    CidMismatch { expected: String, actual: String },
+    /// A net is given a JSON format version this crate cannot read back.
+    UnsupportedVersion(String),
+}
+
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownModelType(err) => write!(f, "{err}"),
+            Self::Parse(err) => write!(f, "failed to parse petri-net: {err}"),
+            Self::InvalidOffset { label, offset } => {
+                write!(f, "{label} has invalid offset {offset}")
+            }
+            Self::DuplicateOffset {
+                label,
+                other,
+                offset,
+            } => write!(f, "{label} and {other} share offset {offset}"),
+            Self::NegativeInitial { label, initial } => {
+                write!(f, "place {label} has negative initial marking {initial}")
+            }
+            Self::InvalidArc {
+                source,
+                target,
+                reason,
+            } => write!(f, "invalid arc {source} -> {target}: {reason}"),
+            Self::UndeclaredNode {
+                source,
+                target,
+                missing,
+            } => write!(
+                f,
+                "arc {source} -> {target} references undeclared node {missing}"
+            ),
+            Self::InvalidCid(cid) => write!(f, "invalid cid: {cid}"),
+            Self::Fetch(err) => write!(f, "failed to fetch blob: {err}"),
+            Self::CidMismatch { expected, actual } => {
+                write!(f, "content hashes to {actual}, expected {expected}")
+            }
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported petri-net version: {version}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+impl From<ModelTypeError> for ModelError {
+    fn from(err: ModelTypeError) -> Self {
+        Self::UnknownModelType(err)
+    }
+}
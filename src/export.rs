@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::petri_net::{Arrow, PetriNet, Place, Transition};
+use crate::vasm::Vector;
+
+impl PetriNet {
+    /// Lists places in canonical order: by offset, then label.
+    ///
+    /// Every textual export iterates through the canonical orderings so that output does not
+    /// depend on `HashMap` iteration order.
+    pub fn canonical_places(&self) -> Vec<(&String, &Place)> {
+        let mut places: Vec<_> = self.places.iter().collect();
+        places.sort_by(|(a, p), (b, q)| (p.offset, a).cmp(&(q.offset, b)));
+        places
+    }
+
+    /// Lists transitions in canonical order: by offset, then label.
+    pub fn canonical_transitions(&self) -> Vec<(&String, &Transition)> {
+        let mut transitions: Vec<_> = self.transitions.iter().collect();
+        transitions.sort_by(|(a, t), (b, u)| (t.offset, a).cmp(&(u.offset, b)));
+        transitions
+    }
+
+    /// Lists arcs with their index in `arcs`, in canonical order: by source, target, then weight.
+    ///
+    /// Arcs that tie keep their stored order.
+    pub fn canonical_arcs(&self) -> Vec<(usize, &Arrow)> {
+        let mut arcs: Vec<_> = self.arcs.iter().enumerate().collect();
+        arcs.sort_by(|(_, a), (_, b)| {
+            (&a.source, &a.target, a.weight).cmp(&(&b.source, &b.target, b.weight))
+        });
+        arcs
+    }
+
+    /// Orients an arc for drawing, returning its `(from, to)` labels.
+    ///
+    /// Flow arcs keep their stored direction. Guards always run from the place to the transition,
+    /// whichever way round they are stored: the DSL stores a read arc transition-first, so the
+    /// `read` flag, or a transition source when it is unset, marks the place as the target.
+    pub fn arc_orientation<'a>(&self, arc: &'a Arrow) -> (&'a str, &'a str) {
+        let inhibit = arc.inhibit.unwrap_or(false);
+        let read = arc
+            .read
+            .unwrap_or_else(|| self.transitions.contains_key(&arc.source));
+        if inhibit && read {
+            (&arc.target, &arc.source)
+        } else {
+            (&arc.source, &arc.target)
+        }
+    }
+
+    /// Writes the net in Graphviz DOT format.
+    ///
+    /// Places are circles and transitions boxes, both in canonical order. Guards are drawn from
+    /// place to transition with an `odot` head, and any weight other than 1 labels its edge.
+    pub fn to_dot(&self) -> String {
+        let quote = |label: &str| label.replace('"', "\\\"");
+        let mut dot = String::from("digraph net {\n");
+        for (label, p) in self.canonical_places() {
+            let label = quote(label);
+            let tokens = p.initial.unwrap_or(0);
+            writeln!(
+                dot,
+                "  \"{label}\" [shape=circle, label=\"{label}\\n{tokens}\"];"
+            )
+            .expect("write failed");
+        }
+        for (label, _) in self.canonical_transitions() {
+            writeln!(dot, "  \"{}\" [shape=box];", quote(label)).expect("write failed");
+        }
+        for (_, arc) in self.canonical_arcs() {
+            let (from, to) = self.arc_orientation(arc);
+            let mut attrs = Vec::new();
+            if arc.inhibit.unwrap_or(false) {
+                attrs.push("arrowhead=odot".to_string());
+            }
+            let weight = arc.weight.unwrap_or(1);
+            if weight != 1 {
+                attrs.push(format!("label=\"{weight}\""));
+            }
+            let attrs = if attrs.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", attrs.join(", "))
+            };
+            writeln!(dot, "  \"{}\" -> \"{}\"{attrs};", quote(from), quote(to))
+                .expect("write failed");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Writes the net as a Mermaid flowchart, a bipartite graph of places and transitions.
+    ///
+    /// Places are rounded nodes `p0`, `p1`, … and transitions rectangles `t0`, `t1`, …,
+    /// numbered in canonical order. With a `marking`, indexed by place offset, each place is
+    /// labelled with its token count. Guards are drawn from place to transition with a circle
+    /// head, and any weight other than 1 labels its edge. Arcs to undeclared nodes are skipped.
+    pub fn to_mermaid_flowchart(&self, marking: Option<&Vector>) -> String {
+        let quote = |label: &str| label.replace('"', "#quot;");
+        let mut ids: HashMap<&str, String> = HashMap::new();
+        let mut chart = String::from("flowchart LR\n");
+        for (i, (label, p)) in self.canonical_places().into_iter().enumerate() {
+            let tokens = marking.and_then(|m| {
+                usize::try_from(p.offset)
+                    .ok()
+                    .and_then(|offset| m.get(offset))
+            });
+            let text = tokens.map_or_else(|| quote(label), |t| format!("{}: {t}", quote(label)));
+            writeln!(chart, "  p{i}(\"{text}\")").expect("write failed");
+            ids.insert(label, format!("p{i}"));
+        }
+        for (i, (label, _)) in self.canonical_transitions().into_iter().enumerate() {
+            writeln!(chart, "  t{i}[\"{}\"]", quote(label)).expect("write failed");
+            ids.insert(label, format!("t{i}"));
+        }
+        for (_, arc) in self.canonical_arcs() {
+            let (from, to) = self.arc_orientation(arc);
+            let (Some(from), Some(to)) = (ids.get(from), ids.get(to)) else {
+                continue;
+            };
+            let edge = if arc.inhibit.unwrap_or(false) {
+                "--o"
+            } else {
+                "-->"
+            };
+            let weight = arc.weight.unwrap_or(1);
+            let label = if weight == 1 {
+                String::new()
+            } else {
+                format!("|{weight}|")
+            };
+            writeln!(chart, "  {from} {edge}{label} {to}").expect("write failed");
+        }
+        chart
+    }
+
+    /// Writes the net as a PNML place/transition net document.
+    ///
+    /// Arcs get ids `a0`, `a1`, … from their index in `arcs`. Guards are oriented place to
+    /// transition and marked with `<type value="inhibitor"/>`, the extension most PNML tools read.
+    pub fn to_pnml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<pnml>\n");
+        xml.push_str(
+            "  <net id=\"net\" type=\"http://www.pnml.org/version-2009/grammar/ptnet\">\n",
+        );
+        xml.push_str("    <page id=\"page\">\n");
+        for (label, p) in self.canonical_places() {
+            let id = escape_xml(label);
+            writeln!(xml, "      <place id=\"{id}\">").expect("write failed");
+            writeln!(xml, "        <name><text>{id}</text></name>").expect("write failed");
+            if let Some(initial) = p.initial.filter(|&i| i != 0) {
+                writeln!(
+                    xml,
+                    "        <initialMarking><text>{initial}</text></initialMarking>"
+                )
+                .expect("write failed");
+            }
+            writeln!(
+                xml,
+                "        <graphics><position x=\"{}\" y=\"{}\"/></graphics>",
+                p.x, p.y
+            )
+            .expect("write failed");
+            xml.push_str("      </place>\n");
+        }
+        for (label, t) in self.canonical_transitions() {
+            let id = escape_xml(label);
+            writeln!(xml, "      <transition id=\"{id}\">").expect("write failed");
+            writeln!(xml, "        <name><text>{id}</text></name>").expect("write failed");
+            writeln!(
+                xml,
+                "        <graphics><position x=\"{}\" y=\"{}\"/></graphics>",
+                t.x, t.y
+            )
+            .expect("write failed");
+            xml.push_str("      </transition>\n");
+        }
+        for (index, arc) in self.canonical_arcs() {
+            let (from, to) = self.arc_orientation(arc);
+            writeln!(
+                xml,
+                "      <arc id=\"a{index}\" source=\"{}\" target=\"{}\">",
+                escape_xml(from),
+                escape_xml(to)
+            )
+            .expect("write failed");
+            writeln!(
+                xml,
+                "        <inscription><text>{}</text></inscription>",
+                arc.weight.unwrap_or(1)
+            )
+            .expect("write failed");
+            if arc.inhibit.unwrap_or(false) {
+                xml.push_str("        <type value=\"inhibitor\"/>\n");
+            }
+            xml.push_str("      </arc>\n");
+        }
+        xml.push_str("    </page>\n  </net>\n</pnml>\n");
+        xml
+    }
+
+    /// Emits the body of a `pflow!` or `pflow_dsl!` macro that declares this net.
+    ///
+    /// Places and transitions are listed in canonical order and arcs in their stored order, so
+    /// the DSL rebuilds the same `arcs`, keeping coordinates and weights. Inhibitor arcs become
+    /// `guard` lines, or `guard_any` when grouped. An unbounded capacity is written as 0, which
+    /// the DSL reads back as unbounded. Quotes and backslashes in labels are escaped as in a
+    /// Rust string literal.
+    pub fn to_dsl_source(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "declare \"{}\"", escape_dsl(&self.model_type)).expect("write failed");
+
+        for (label, p) in self.canonical_places() {
+            writeln!(
+                out,
+                "cell \"{}\", {}, {}, [{}, {}]",
+                escape_dsl(label),
+                p.initial.unwrap_or(0),
+                p.capacity.unwrap_or(0),
+                p.x,
+                p.y
+            )
+            .expect("write failed");
+        }
+
+        for (label, t) in self.canonical_transitions() {
+            let role = t.role.as_deref().unwrap_or("default");
+            writeln!(
+                out,
+                "func \"{}\", \"{}\", [{}, {}]",
+                escape_dsl(label),
+                escape_dsl(role),
+                t.x,
+                t.y
+            )
+            .expect("write failed");
+        }
+        for (label, t) in self.canonical_transitions() {
+            if let (Some(count), Some(completion)) = (t.server_count, &t.completed_by) {
+                writeln!(
+                    out,
+                    "servers \"{}\", {count}, \"{}\"",
+                    escape_dsl(label),
+                    escape_dsl(completion)
+                )
+                .expect("write failed");
+            }
+        }
+
+        for arc in &self.arcs {
+            let keyword = match (&arc.group, arc.inhibit.unwrap_or(false)) {
+                (Some(group), true) => format!("guard_any \"{}\",", escape_dsl(group)),
+                (None, true) => "guard".to_string(),
+                (_, false) => "arrow".to_string(),
+            };
+            writeln!(
+                out,
+                "{keyword} \"{}\", \"{}\", {}",
+                escape_dsl(&arc.source),
+                escape_dsl(&arc.target),
+                arc.weight.unwrap_or(1)
+            )
+            .expect("write failed");
+        }
+        out
+    }
+}
+
+/// Escapes backslashes and double quotes, so a label reads back as the same string literal.
+fn escape_dsl(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escapes the characters that may not appear literally in XML text or attribute values.
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::petri_net::PetriNet;
+    use crate::vasm::StateMachine;
+    use crate::{declaration_function, dsl, model, pflow_dsl};
+
+    #[test]
+    fn test_to_dsl_source_round_trip() {
+        let mut net = PetriNet::from_json_str(
+            r#"{
+                "modelType": "petriNet",
+                "version": "v0",
+                "places": {
+                    "stock": { "offset": 0, "initial": 5, "capacity": 10, "x": 100, "y": 120 },
+                    "sold": { "offset": 1, "initial": 0, "x": 300, "y": 120 }
+                },
+                "transitions": {
+                    "sell": { "offset": 0, "role": "clerk", "x": 200, "y": 60 },
+                    "restock": { "offset": 1, "role": "default", "x": 200, "y": 180 }
+                },
+                "arcs": [
+                    { "source": "stock", "target": "sell", "weight": 2 },
+                    { "source": "sell", "target": "sold", "weight": 2 },
+                    { "source": "stock", "target": "restock", "weight": 3, "inhibit": true },
+                    { "source": "restock", "target": "stock", "weight": 4 }
+                ]
+            }"#,
+        )
+        .expect("valid json");
+
+        let source = net.to_dsl_source();
+        assert_eq!(
+            source,
+            r#"declare "petriNet"
+cell "stock", 5, 10, [100, 120]
+cell "sold", 0, 0, [300, 120]
+func "sell", "clerk", [200, 60]
+func "restock", "default", [200, 180]
+arrow "stock", "sell", 2
+arrow "sell", "sold", 2
+guard "stock", "restock", 3
+arrow "restock", "stock", 4
+"#
+        );
+
+        let rebuilt = pflow_dsl! {
+            declare "petriNet"
+            cell "stock", 5, 10, [100, 120]
+            cell "sold", 0, 0, [300, 120]
+            func "sell", "clerk", [200, 60]
+            func "restock", "default", [200, 180]
+            arrow "stock", "sell", 2
+            arrow "sell", "sold", 2
+            guard "stock", "restock", 3
+            arrow "restock", "stock", 4
+        };
+        // a missing capacity comes back as 0, which is unbounded just the same
+        assert_eq!(rebuilt.net.to_dsl_source(), source);
+        assert_eq!(
+            rebuilt.vm.debug_dump(),
+            StateMachine::from_model(&mut net).debug_dump()
+        );
+    }
+
+    #[test]
+    fn test_to_dsl_source_escapes_labels() {
+        let mut net = PetriNet::new();
+        net.add_place("say \"hi\"", 0, Some(1), None, 0, 0);
+        net.add_transition("C:\\path", "default", 0, 0);
+        let source = net.to_dsl_source();
+        assert!(
+            source.contains("cell \"say \\\"hi\\\"\", 1, 0, [0, 0]\n"),
+            "{source}"
+        );
+        assert!(
+            source.contains("func \"C:\\\\path\", \"default\", [0, 0]\n"),
+            "{source}"
+        );
+
+        let rebuilt = pflow_dsl! {
+            declare "petriNet"
+            cell "say \"hi\"", 1, 0, [0, 0]
+            func "C:\\path", "default", [0, 0]
+        };
+        assert_eq!(rebuilt.net.to_dsl_source(), source);
+    }
+
+    #[test]
+    fn test_guards_point_from_place_to_transition() {
+        let model = model::Model::new(dsl::tests::model_test_code);
+        let net = &model.net;
+
+        // `guard(bar, foo, 3)` is stored transition-first, `guard(foo, baz, 1)` place-first
+        let dot = net.to_dot();
+        assert!(dot.contains("  \"foo\" -> \"bar\" [arrowhead=odot, label=\"3\"];\n"));
+        assert!(dot.contains("  \"foo\" -> \"baz\" [arrowhead=odot];\n"));
+        assert!(dot.contains("  \"foo\" -> \"dec\";\n"));
+        assert!(dot.contains("  \"inc\" -> \"foo\";\n"));
+
+        let pnml = net.to_pnml();
+        for (id, target, weight) in [("a2", "bar", 3), ("a3", "baz", 1)] {
+            assert!(pnml.contains(&format!(
+                "<arc id=\"{id}\" source=\"foo\" target=\"{target}\">\n        <inscription><text>{weight}</text></inscription>\n        <type value=\"inhibitor\"/>"
+            )));
+        }
+        assert_eq!(pnml.matches("inhibitor").count(), 2);
+
+        let svg = model.to_svg(None);
+        for (x2, y2) in [(560, 480), (850, 480)] {
+            assert!(svg.contains(&format!(
+                "<line x1=\"707\" y1=\"364\" x2=\"{x2}\" y2=\"{y2}\" stroke=\"#000000\" fill=\"#000000\" marker-end=\"url(#markerInhibit1)\" />"
+            )));
+        }
+    }
+
+    #[test]
+    fn test_to_mermaid_flowchart() {
+        let net = PetriNet::from_json_str(
+            r#"{
+                "modelType": "petriNet",
+                "places": {
+                    "water": { "offset": 0, "initial": 2 },
+                    "steam": { "offset": 1 }
+                },
+                "transitions": { "boil": { "offset": 0 } },
+                "arcs": [
+                    { "source": "water", "target": "boil", "weight": 2 },
+                    { "source": "boil", "target": "steam" },
+                    { "source": "steam", "target": "boil", "inhibit": true }
+                ]
+            }"#,
+        )
+        .expect("valid net");
+        let chart = net.to_mermaid_flowchart(None);
+        assert_eq!(
+            chart,
+            "flowchart LR\n  p0(\"water\")\n  p1(\"steam\")\n  t0[\"boil\"]\n  t0 --> p1\n  p1 --o t0\n  p0 -->|2| t0\n"
+        );
+
+        let marked = net.to_mermaid_flowchart(Some(&vec![0, 1]));
+        assert!(marked.contains("  p0(\"water: 0\")\n  p1(\"steam: 1\")\n"));
+    }
+}
@@ -91,11 +91,51 @@ pub mod dsl;
 /// The `zblob` contains utilities to facilitate loading zipped blob data as petri-nets.
 pub mod zblob;
 
+/// The `reachability` module explores the state space of a `StateMachine`.
+pub mod reachability;
+
+/// The `structure` module classifies a `StateMachine` by the shape of its arcs.
+pub mod structure;
+
+/// The `trace` module produces example firing sequences for a `StateMachine`.
+pub mod trace;
+
+/// The `symmetry` module finds the automorphisms of a `StateMachine`.
+pub mod symmetry;
+
+/// The `simulate` module runs seeded random simulations of a `StateMachine`.
+pub mod simulate;
+
+/// The `compose` module folds firing sequences of a `StateMachine` into single transitions.
+pub mod compose;
+
+/// The `testing` module generates random nets for property tests, behind the `testing` feature.
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+/// The `export` module writes a `PetriNet` back out as DSL source, Graphviz DOT, or PNML.
+pub mod export;
+
+/// The `dfa` module compiles finite automata with accepting states to elementary nets.
+pub mod dfa;
+
+/// The `templates` module builds classic example nets such as producer/consumer and mutex.
+pub mod templates;
+
+/// The `diff` module compares two `PetriNet`s and merges the differences.
+pub mod diff;
+
+/// The `error` module contains the errors raised while compiling a `PetriNet` into a `StateMachine`.
+pub mod error;
+
+/// The `validate` module reports structural problems in a `PetriNet` or `Model`.
+pub mod validate;
+
 /// The `model` encapsulates the `PetriNet` and `Vasm` objects into a single `Model` object.
 pub mod model;
 
 /// The `display` module contains the `ImageBuilder` and `ImageOutput` traits for rendering Petri-nets as SVG.
-mod display;
+pub mod display;
 
 pub use crate::model::*;
 pub use crate::vasm::*;
@@ -127,6 +167,14 @@ macro_rules! pflow_dsl {
                 fn guard(p: &mut dyn dsl::Dsl, source: &str, target: &str, weight: i32) {
                     p.guard(source, target, weight);
                 }
+                #[allow(unused)]
+                fn guard_any(p: &mut dyn dsl::Dsl, group: &str, source: &str, target: &str, weight: i32) {
+                    p.guard_any(group, source, target, weight);
+                }
+                #[allow(unused)]
+                fn servers(p: &mut dyn dsl::Dsl, transition: &str, count: u32, completion: &str) {
+                    p.servers(transition, count, completion);
+                }
                 $(
                     $name(p, $($args),*);
                 )*
@@ -155,7 +203,7 @@ macro_rules! pflow_json {
         ).expect("json fault");
 
         let sm = vasm::StateMachine::from_model(&mut net);
-        model::Model { net, vm: Box::new(sm) }
+        model::Model::from_parts(net, Box::new(sm))
     }};
 }
 
@@ -193,6 +241,12 @@ macro_rules! state_machine {
                     state: std::sync::Arc::new(std::sync::Mutex::new(state)),
                 }
             }
+
+            /// Overwrite the current state with the initial marking
+            #[allow(unused)]
+            pub fn reset(&self) {
+                *self.state.lock().expect("lock failed") = self.model.reset_state();
+            }
         }
     };
 }
@@ -221,6 +275,12 @@ macro_rules! petri_net {
                     state: std::sync::Arc::new(std::sync::Mutex::new(state)),
                 }
             }
+
+            /// Overwrite the current state with the initial marking
+            #[allow(unused)]
+            pub fn reset(&self) {
+                *self.state.lock().expect("lock failed") = self.model.reset_state();
+            }
         }
     };
 }
@@ -249,6 +309,12 @@ macro_rules! pflow {
                     state: std::sync::Arc::new(std::sync::Mutex::new(state)),
                 }
             }
+
+            /// Overwrite the current state with the initial marking
+            #[allow(unused)]
+            pub fn reset(&self) {
+                *self.state.lock().expect("lock failed") = self.model.reset_state();
+            }
         }
     };
 }
@@ -258,7 +324,7 @@ pub enum StateMachineError {
     InvalidAction,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Event<T> {
     pub action: String,
     pub seq: u64,
@@ -266,6 +332,19 @@ pub struct Event<T> {
     pub data: T,
 }
 
+/// Receives events one at a time as a process runs
+pub trait EventSink<T> {
+    /// Handle a single event, such as writing it to a file or sending it on a channel
+    fn emit(&mut self, event: &Event<T>);
+}
+
+/// Buffers every event, as `Process::run` does
+impl<T: Clone> EventSink<T> for Vec<Event<T>> {
+    fn emit(&mut self, event: &Event<T>) {
+        self.push(event.clone());
+    }
+}
+
 pub trait State {
     /// Evaluate the preconditions to initialize the model state
     fn evaluate_preconditions(&self) -> Result<bool, StateMachineError>;
@@ -277,6 +356,22 @@ pub trait State {
 pub trait Process<TContext> {
     /// Run the state machine until no actions are available
     fn run(&self, input: TContext) -> Vec<Event<TContext>>;
+    /// Run the state machine like `run`, passing each event to the sink instead of collecting them
+    ///
+    /// By default this fires the first of `next_action` until none is left, emitting each event
+    /// from `process_action` and threading its data into the next. Sequence numbers start at 1.
+    fn run_streaming(&self, input: TContext, sink: &mut dyn EventSink<TContext>) {
+        let mut context = input;
+        let mut seq = 1;
+        while let Some(action) = self.next_action().into_iter().next() {
+            let Some(event) = self.process_action(&action, seq, context) else {
+                return;
+            };
+            sink.emit(&event);
+            context = event.data;
+            seq += 1;
+        }
+    }
     /// Runs the main loop of the state machine
     fn run_impl(
         &self,
@@ -315,7 +410,6 @@ pub trait SubProcess<'a, TPayload> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::usize;
 
     pflow! { CoffeeMachineUsingDsl {
         declare "PetriNet"
@@ -448,27 +542,17 @@ mod tests {
 
         fn run_impl(
             &self,
-            action: Option<&str>,
-            seq: Option<u64>,
+            _action: Option<&str>,
+            _seq: Option<u64>,
             mut event_log: Vec<Event<Context>>,
         ) -> Vec<Event<Context>> {
-            let mut current_action = action.map(ToString::to_string);
-            let mut current_seq = seq.unwrap_or(0) + 1;
-
-            while let Some(ref action) = current_action {
-                let context = event_log.last().expect("last event").data.clone();
-                if let Some(transaction) = self.process_action(action, current_seq, context) {
-                    event_log.push(transaction);
-                } else {
-                    break;
-                }
-                current_action = self.next_action().first().cloned();
-                current_seq += 1;
-            }
+            // the streamed run picks each action itself and numbers them from 1
+            let context = event_log.last().expect("last event").data.clone();
+            self.run_streaming(context, &mut event_log);
 
             let evt = Event {
                 action: "__end__".to_string(),
-                seq: current_seq + 1,
+                seq: event_log.last().expect("last event").seq + 2,
                 state: self.state.lock().expect("lock failed").clone(),
                 data: Context {
                     msg: "Coffee machine stopped".to_string(),
@@ -487,7 +571,7 @@ mod tests {
         fn process_action(&self, action: &str, seq: u64, ctx: Context) -> Option<Event<Context>> {
             let mut state = self.state.lock().expect("lock failed");
             let res = self.model.vm.transform(&state, action, 1);
-            let mut data = ctx.clone();
+            let mut data = ctx;
             data.msg = format!("completed! #{seq}: {action}");
 
             if res.is_ok() {
@@ -555,6 +639,56 @@ mod tests {
         }
     }
 
+    #[derive(Default)]
+    struct CountingSink {
+        count: usize,
+        last_seq: u64,
+    }
+
+    impl EventSink<Context> for CountingSink {
+        fn emit(&mut self, event: &Event<Context>) {
+            self.count += 1;
+            self.last_seq = event.seq;
+        }
+    }
+
+    #[test]
+    fn test_coffee_machine_streaming() {
+        let buffered = CoffeeMachineUsingPetriNet::new().run(Context {
+            msg: "Start".to_string(),
+        });
+        let mut sink = CountingSink::default();
+        CoffeeMachineUsingPetriNet::new().run_streaming(
+            Context {
+                msg: "Start".to_string(),
+            },
+            &mut sink,
+        );
+        // the buffered run adds __begin__ and __end__ around the four actions
+        assert_eq!(sink.count, 4);
+        assert_eq!(sink.count, buffered.len() - 2);
+        assert_eq!(sink.last_seq, buffered[buffered.len() - 2].seq);
+    }
+
+    #[test]
+    fn test_coffee_machine_reset() {
+        let cm = CoffeeMachineUsingPetriNet::new();
+        cm.run(Context {
+            msg: "Start".to_string(),
+        });
+        assert_ne!(
+            *cm.state.lock().expect("lock failed"),
+            cm.model.vm.initial_vector()
+        );
+
+        cm.reset();
+        assert_eq!(
+            *cm.state.lock().expect("lock failed"),
+            cm.model.vm.initial_vector()
+        );
+        assert_eq!(cm.model.reset_state(), cm.model.vm.initial_vector());
+    }
+
     #[test]
     fn test_coffee_machine_using_dsl() {
         let cm = CoffeeMachineUsingDsl::new();
@@ -581,4 +715,32 @@ mod tests {
             sm.model.net.to_zblob().base64_zipped
         );
     }
+
+    #[test]
+    fn test_reachable_count() {
+        let sm = SimpleStateMachine::new();
+        let vm = &sm.model.vm;
+        assert_eq!(vm.reachable_count(10), Ok(5));
+        assert_eq!(
+            vm.reachable_count(10),
+            Ok(vm.reachability_graph(10).expect("fits").states.len())
+        );
+        assert_eq!(
+            vm.reachable_count(1),
+            Err(reachability::Truncated { limit: 1 })
+        );
+    }
+
+    #[test]
+    fn test_reachability_graph_to_dot() {
+        let sm = SimpleStateMachine::new();
+        let graph = sm.model.vm.reachability_graph(10).expect("fits");
+        let dot = graph.to_dot();
+
+        assert!(dot.starts_with("digraph reachability {"));
+        assert_eq!(dot.matches(" [label=\"s").count(), 5);
+        assert_eq!(dot.matches(" -> ").count(), 12);
+        assert_eq!(dot.matches("style=filled").count(), 1);
+        assert!(dot.contains("  s0 [label=\"s0\\n["));
+    }
 }
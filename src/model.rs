@@ -1,18 +1,45 @@
+use crate::compression::decompress_brotli_decode;
+use crate::display::Display;
 use crate::dsl::Dsl;
+use crate::error::ModelError;
 use crate::petri_net::PetriNet;
-use crate::vasm::StateMachine;
+use crate::vasm::{StateMachine, Tx, Vasm, Vector};
+use crate::zblob::Zblob;
+use crate::Event;
+use libipld::cid::Cid;
+use std::fmt;
+use std::sync::{Arc, Mutex};
 
 #[derive(Debug)]
 pub struct Model {
     pub net: PetriNet,
     pub vm: Box<StateMachine>,
+    zblob_cache: Mutex<ZblobCache>,
+}
+
+/// The last zblob built by `Model::to_zblob`, with the net it was built from and its `net_hash`.
+#[derive(Debug, Default)]
+struct ZblobCache {
+    entry: Option<(u64, PetriNet, Zblob)>,
+    /// How many zblobs have been compressed, for observing cache hits.
+    #[cfg(test)]
+    builds: usize,
 }
 
 impl Model {
+    /// Pairs a net with its state machine, as built by `StateMachine::from_model`.
+    pub fn from_parts(net: PetriNet, vm: Box<StateMachine>) -> Self {
+        Self {
+            net,
+            vm,
+            zblob_cache: Mutex::default(),
+        }
+    }
+
     pub fn new(func: fn(&mut dyn Dsl)) -> Self {
         let mut net = PetriNet::new();
         let vm = Box::new(net.declare(func).as_vasm());
-        Self { net, vm }
+        Self::from_parts(net, vm)
     }
 
     /// Use pflow DSL to declare a function that defines the model
@@ -20,24 +47,75 @@ impl Model {
     /// This is the same logic as the `new` function, but it allows
     /// for chaining
     pub fn declare(&mut self, func: fn(&mut dyn Dsl)) -> &mut Model {
-        self.vm = Box::new(self.net.declare(func).as_vasm());
+        *self.vm = self.net.declare(func).as_vasm();
         self
     }
 
+    /// Returns the initial marking, for restarting a simulation on the same model
+    pub fn reset_state(&self) -> Vector {
+        self.vm.initial_vector()
+    }
+
+    /// Fires `action` against shared state, updating it only when the transformation succeeds
+    ///
+    /// On success the new state is stored and an `Event` recording it is returned, carrying the
+    /// transaction as its data. On failure the state is left unchanged and the failed
+    /// transaction is returned so the caller can see why.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `Tx` if the action is not enabled in the current state.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the state lock is poisoned.
+    pub fn fire(
+        &self,
+        state: &Arc<Mutex<Vector>>,
+        action: &str,
+        seq: u64,
+    ) -> Result<Event<Tx>, Tx> {
+        let mut state = state.lock().expect("lock failed");
+        let tx = self.vm.transform(&state, action, 1);
+        if !tx.is_ok() {
+            return Err(tx);
+        }
+        state.clone_from(&tx.output);
+        drop(state);
+        Ok(Event {
+            action: action.to_string(),
+            seq,
+            state: tx.output.clone(),
+            data: tx,
+        })
+    }
+
     ///  Parse a diagram into a PetriNet
     ///
     /// # Panics
     ///
     /// Panics if the diagram is not valid
     pub fn from_diagram(contents: String) -> Self {
+        Self::try_from_diagram(&contents).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Parse a diagram into a PetriNet, reporting every problem as an error
+    ///
+    /// A diagram containing `ModelType::` is read by `PetriNet::try_from_diagram`, and any
+    /// other by `PetriNet::try_from_state_diagram`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ModelError::Parse` if the diagram is malformed, or any error from
+    /// `StateMachine::try_from_model`.
+    pub fn try_from_diagram(contents: &str) -> Result<Self, ModelError> {
         let mut net = if contents.contains("ModelType::") {
-            PetriNet::from_diagram(contents)
+            PetriNet::try_from_diagram(contents)?
         } else {
-            PetriNet::from_state_diagram(contents)
+            PetriNet::try_from_state_diagram(contents)?
         };
-        // println!("https://pflow.dev/?z={}", net.to_zblob().base64_zipped);
-        let vm = Box::new(net.declare(|_| {}).as_vasm());
-        Self { net, vm }
+        let vm = Box::new(StateMachine::try_from_model(&mut net)?);
+        Ok(Self::from_parts(net, vm))
     }
 
     /// Parse a JSON value into a PetriNet
@@ -48,7 +126,20 @@ impl Model {
     pub fn from_json_value(value: serde_json::Value) -> Self {
         let mut net = PetriNet::from_json_value(value).expect("Failed to parse JSON");
         let vm = Box::new(net.declare(|_| {}).as_vasm());
-        Self { net, vm }
+        Self::from_parts(net, vm)
+    }
+
+    /// Parse a JSON value into a PetriNet, reporting every problem as an error
+    ///
+    /// # Errors
+    ///
+    /// Returns `ModelError::Parse` if the value does not describe a `PetriNet`, or any error
+    /// from `StateMachine::try_from_model`.
+    pub fn try_from_json_value(value: serde_json::Value) -> Result<Self, ModelError> {
+        let mut net =
+            PetriNet::from_json_value(value).map_err(|err| ModelError::Parse(err.to_string()))?;
+        let vm = Box::new(StateMachine::try_from_model(&mut net)?);
+        Ok(Self::from_parts(net, vm))
     }
 
     /// Parse a JSON string into a PetriNet
@@ -59,7 +150,109 @@ impl Model {
     pub fn from_json_str(value: &str) -> Self {
         let mut net = PetriNet::from_json_str(value).expect("Failed to parse JSON");
         let vm = Box::new(net.declare(|_| {}).as_vasm());
-        Self { net, vm }
+        Self::from_parts(net, vm)
+    }
+
+    /// Parse a JSON string into a PetriNet, reporting every problem as an error
+    ///
+    /// This never panics, whatever the input, so it is suitable as a fuzzing target.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ModelError::Parse` if the JSON does not describe a `PetriNet`, or any error
+    /// from `StateMachine::try_from_model`.
+    pub fn try_from_json_str(value: &str) -> Result<Self, ModelError> {
+        let mut net =
+            PetriNet::from_json_str(value).map_err(|err| ModelError::Parse(err.to_string()))?;
+        let vm = Box::new(StateMachine::try_from_model(&mut net)?);
+        Ok(Self::from_parts(net, vm))
+    }
+
+    /// Loads a model by its content identifier, using `fetch` to retrieve the blob
+    ///
+    /// The `cid` may be bare or given as a share link such as `https://pflow.dev/p/<cid>/`.
+    /// `fetch` is called with the bare CID and must return the blob's base64 zipped content;
+    /// the crate does no I/O itself, so any transport can be plugged in. The content is checked
+    /// against the CID with `Zblob::verify_cid` before it is decompressed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ModelError::InvalidCid` if `cid` does not parse, `ModelError::Fetch` if the
+    /// fetcher fails, `ModelError::CidMismatch` if the content does not hash to the CID, and
+    /// `ModelError::Parse` or any error from `StateMachine::try_from_model` if the content is
+    /// not a valid net.
+    pub fn from_cid<E: fmt::Display>(
+        cid: &str,
+        fetch: impl Fn(&str) -> Result<Vec<u8>, E>,
+    ) -> Result<Self, ModelError> {
+        let cid = cid
+            .trim()
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_start_matches("pflow.dev/p/")
+            .trim_end_matches('/');
+        Cid::try_from(cid).map_err(|err| ModelError::InvalidCid(format!("{cid}: {err}")))?;
+        let bytes = fetch(cid).map_err(|err| ModelError::Fetch(err.to_string()))?;
+        let base64_zipped =
+            String::from_utf8(bytes).map_err(|err| ModelError::Parse(err.to_string()))?;
+        let zblob = Zblob::from_string(Some(base64_zipped.trim()));
+        if !zblob.verify_cid() || zblob.ipfs_cid != cid {
+            return Err(ModelError::CidMismatch {
+                expected: cid.to_string(),
+                actual: zblob.ipfs_cid,
+            });
+        }
+        let json = decompress_brotli_decode(&zblob.base64_zipped)
+            .map_err(|err| ModelError::Parse(err.to_string()))?;
+        Self::try_from_json_str(&json)
+    }
+
+    /// Packs the net into a `Zblob`, reusing the last one while the net is unchanged
+    ///
+    /// Changes are detected with `PetriNet::net_hash` and confirmed against a copy of the net
+    /// the zblob was built from, so an edit made directly to `net` is picked up on the next call
+    /// without recompressing in between.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cache lock is poisoned.
+    pub fn to_zblob(&self) -> Zblob {
+        let hash = self.net.net_hash();
+        let mut cache = self.zblob_cache.lock().expect("lock failed");
+        if let Some((cached, net, zblob)) = &cache.entry {
+            if *cached == hash && *net == self.net {
+                return zblob.clone();
+            }
+        }
+        let zblob = self.net.to_zblob();
+        cache.entry = Some((hash, self.net.clone(), zblob.clone()));
+        #[cfg(test)]
+        {
+            cache.builds += 1;
+        }
+        drop(cache);
+        zblob
+    }
+
+    /// Returns the model as a single self-contained net, for archiving under a stable CID
+    ///
+    /// Nets cannot import or reference one another yet, so every model is already flat and
+    /// this is a copy of `net`.
+    pub fn flatten(&self) -> PetriNet {
+        self.net.clone()
+    }
+
+    /// Renders the net as SVG markup, with tokens from `marking` or the initial marking if `None`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `marking` does not have one entry per place.
+    pub fn to_svg(&self, marking: Option<&Vector>) -> String {
+        let mut svg: Vec<u8> = Vec::new();
+        Display::new(self.clone())
+            .render_to(&mut svg, marking)
+            .expect("failed to render svg");
+        String::from_utf8(svg).expect("svg is utf8")
     }
 }
 
@@ -67,7 +260,7 @@ impl Clone for Model {
     fn clone(&self) -> Self {
         let mut net = self.net.clone();
         let vm = Box::new(net.declare(|_| {}).as_vasm());
-        Self { net, vm }
+        Self::from_parts(net, vm)
     }
 }
 
@@ -75,6 +268,228 @@ impl Clone for Model {
 mod tests {
     use super::*;
 
+    fn try_load(places: &str, arcs: &str) -> Result<Model, ModelError> {
+        Model::try_from_json_str(&format!(
+            r#"{{
+                "modelType": "petriNet",
+                "places": {places},
+                "transitions": {{ "t": {{ "offset": 0 }} }},
+                "arcs": {arcs}
+            }}"#
+        ))
+    }
+
+    #[test]
+    fn test_try_from_json_str() {
+        let ok = try_load(
+            r#"{ "p": { "offset": 0, "initial": 1 } }"#,
+            r#"[ { "source": "p", "target": "t" } ]"#,
+        );
+        assert_eq!(ok.expect("valid net").vm.initial_vector(), vec![1]);
+
+        assert!(matches!(
+            Model::try_from_json_str("{ not json"),
+            Err(ModelError::Parse(_))
+        ));
+        assert!(matches!(
+            Model::try_from_json_str(
+                r#"{ "modelType": "bogus", "places": {}, "transitions": {}, "arcs": [] }"#
+            ),
+            Err(ModelError::UnknownModelType(_))
+        ));
+        assert_eq!(
+            try_load(r#"{ "p": { "offset": 3 } }"#, "[]").err(),
+            Some(ModelError::InvalidOffset {
+                label: "p".to_string(),
+                offset: 3
+            })
+        );
+        assert_eq!(
+            try_load(r#"{ "p": { "offset": -1 } }"#, "[]").err(),
+            Some(ModelError::InvalidOffset {
+                label: "p".to_string(),
+                offset: -1
+            })
+        );
+        assert_eq!(
+            try_load(r#"{ "p": { "offset": 0, "initial": -2 } }"#, "[]").err(),
+            Some(ModelError::NegativeInitial {
+                label: "p".to_string(),
+                initial: -2
+            })
+        );
+        assert_eq!(
+            try_load(
+                r#"{ "p": { "offset": 0 } }"#,
+                r#"[ { "source": "p", "target": "nowhere" } ]"#
+            )
+            .err(),
+            Some(ModelError::UndeclaredNode {
+                source: "p".to_string(),
+                target: "nowhere".to_string(),
+                missing: "nowhere".to_string()
+            })
+        );
+        assert_eq!(
+            try_load(r#"{ "p": { "offset": 0 }, "q": { "offset": 0 } }"#, "[]").err(),
+            Some(ModelError::DuplicateOffset {
+                label: "q".to_string(),
+                other: "p".to_string(),
+                offset: 0
+            })
+        );
+        assert!(matches!(
+            Model::try_from_json_str(
+                r#"{
+                    "modelType": "petriNet",
+                    "places": {},
+                    "transitions": { "t": { "offset": 0 }, "u": { "offset": 2 } },
+                    "arcs": []
+                }"#
+            ),
+            Err(ModelError::InvalidOffset { offset: 2, .. })
+        ));
+        assert!(matches!(
+            try_load(
+                r#"{ "p": { "offset": 0 }, "q": { "offset": 1 } }"#,
+                r#"[ { "source": "p", "target": "q" } ]"#
+            ),
+            Err(ModelError::InvalidArc { .. })
+        ));
+        assert!(matches!(
+            try_load(
+                r#"{ "p": { "offset": 0 } }"#,
+                r#"[ { "source": "p", "target": "t", "weight": -2147483648 } ]"#
+            ),
+            Err(ModelError::InvalidArc { .. })
+        ));
+    }
+
+    #[test]
+    fn test_try_from_diagram() {
+        let model =
+            Model::try_from_diagram("ModelType::PetriNet; Water --> boil;").expect("valid diagram");
+        assert_eq!(model.vm.places, vec!["Water"]);
+        let model = Model::try_from_diagram("[*] --> Idle; Idle --> [*];").expect("state diagram");
+        assert_eq!(model.net.model_type, "workflow");
+
+        assert!(matches!(
+            Model::try_from_diagram("ModelType::Bogus; Water --> boil;"),
+            Err(ModelError::Parse(_))
+        ));
+        assert!(matches!(
+            Model::try_from_diagram("ModelType::PetriNet; water --> boil;"),
+            Err(ModelError::Parse(_))
+        ));
+        assert!(matches!(
+            Model::try_from_diagram("ModelType::PetriNet; Water --> ;"),
+            Err(ModelError::Parse(_))
+        ));
+        assert!(matches!(
+            Model::try_from_diagram("[*] --> Idle; Idle --x--> [*];"),
+            Err(ModelError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_try_from_json_value() {
+        let model = Model::try_from_json_value(serde_json::json!({
+            "modelType": "petriNet",
+            "places": { "p": { "offset": 0, "initial": 1 } },
+            "transitions": { "t": { "offset": 0 } },
+            "arcs": [ { "source": "p", "target": "t" } ]
+        }))
+        .expect("valid net");
+        assert_eq!(model.vm.initial_vector(), vec![1]);
+
+        assert!(matches!(
+            Model::try_from_json_value(serde_json::json!([1, 2, 3])),
+            Err(ModelError::Parse(_))
+        ));
+        assert!(matches!(
+            Model::try_from_json_value(serde_json::json!({
+                "modelType": "bogus", "places": {}, "transitions": {}, "arcs": []
+            })),
+            Err(ModelError::UnknownModelType(_))
+        ));
+        assert_eq!(
+            Model::try_from_json_value(serde_json::json!({
+                "modelType": "petriNet",
+                "places": { "p": { "offset": 0, "initial": -1 } },
+                "transitions": {},
+                "arcs": []
+            }))
+            .err(),
+            Some(ModelError::NegativeInitial {
+                label: "p".to_string(),
+                initial: -1
+            })
+        );
+    }
+
+    #[test]
+    fn test_from_cid() {
+        let blob = PetriNet::from_json_str(crate::petri_net::tests::DINING_PHILOSOPHERS)
+            .expect("valid net")
+            .to_zblob();
+        let cid = blob.ipfs_cid.clone();
+        let fetch = |requested: &str| -> Result<Vec<u8>, String> {
+            if requested == cid {
+                Ok(blob.base64_zipped.clone().into_bytes())
+            } else {
+                Err(format!("not found: {requested}"))
+            }
+        };
+
+        let model = Model::from_cid(&cid, fetch).expect("blob loads");
+        assert_eq!(model.net.places.len(), 15);
+        let link = format!("https://pflow.dev/p/{cid}/");
+        assert_eq!(
+            Model::from_cid(&link, fetch).expect("link loads").net,
+            model.net
+        );
+
+        assert!(matches!(
+            Model::from_cid("not a cid", fetch),
+            Err(ModelError::InvalidCid(_))
+        ));
+        assert!(matches!(
+            Model::from_cid(crate::zblob::EMPTY_NET_CID, fetch),
+            Err(ModelError::Fetch(_))
+        ));
+        let tampered = |_: &str| Ok::<_, String>(Zblob::empty().base64_zipped.into_bytes());
+        assert_eq!(
+            Model::from_cid(&cid, tampered).err(),
+            Some(ModelError::CidMismatch {
+                expected: cid.clone(),
+                actual: crate::zblob::EMPTY_NET_CID.to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_fire_updates_shared_state() {
+        let m = Model::new(|p| {
+            p.cell("a", Option::from(1), None, 100, 100);
+            p.cell("b", None, None, 200, 100);
+            p.func("move", "default", 150, 100);
+            p.arrow("a", "move", 1);
+            p.arrow("move", "b", 1);
+        });
+        let state = Arc::new(Mutex::new(m.reset_state()));
+
+        let event = m.fire(&state, "move", 1).expect("move is enabled");
+        assert_eq!(event.action, "move");
+        assert_eq!(event.seq, 1);
+        assert_eq!(event.state, vec![0, 1]);
+        assert!(event.data.is_ok());
+        assert_eq!(*state.lock().expect("lock failed"), vec![0, 1]);
+
+        let tx = m.fire(&state, "move", 2).expect_err("a is empty");
+        assert!(tx.underflow);
+        assert_eq!(*state.lock().expect("lock failed"), vec![0, 1]);
+    }
+
     #[test]
     fn test_model() {
         let m = Model::from_diagram(
@@ -82,11 +497,60 @@ mod tests {
                 .to_string(),
         );
         assert_eq!(m.net.places.len(), 4);
-        for place in m.net.places.iter() {
+        for place in &m.net.places {
             println!("{place:?}");
         }
-        for transition in m.net.transitions.iter() {
+        for transition in &m.net.transitions {
             println!("{transition:?}");
         }
     }
+
+    #[test]
+    fn test_to_svg() {
+        let model = Model::new(|p| {
+            p.cell("place0", Option::from(1), None, 100, 100);
+            p.func("txn0", "default", 200, 100);
+            p.arrow("place0", "txn0", 1);
+        });
+        let svg = model.to_svg(None);
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.ends_with("</svg>"));
+        assert_eq!(svg, model.to_svg(Some(&vec![1])));
+    }
+
+    #[test]
+    fn test_to_zblob_is_cached() {
+        let mut model = Model::new(crate::petri_net::tests::coffee_machine);
+        let builds = |m: &Model| m.zblob_cache.lock().expect("lock failed").builds;
+        let first = model.to_zblob();
+        let second = model.to_zblob();
+        assert_eq!(builds(&model), 1);
+        assert_eq!(first.ipfs_cid, second.ipfs_cid);
+        assert_eq!(first.ipfs_cid, model.net.to_zblob().ipfs_cid);
+        // separately built maps iterate in different orders but hash alike
+        let rebuilt = Model::new(crate::petri_net::tests::coffee_machine);
+        assert_eq!(rebuilt.net.net_hash(), model.net.net_hash());
+
+        // another model's calls do not evict this one's zblob
+        let other = Model::from_json_str(crate::petri_net::tests::DINING_PHILOSOPHERS);
+        other.to_zblob();
+        model.to_zblob();
+        assert_eq!(builds(&model), 1);
+
+        model.net.places.get_mut("Water").expect("place").initial = Some(0);
+        let edited = model.to_zblob();
+        assert_eq!(builds(&model), 2);
+        assert_ne!(edited.ipfs_cid, first.ipfs_cid);
+        assert_eq!(edited.ipfs_cid, model.net.to_zblob().ipfs_cid);
+    }
+
+    #[test]
+    fn test_flatten_flat_net_keeps_cid() {
+        let model = Model::new(crate::petri_net::tests::coffee_machine);
+        let flat = model.flatten();
+        assert_eq!(flat, model.net);
+        let rebuilt = Model::new(crate::petri_net::tests::coffee_machine).flatten();
+        assert_eq!(flat.to_zblob().ipfs_cid, rebuilt.to_zblob().ipfs_cid);
+        assert_eq!(flat.to_zblob().ipfs_cid, model.to_zblob().ipfs_cid);
+    }
 }
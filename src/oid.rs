@@ -3,16 +3,28 @@ use libipld::multihash::{Code, MultihashDigest};
 use std::fmt;
 
 /// `Oid` is a struct that represents an object identifier (OID) in the form of a `Cid` object from the `libipld` crate.
+///
 /// It provides methods to create a new `Oid` from a byte slice, and to convert the `Oid` to a string or a byte vector.
 #[derive(Clone, Copy)]
 pub struct Oid {
     cid: Cid,
 }
 
+/// `OidError` is returned when an `Oid` cannot be rendered in the requested base.
+#[derive(Debug)]
+pub struct OidError(libipld::cid::Error);
+
+impl fmt::Display for OidError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cannot encode cid: {}", self.0)
+    }
+}
+
+impl std::error::Error for OidError {}
+
 impl fmt::Display for Oid {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.cid
-            .to_string_of_base(multibase::Base::Base58Btc)
+        self.to_string_base(multibase::Base::Base58Btc)
             .map_or(Err(fmt::Error), |s| write!(f, "{s}"))
     }
 }
@@ -43,4 +55,39 @@ impl Oid {
     pub fn to_bytes(&self) -> Vec<u8> {
         self.cid.to_bytes()
     }
+
+    /// Converts the `Oid` to a string in the given multibase, e.g. `Base32Lower` for `bafk...` CIDs.
+    ///
+    /// `Display` always uses `Base58Btc`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `OidError` if the CID cannot be encoded in `base`.
+    pub fn to_string_base(&self, base: multibase::Base) -> Result<String, OidError> {
+        self.cid.to_string_of_base(base).map_err(OidError)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_string_base() {
+        let oid = Oid::new(b"hello").expect("valid oid");
+        let base58 = oid
+            .to_string_base(multibase::Base::Base58Btc)
+            .expect("base58 encodes");
+        let base32 = oid
+            .to_string_base(multibase::Base::Base32Lower)
+            .expect("base32 encodes");
+        assert_eq!(base58, oid.to_string());
+        assert!(base58.starts_with('z'));
+        assert!(base32.starts_with('b'));
+
+        let from58 = Cid::try_from(base58.as_str()).expect("base58 decodes");
+        let from32 = Cid::try_from(base32.as_str()).expect("base32 decodes");
+        assert_eq!(from58.hash(), from32.hash());
+        assert_eq!(from58, from32);
+    }
 }
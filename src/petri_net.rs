@@ -1,27 +1,176 @@
 use crate::dsl::{ArcParams, Builder, Dsl};
+use crate::error::ModelError;
+use crate::vasm::ModelType;
 use crate::zblob::Zblob;
-use serde::{Deserialize, Serialize};
+use libipld::cbor::DagCborCodec;
+use libipld::codec::Codec;
+use libipld::Ipld;
+use serde::{Deserialize, Serialize, Serializer};
 use serde_json::{Error, Value};
-use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 
 /// PetriNet stores petri-net elements used during the construction of a petri-net.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct PetriNet {
     pub model_type: String,
+    #[serde(default = "default_version")]
     pub version: String,
-    pub places: HashMap<String, Place>,
-    pub transitions: HashMap<String, Transition>,
+    pub places: NodeMap<Place>,
+    pub transitions: NodeMap<Transition>,
     pub arcs: Vec<Arrow>,
 }
 
+/// The map a `PetriNet` keeps its places and transitions in, keyed by label.
+///
+/// With the `indexmap` feature this is an `IndexMap`, which keeps entries in the order they were
+/// declared or read, so `to_json_pretty` can write them back in that order.
+#[cfg(feature = "indexmap")]
+pub type NodeMap<V> = indexmap::IndexMap<String, V>;
+
+/// The map a `PetriNet` keeps its places and transitions in, keyed by label.
+#[cfg(not(feature = "indexmap"))]
+pub type NodeMap<V> = HashMap<String, V>;
+
+/// A `PetriNet` laid out for `to_json_pretty`, with places and transitions in offset order.
+///
+/// `to_json_pretty` destructures the net to fill this in, so a field added to `PetriNet` must be
+/// added here too.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AuthoredOrder<'a> {
+    model_type: &'a str,
+    version: &'a str,
+    places: Entries<'a, Place>,
+    transitions: Entries<'a, Transition>,
+    arcs: &'a [Arrow],
+}
+
+/// Map entries serialized in the order given, rather than the map's own order.
+struct Entries<'a, V>(Vec<(&'a String, &'a V)>);
+
+impl<V: Serialize> Serialize for Entries<'_, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().copied())
+    }
+}
+
+/// The newest JSON format version this crate reads.
+pub const LATEST_VERSION: &str = "v1";
+
+/// Rewrites a JSON document of any supported version into the `v0` field layout.
+///
+/// A missing version is read as `v0`. A `v1` document may name its collections `cells`,
+/// `funcs`, and `arrows`, after the DSL, in place of `places`, `transitions`, and `arcs`.
+fn migrate(contents: &mut Value) -> Result<(), Error> {
+    let version = contents
+        .get("version")
+        .and_then(Value::as_str)
+        .unwrap_or("v0");
+    match version {
+        "v0" => Ok(()),
+        "v1" => {
+            if let Some(object) = contents.as_object_mut() {
+                for (from, to) in [
+                    ("cells", "places"),
+                    ("funcs", "transitions"),
+                    ("arrows", "arcs"),
+                ] {
+                    if let Some(value) = object.remove(from) {
+                        object.insert(to.to_string(), value);
+                    }
+                }
+            }
+            Ok(())
+        }
+        other => Err(serde::de::Error::custom(format!(
+            "unsupported petri-net version: {other}"
+        ))),
+    }
+}
+
+/// The keys a document gives its places, transitions, and arcs, before `migrate` renames them.
+fn collection_names(contents: &Value) -> [&'static str; 3] {
+    let v1 = contents.get("version").and_then(Value::as_str) == Some("v1");
+    [
+        ("places", "cells"),
+        ("transitions", "funcs"),
+        ("arcs", "arrows"),
+    ]
+    .map(|(v0, renamed)| {
+        if v1 && contents.get(renamed).is_some() {
+            renamed
+        } else {
+            v0
+        }
+    })
+}
+
+/// Reads a migrated document into a `PetriNet`.
+///
+/// With the `json-path` feature an error names the path of the value to blame, such as
+/// `arcs[3].weight`, as tracked by the deserializer. Collections are named by `names`, as the
+/// document wrote them.
+#[cfg(feature = "json-path")]
+fn deserialize_net<'de, D>(deserializer: D, names: [&str; 3]) -> Result<PetriNet, Error>
+where
+    D: serde::Deserializer<'de, Error = Error>,
+{
+    use serde_path_to_error::Segment;
+    use std::fmt::Write;
+
+    serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        let mut path = String::new();
+        for segment in err.path() {
+            let key = match segment {
+                Segment::Seq { index } => {
+                    write!(path, "[{index}]").expect("write to string");
+                    continue;
+                }
+                Segment::Map { key } | Segment::Enum { variant: key } => key.as_str(),
+                Segment::Unknown => "?",
+            };
+            if path.is_empty() {
+                let renamed = ["places", "transitions", "arcs"]
+                    .iter()
+                    .position(|&collection| collection == key);
+                path.push_str(renamed.map_or(key, |i| names[i]));
+            } else {
+                path.push('.');
+                path.push_str(key);
+            }
+        }
+        let err = err.into_inner();
+        if path.is_empty() {
+            err
+        } else {
+            serde::de::Error::custom(format!("{path}: {err}"))
+        }
+    })
+}
+
+/// Reads a migrated document into a `PetriNet`.
+#[cfg(not(feature = "json-path"))]
+fn deserialize_net<'de, D>(deserializer: D, _names: [&str; 3]) -> Result<PetriNet, Error>
+where
+    D: serde::Deserializer<'de, Error = Error>,
+{
+    PetriNet::deserialize(deserializer)
+}
+
+fn default_version() -> String {
+    "v0".to_string()
+}
+
 impl Default for PetriNet {
     fn default() -> Self {
         Self {
             model_type: "petriNet".to_string(),
-            version: "v0".to_string(),
-            places: HashMap::new(),
-            transitions: HashMap::new(),
+            version: default_version(),
+            places: NodeMap::default(),
+            transitions: NodeMap::default(),
             arcs: Vec::new(),
         }
     }
@@ -39,29 +188,163 @@ impl PetriNet {
     }
 
     /// Creates a new `PetriNet` object from the given JSON value.
-    pub fn from_json_value(contents: Value) -> Result<Self, Error> {
-        let mut petri_net: PetriNet = serde_json::from_value(contents)?;
+    ///
+    /// Both `v0` and `v1` documents are accepted, see `migrate`; any other version is an error.
+    ///
+    /// A document may also carry a top-level `"initial"` map from place label to token count.
+    /// It is applied after the places are read, so it wins over a place's own `initial`; naming
+    /// a place that does not exist is an error.
+    ///
+    /// With the `json-path` feature, when the net fails to parse the error message starts with
+    /// the JSON path of the value to blame, such as `arcs[3].weight`.
+    pub fn from_json_value(mut contents: Value) -> Result<Self, Error> {
+        let names = collection_names(&contents);
+        migrate(&mut contents)?;
+        let initial: Option<BTreeMap<String, i32>> = contents
+            .as_object_mut()
+            .and_then(|object| object.remove("initial"))
+            .map(serde_json::from_value)
+            .transpose()?;
+        let mut petri_net = deserialize_net(&contents, names)?;
+        for (label, tokens) in initial.unwrap_or_default() {
+            let place = petri_net.places.get_mut(&label).ok_or_else(|| {
+                serde::de::Error::custom(format!("initial marking names unknown place: {label}"))
+            })?;
+            place.initial = Some(tokens);
+        }
         petri_net.populate_arc_attributes();
         Ok(petri_net)
     }
 
-    /// Creates a new `PetriNet` object from the given JSON string.
+    /// Creates a new `PetriNet` object from the given JSON string, like `from_json_value`.
+    ///
+    /// A `v0` document without an `"initial"` map is read straight from the text, so an error
+    /// in a field also reports the line and column where `serde_json` found it. Any other
+    /// document is read through a `Value`, which keeps no positions.
     pub fn from_json_str(contents: &str) -> Result<Self, Error> {
-        let mut petri_net: PetriNet = serde_json::from_str(contents)?;
+        let value: Value = serde_json::from_str(contents)?;
+        let version = value.get("version").and_then(Value::as_str);
+        if !matches!(version, None | Some("v0")) || value.get("initial").is_some() {
+            return Self::from_json_value(value);
+        }
+        let mut deserializer = serde_json::Deserializer::from_str(contents);
+        let mut petri_net = deserialize_net(&mut deserializer, collection_names(&value))?;
         petri_net.populate_arc_attributes();
         Ok(petri_net)
     }
 
+    /// Sets the JSON format version the net declares, which `from_json_str` reads it back as.
+    ///
+    /// Arcs are left as they are, since both versions read implicit flags the same way; use
+    /// `upgrade_version` to also make every arc explicit.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ModelError::UnsupportedVersion` unless the version is `v0` or `LATEST_VERSION`,
+    /// the only ones `from_json_str` can read back.
+    pub fn set_version(&mut self, version: &str) -> Result<(), ModelError> {
+        if version != "v0" && version != LATEST_VERSION {
+            return Err(ModelError::UnsupportedVersion(version.to_string()));
+        }
+        version.clone_into(&mut self.version);
+        Ok(())
+    }
+
+    /// Marks the net as `LATEST_VERSION` and makes every arc explicit with `normalize`.
+    pub fn upgrade_version(&mut self) {
+        LATEST_VERSION.clone_into(&mut self.version);
+        self.normalize();
+    }
+
+    /// Creates a new `PetriNet` object from a JSON template whose arc weights may be symbolic.
+    ///
+    /// An arc weight written as a string such as `"$n"` is replaced by `params["n"]` before
+    /// the net is parsed; an unknown parameter is reported as a JSON error.
+    pub fn from_json_with_params(
+        contents: &str,
+        params: &HashMap<String, i32>,
+    ) -> Result<Self, Error> {
+        let mut value: Value = serde_json::from_str(contents)?;
+        if let Some(arcs) = value.get_mut("arcs").and_then(Value::as_array_mut) {
+            for arc in arcs {
+                let Some(weight) = arc.get_mut("weight") else {
+                    continue;
+                };
+                if let Some(name) = weight.as_str().and_then(|w| w.strip_prefix('$')) {
+                    let resolved = params.get(name).ok_or_else(|| {
+                        serde::de::Error::custom(format!("unknown weight parameter: ${name}"))
+                    })?;
+                    *weight = Value::from(*resolved);
+                }
+            }
+        }
+        Self::from_json_value(value)
+    }
+
     /// Converts the `PetriNet` to a JSON value.
     pub fn to_json(&self) -> Result<Value, Error> {
         serde_json::to_value(self)
     }
 
+    /// Converts the `PetriNet` to indented JSON, listing places and transitions in authored order.
+    ///
+    /// With the `indexmap` feature that is the order they were declared or read in. Otherwise
+    /// it is recovered from their offsets, which the DSL assigns in declaration order, but which
+    /// a hand-written document need not follow. Either way this keeps diffs readable, where
+    /// `to_json` sorts by label. The net reads back the same with `from_json_str`.
+    pub fn to_json_pretty(&self) -> Result<String, Error> {
+        let Self {
+            model_type,
+            version,
+            places,
+            transitions,
+            arcs,
+        } = self;
+        #[cfg_attr(feature = "indexmap", allow(unused_mut))]
+        let (mut places, mut transitions): (Vec<_>, Vec<_>) =
+            (places.iter().collect(), transitions.iter().collect());
+        #[cfg(not(feature = "indexmap"))]
+        {
+            places.sort_by_key(|(label, place)| (place.offset, *label));
+            transitions.sort_by_key(|(label, transition)| (transition.offset, *label));
+        }
+        serde_json::to_string_pretty(&AuthoredOrder {
+            model_type,
+            version,
+            places: Entries(places),
+            transitions: Entries(transitions),
+            arcs,
+        })
+    }
+
     /// Converts the `PetriNet` to a canonical JSON string.
     pub fn to_json_str(&self) -> Result<String, cjson::Error> {
         self.to_json().map(|v| cjson::to_string(&v))?
     }
 
+    /// Encodes the `PetriNet` as compact DAG-CBOR bytes, for storage where the JSON form isn't needed.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let ipld = json_to_ipld(self.to_json()?);
+        Ok(DagCborCodec.encode(&ipld)?)
+    }
+
+    /// Creates a new `PetriNet` object from bytes written by `to_bytes`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let ipld: Ipld = DagCborCodec.decode(bytes)?;
+        Ok(Self::from_json_value(ipld_to_json(ipld)?)?)
+    }
+
+    /// Hashes the structure of the net, for cheap change detection.
+    ///
+    /// Places, transitions, and meta are hashed without regard to map order, so equal nets hash
+    /// alike. This is not a cryptographic hash and is only stable within one build of the crate;
+    /// use the zblob CID to identify a net across programs.
+    pub fn net_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
     /// Converts the `PetriNet` to a `Zblob` object.
     pub fn to_zblob(&self) -> Zblob {
         Zblob::from_net(self)
@@ -73,7 +356,21 @@ impl PetriNet {
     ///
     /// Panics if the diagram is invalid
     pub fn from_state_diagram(contents: String) -> Self {
-        let contents = contents.replace('\n', "").replace(" ", "");
+        Self::try_from_state_diagram(&contents).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates a new `PetriNet` object from the given state diagram string, like
+    /// `from_state_diagram`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ModelError::Parse` if a line has a weight that is not a positive integer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the diagram has more places than fit in an `i32` offset.
+    pub fn try_from_state_diagram(contents: &str) -> Result<Self, ModelError> {
+        let contents = contents.replace(['\n', ' '], "");
         let mut net = PetriNet::new();
         net.model_type = "workflow".to_string();
         let mut x = 20;
@@ -81,20 +378,15 @@ impl PetriNet {
         let grid = 80;
 
         let lines: Vec<&str> = contents.split(';').map(str::trim).collect();
-        assert!(!lines.is_empty(), "Contents cannot be empty");
 
         for line in lines {
             let action = line.to_string();
             if action.is_empty() {
                 continue;
             }
-            let parts: Vec<&str> = action.split("-->").map(str::trim).collect();
-            if parts.len() != 2 {
+            let Some((input, weight, output)) = split_weighted_arrow(&action)? else {
                 continue;
-            }
-
-            let input = parts[0];
-            let output = parts[1];
+            };
 
             if !net.places.contains_key(input) {
                 x += grid;
@@ -116,25 +408,27 @@ impl PetriNet {
             net.add_arc(ArcParams {
                 source: input,
                 target: &action,
-                weight: Some(1),
+                weight: Some(weight),
                 consume: Some(true),
                 produce: Some(false),
                 inhibit: None,
                 read: None,
+                group: None,
             });
 
             net.add_arc(ArcParams {
                 source: &action,
                 target: output,
-                weight: Some(1),
+                weight: Some(weight),
                 consume: Some(false),
                 produce: Some(true),
                 inhibit: None,
                 read: None,
+                group: None,
             });
         }
 
-        net
+        Ok(net)
     }
     /// Creates a new `PetriNet` object from the given diagram string.
     ///
@@ -142,6 +436,21 @@ impl PetriNet {
     ///
     /// Panics if the diagram is invalid
     pub fn from_diagram(contents: String) -> Self {
+        Self::try_from_diagram(&contents).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates a new `PetriNet` object from the given diagram string, like `from_diagram`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ModelError::Parse` if the first line is not a `ModelType::[type]` declaration,
+    /// or a line joins two actions, has an empty side, or has a weight that is not a positive
+    /// integer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the diagram has more places than fit in an `i32` offset.
+    pub fn try_from_diagram(contents: &str) -> Result<Self, ModelError> {
         let contents = contents.replace('\n', "");
         let mut net = PetriNet::new();
         let mut x = 20;
@@ -149,55 +458,47 @@ impl PetriNet {
         let grid = 80;
 
         let lines: Vec<&str> = contents.split(';').map(str::trim).collect();
-        assert!(!lines.is_empty(), "Contents cannot be empty");
 
         // Parse the first line to set the model type
         let first_line = lines[0];
-        assert!(
-            first_line.starts_with("ModelType::"),
-            "First line must specify the model type in the format ModelType::[type]"
-        );
+        if !first_line.starts_with("ModelType::") {
+            return Err(ModelError::Parse(
+                "First line must specify the model type in the format ModelType::[type]"
+                    .to_string(),
+            ));
+        }
 
-        net.model_type = match first_line
+        net.model_type = first_line
             .replace("ModelType::", "")
-            .to_lowercase()
-            .as_str()
-        {
-            "petrinet" => "petriNet".to_string(),
-            "workflow" => "workflow".to_string(),
-            "elementary" => "elementary".to_string(),
-            _ => panic!("Invalid ModelType: must be one of petrinet, workflow, or elementary"),
-        };
+            .parse::<ModelType>()
+            .map_err(|err| ModelError::Parse(format!("Invalid ModelType: {err}")))?
+            .to_string();
 
         for line in &lines[1..] {
             if line.is_empty() {
                 continue;
             }
-            let parts: Vec<&str> = line.split("-->").map(str::trim).collect();
-            if parts.len() != 2 {
+            let Some((first, weight, second)) = split_weighted_arrow(line)? else {
                 continue;
-            }
-            let first_param_is_state = parts[0].chars().next().expect("first char").is_uppercase();
-            let second_param_is_state = parts[1].chars().next().expect("first char").is_uppercase();
-
-            let state = if first_param_is_state {
-                parts[0]
-            } else {
-                assert!(
-                    second_param_is_state,
-                    "Second param must be uppercased state"
-                );
-                parts[1]
             };
+            let is_state = |part: &str| {
+                part.chars()
+                    .next()
+                    .map(char::is_uppercase)
+                    .ok_or_else(|| ModelError::Parse(format!("empty node in line: {line}")))
+            };
+            let first_param_is_state = is_state(first)?;
+            let second_param_is_state = is_state(second)?;
+            if !first_param_is_state && !second_param_is_state {
+                return Err(ModelError::Parse(
+                    "Second param must be uppercase state".to_string(),
+                ));
+            }
 
-            let action = if first_param_is_state {
-                parts[1]
+            let (state, action) = if first_param_is_state {
+                (first, second)
             } else {
-                assert!(
-                    second_param_is_state,
-                    "Second param must be uppercase state"
-                );
-                parts[0]
+                (second, first)
             };
 
             if !net.places.contains_key(state) {
@@ -214,26 +515,99 @@ impl PetriNet {
             net.add_arc(ArcParams {
                 source: if first_param_is_state { state } else { action },
                 target: if first_param_is_state { action } else { state },
-                weight: Some(1),
+                weight: Some(weight),
                 consume: Some(first_param_is_state),
                 produce: Some(second_param_is_state),
                 inhibit: None, // FIXME: not currently supported
                 read: None,    // FIXME not currently supported
+                group: None,
             });
         }
 
-        net
+        Ok(net)
+    }
+}
+
+/// Splits a diagram line `A --> B`, or `A --N--> B` for an arc of weight `N`, into its sides.
+///
+/// Returns `None` unless the line has exactly one arrow.
+///
+/// # Errors
+///
+/// Returns `ModelError::Parse` if the weight is not a positive integer.
+fn split_weighted_arrow(line: &str) -> Result<Option<(&str, i32, &str)>, ModelError> {
+    let Some((first, second)) = line.split_once("-->") else {
+        return Ok(None);
+    };
+    if second.contains("-->") {
+        return Ok(None);
+    }
+    let (first, weight) = match first.split_once("--") {
+        Some((node, weight)) => {
+            let weight = weight
+                .trim()
+                .parse::<i32>()
+                .ok()
+                .filter(|&w| w > 0)
+                .ok_or_else(|| ModelError::Parse(format!("invalid arc weight in line: {line}")))?;
+            (node, weight)
+        }
+        None => (first, 1),
+    };
+    Ok(Some((first.trim(), weight, second.trim())))
+}
+
+impl TryFrom<&str> for PetriNet {
+    type Error = ModelError;
+
+    /// Parses a net in whichever format `input` is written.
+    ///
+    /// Input starting with `{` is JSON, input containing `ModelType::` is a diagram, and any
+    /// other input containing `-->` is a state diagram.
+    fn try_from(input: &str) -> Result<Self, Self::Error> {
+        if input.trim_start().starts_with('{') {
+            Self::from_json_str(input).map_err(|err| ModelError::Parse(err.to_string()))
+        } else if input.contains("ModelType::") {
+            Self::try_from_diagram(input)
+        } else if input.contains("-->") {
+            Self::try_from_state_diagram(input)
+        } else {
+            Err(ModelError::Parse(
+                "expected JSON, a diagram, or a state diagram".to_string(),
+            ))
+        }
     }
 }
 
 /// Place is a struct that represents a place (cell in FLowDsl).
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Place {
     pub offset: i32,
     pub initial: Option<i32>,
+    /// The most tokens the place may hold; `None` and 0 are unbounded unless `zero_capacity` is set.
     pub capacity: Option<i32>,
+    #[serde(default)]
     pub x: i32,
+    #[serde(default)]
     pub y: i32,
+    /// A debit place may hold a negative token count instead of underflowing.
+    #[serde(
+        default,
+        rename = "allowNegative",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub allow_negative: bool,
+    /// Enforces a capacity of 0, so the place can never hold a token, instead of reading it as
+    /// unbounded the way JSON models do.
+    #[serde(
+        default,
+        rename = "zeroCapacity",
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub zero_capacity: bool,
+    /// Free-form annotations such as units or external ids, ignored by the state machine.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub meta: HashMap<String, String>,
 }
 
 impl Default for Place {
@@ -244,32 +618,184 @@ impl Default for Place {
             capacity: Option::from(0),
             x: 0,
             y: 0,
+            allow_negative: false,
+            zero_capacity: false,
+            meta: HashMap::new(),
         }
     }
 }
 
+impl Hash for PetriNet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let Self {
+            model_type,
+            version,
+            places,
+            transitions,
+            arcs,
+        } = self;
+        model_type.hash(state);
+        version.hash(state);
+        hash_unordered(places, state);
+        hash_unordered(transitions, state);
+        arcs.hash(state);
+    }
+}
+
+/// Hashes a map as the wrapping sum of its entries' digests, so the hash does not depend on
+/// iteration order.
+fn hash_unordered<'a, K, V, H>(map: impl IntoIterator<Item = (&'a K, &'a V)>, state: &mut H)
+where
+    K: Hash + 'a,
+    V: Hash + 'a,
+    H: Hasher,
+{
+    let (len, sum) = map.into_iter().fold((0usize, 0u64), |(len, sum), entry| {
+        let mut hasher = DefaultHasher::new();
+        entry.hash(&mut hasher);
+        (len + 1, sum.wrapping_add(hasher.finish()))
+    });
+    len.hash(state);
+    sum.hash(state);
+}
+
+impl Hash for Place {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let Self {
+            offset,
+            initial,
+            capacity,
+            x,
+            y,
+            allow_negative,
+            zero_capacity,
+            meta,
+        } = self;
+        (
+            offset,
+            initial,
+            capacity,
+            x,
+            y,
+            allow_negative,
+            zero_capacity,
+        )
+            .hash(state);
+        hash_unordered(meta, state);
+    }
+}
+
 /// Transition is a struct that represents a transition (func in FlowDsl).
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct Transition {
     pub role: Option<String>,
     pub offset: i32,
+    #[serde(default)]
     pub x: i32,
+    #[serde(default)]
     pub y: i32,
+    /// Free-form annotations such as descriptions or external ids, ignored by the state machine.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub meta: HashMap<String, String>,
+    /// The most firings that may be in flight at once, `None` is unbounded.
+    ///
+    /// Each firing occupies a server until `completed_by` fires and releases it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_count: Option<u32>,
+    /// The transition whose firing completes an in-flight firing of this one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_by: Option<String>,
+    /// How likely the transition is to be picked by `simulate_weighted` when several are
+    /// enabled, relative to the others; `None` counts as 1.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<u32>,
+}
+
+impl Hash for Transition {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let Self {
+            role,
+            offset,
+            x,
+            y,
+            meta,
+            server_count,
+            completed_by,
+            weight,
+        } = self;
+        (role, offset, x, y, server_count, completed_by, weight).hash(state);
+        hash_unordered(meta, state);
+    }
+}
+
+fn default_role() -> Option<String> {
+    Option::from("default".to_string())
+}
+
+fn json_to_ipld(value: Value) -> Ipld {
+    match value {
+        Value::Null => Ipld::Null,
+        Value::Bool(b) => Ipld::Bool(b),
+        Value::Number(n) => n.as_i64().map_or_else(
+            || Ipld::Float(n.as_f64().unwrap_or_default()),
+            |i| Ipld::Integer(i.into()),
+        ),
+        Value::String(s) => Ipld::String(s),
+        Value::Array(items) => Ipld::List(items.into_iter().map(json_to_ipld).collect()),
+        Value::Object(map) => Ipld::Map(
+            map.into_iter()
+                .map(|(k, v)| (k, json_to_ipld(v)))
+                .collect::<BTreeMap<_, _>>(),
+        ),
+    }
+}
+
+fn ipld_to_json(ipld: Ipld) -> Result<Value, Box<dyn std::error::Error>> {
+    Ok(match ipld {
+        Ipld::Null => Value::Null,
+        Ipld::Bool(b) => Value::Bool(b),
+        Ipld::Integer(i) => Value::from(i64::try_from(i)?),
+        Ipld::Float(f) => Value::from(f),
+        Ipld::String(s) => Value::String(s),
+        Ipld::List(items) => Value::Array(
+            items
+                .into_iter()
+                .map(ipld_to_json)
+                .collect::<Result<_, _>>()?,
+        ),
+        Ipld::Map(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| ipld_to_json(v).map(|v| (k, v)))
+                .collect::<Result<_, _>>()?,
+        ),
+        Ipld::Bytes(_) | Ipld::Link(_) => {
+            return Err("unexpected bytes or link in petri-net".into())
+        }
+    })
 }
 
 impl Default for Transition {
     fn default() -> Self {
         Self {
-            role: Option::from("default".to_string()),
+            role: default_role(),
             offset: 0,
             x: 0,
             y: 0,
+            meta: HashMap::new(),
+            server_count: None,
+            completed_by: None,
+            weight: None,
         }
     }
 }
 
 /// Arrow is a struct that represents an arrow (arc in FlowDsl).
-#[derive(Serialize, Deserialize, Debug, Clone)]
+///
+/// Besides the explicit flags, JSON from other pflow tools may give an arc a `"type"` of
+/// `normal`, `inhibitor`, or `read`, which fills in any flag left unset. As with the `read` flag,
+/// a read arc runs from the transition to the place it reads.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(from = "ArrowFields")]
 pub struct Arrow {
     pub source: String,
     pub target: String,
@@ -278,6 +804,95 @@ pub struct Arrow {
     pub produce: Option<bool>,
     pub inhibit: Option<bool>,
     pub read: Option<bool>,
+    /// Names an OR-group of guards: a transition's guards sharing a group are satisfied when any one is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+/// `ArcType` is the `"type"` key some pflow tools write on arcs in place of the boolean flags.
+///
+/// A type this crate does not know is ignored, as every type was before the key was read.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum ArcType {
+    Normal,
+    Inhibitor,
+    Read,
+}
+
+/// The JSON fields of an `Arrow`, including the optional `"type"` key.
+#[derive(Deserialize)]
+struct ArrowFields {
+    source: String,
+    target: String,
+    weight: Option<i32>,
+    consume: Option<bool>,
+    produce: Option<bool>,
+    inhibit: Option<bool>,
+    read: Option<bool>,
+    #[serde(default)]
+    group: Option<String>,
+    #[serde(rename = "type", default)]
+    kind: Option<Value>,
+}
+
+impl From<ArrowFields> for Arrow {
+    fn from(fields: ArrowFields) -> Self {
+        let mut arrow = Self {
+            source: fields.source,
+            target: fields.target,
+            weight: fields.weight,
+            consume: fields.consume,
+            produce: fields.produce,
+            inhibit: fields.inhibit,
+            read: fields.read,
+            group: fields.group,
+        };
+        match fields.kind.and_then(|kind| ArcType::deserialize(kind).ok()) {
+            Some(ArcType::Normal) => {
+                arrow.inhibit.get_or_insert(false);
+            }
+            Some(ArcType::Inhibitor) => {
+                arrow.inhibit.get_or_insert(true);
+            }
+            Some(ArcType::Read) => {
+                arrow.inhibit.get_or_insert(true);
+                arrow.read.get_or_insert(true);
+            }
+            None => {}
+        }
+        arrow
+    }
+}
+
+impl Arrow {
+    /// Checks that the arc plays exactly one role: consume, produce, or inhibit.
+    ///
+    /// An inhibitor arc is a guard whatever its consume and produce flags say, since legacy
+    /// JSON, `Dsl::guard` and `populate_arc_attributes` set them from the arc's direction.
+    /// The read flag is only meaningful on an inhibitor arc, where it turns the guard into an
+    /// enabling threshold. Unset flags are treated as false, so call this after `populate_arc_attributes`.
+    pub fn validate_flags(&self) -> Result<(), ModelError> {
+        let consume = self.consume.unwrap_or(false);
+        let produce = self.produce.unwrap_or(false);
+        let inhibit = self.inhibit.unwrap_or(false);
+        let read = self.read.unwrap_or(false);
+
+        let reason = match (consume, produce, inhibit) {
+            (_, _, true) => None,
+            (true, true, false) => Some("cannot both consume and produce"),
+            (false, false, false) => Some("must consume, produce, or inhibit"),
+            _ if read => Some("read is only allowed on an inhibitor arc"),
+            _ => None,
+        };
+        reason.map_or(Ok(()), |reason| {
+            Err(ModelError::InvalidArc {
+                source: self.source.clone(),
+                target: self.target.clone(),
+                reason: reason.to_string(),
+            })
+        })
+    }
 }
 
 impl PetriNet {
@@ -301,6 +916,63 @@ impl PetriNet {
         }
     }
 
+    /// Checks that an arc joins a declared place and a declared transition.
+    pub(crate) fn check_arc_endpoints(&self, arc: &Arrow) -> Result<(), ModelError> {
+        let declared =
+            |node: &String| self.places.contains_key(node) || self.transitions.contains_key(node);
+        if let Some(missing) = [&arc.source, &arc.target]
+            .into_iter()
+            .find(|node| !declared(node))
+        {
+            return Err(ModelError::UndeclaredNode {
+                source: arc.source.clone(),
+                target: arc.target.clone(),
+                missing: missing.clone(),
+            });
+        }
+        if self.places.contains_key(&arc.source) == self.places.contains_key(&arc.target) {
+            return Err(ModelError::InvalidArc {
+                source: arc.source.clone(),
+                target: arc.target.clone(),
+                reason: "must connect a place and a transition".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Makes every arc fully explicit, filling in a weight of 1 and the inferred consume, produce,
+    /// and read flags where they were omitted.
+    pub fn normalize(&mut self) {
+        for arc in &mut self.arcs {
+            arc.weight.get_or_insert(1);
+        }
+        self.populate_arc_attributes();
+    }
+
+    /// Rounds every place and transition coordinate to the nearest multiple of `grid`.
+    ///
+    /// Halfway values round up. Offsets and arcs are left alone, so only the layout, and with
+    /// it the CID, changes. A `grid` of zero or less leaves the net as it is.
+    pub fn snap_to_grid(&mut self, grid: i32) {
+        if grid <= 0 {
+            return;
+        }
+        let snap = |v: &mut i32| {
+            let (value, step) = (i64::from(*v), i64::from(grid));
+            let nearest = (value + step / 2).div_euclid(step) * step;
+            // a coordinate too close to the integer limits is rounded toward zero instead
+            *v = i32::try_from(nearest).unwrap_or(*v - *v % grid);
+        };
+        for place in self.places.values_mut() {
+            snap(&mut place.x);
+            snap(&mut place.y);
+        }
+        for transition in self.transitions.values_mut() {
+            snap(&mut transition.x);
+            snap(&mut transition.y);
+        }
+    }
+
     /// Adds a place to the petri-net.
     pub fn add_place(
         &mut self,
@@ -319,6 +991,9 @@ impl PetriNet {
                 capacity,
                 x,
                 y,
+                allow_negative: false,
+                zero_capacity: false,
+                meta: HashMap::new(),
             },
         );
     }
@@ -337,6 +1012,10 @@ impl PetriNet {
                 offset,
                 x,
                 y,
+                meta: HashMap::new(),
+                server_count: None,
+                completed_by: None,
+                weight: None,
             },
         );
     }
@@ -351,14 +1030,166 @@ impl PetriNet {
             produce: params.produce,
             inhibit: params.inhibit,
             read: params.read,
+            group: params.group.map(str::to_string),
         });
     }
+
+    /// Scores how alike two nets are, from 0.0 for nothing shared to 1.0 for the same structure.
+    ///
+    /// This is the Jaccard index over the labelled places, transitions, and arcs (by source and
+    /// target) of both nets. Coordinates, markings, and weights are ignored, and two empty nets
+    /// are considered identical.
+    pub fn similarity(&self, other: &Self) -> f64 {
+        let elements = |net: &Self| -> HashSet<String> {
+            net.places
+                .keys()
+                .map(|p| format!("place:{p}"))
+                .chain(net.transitions.keys().map(|t| format!("transition:{t}")))
+                .chain(
+                    net.arcs
+                        .iter()
+                        .map(|a| format!("arc:{}->{}", a.source, a.target)),
+                )
+                .collect()
+        };
+        let (ours, theirs) = (elements(self), elements(other));
+        let union = ours.union(&theirs).count();
+        if union == 0 {
+            return 1.0;
+        }
+        let shared = ours.intersection(&theirs).count();
+        f64::from(u32::try_from(shared).unwrap_or(u32::MAX))
+            / f64::from(u32::try_from(union).unwrap_or(u32::MAX))
+    }
+
+    /// Returns the arcs that end at the given place or transition, in stored order.
+    pub fn in_arcs(&self, node: &str) -> Vec<&Arrow> {
+        self.arcs.iter().filter(|arc| arc.target == node).collect()
+    }
+
+    /// Returns the arcs that start at the given place or transition, in stored order.
+    pub fn out_arcs(&self, node: &str) -> Vec<&Arrow> {
+        self.arcs.iter().filter(|arc| arc.source == node).collect()
+    }
+
+    /// Groups the transitions into layers of a coarse execution order.
+    ///
+    /// A transition follows another when it consumes from a place the other produces into.
+    /// Guards are ignored, and any arc closing a cycle is dropped, with the search starting from
+    /// transitions in canonical order. Each transition is placed one layer after the latest
+    /// transition it follows; layers list transitions in canonical order.
+    pub fn to_sequence_summary(&self) -> Vec<Vec<String>> {
+        let order: Vec<&String> = self
+            .canonical_transitions()
+            .into_iter()
+            .map(|(label, _)| label)
+            .collect();
+        let index: HashMap<&str, usize> = order
+            .iter()
+            .enumerate()
+            .map(|(i, label)| (label.as_str(), i))
+            .collect();
+        let flows = |arc: &&Arrow| !arc.inhibit.unwrap_or(false);
+
+        let mut successors = vec![Vec::new(); order.len()];
+        for produce in self.arcs.iter().filter(flows) {
+            let Some(&from) = index.get(produce.source.as_str()) else {
+                continue;
+            };
+            for consume in self.out_arcs(&produce.target).into_iter().filter(flows) {
+                if let Some(&to) = index.get(consume.target.as_str()) {
+                    successors[from].push(to);
+                }
+            }
+        }
+
+        let mut visit = Visit {
+            successors: &successors,
+            state: vec![VisitState::New; order.len()],
+            dag: vec![Vec::new(); order.len()],
+            postorder: Vec::new(),
+        };
+        for start in 0..order.len() {
+            visit.dfs(start);
+        }
+
+        let mut level = vec![0; order.len()];
+        for &u in visit.postorder.iter().rev() {
+            for &v in &visit.dag[u] {
+                level[v] = level[v].max(level[u] + 1);
+            }
+        }
+        let depth = level.iter().max().map_or(0, |&l| l + 1);
+        let mut layers = vec![Vec::new(); depth];
+        for (i, label) in order.into_iter().enumerate() {
+            layers[level[i]].push(label.clone());
+        }
+        layers
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum VisitState {
+    New,
+    OnStack,
+    Done,
+}
+
+/// A depth-first search that keeps only the forward edges of a graph, leaving it acyclic.
+struct Visit<'a> {
+    successors: &'a [Vec<usize>],
+    state: Vec<VisitState>,
+    dag: Vec<Vec<usize>>,
+    postorder: Vec<usize>,
+}
+
+impl Visit<'_> {
+    fn dfs(&mut self, u: usize) {
+        if self.state[u] != VisitState::New {
+            return;
+        }
+        self.state[u] = VisitState::OnStack;
+        for &v in &self.successors[u] {
+            if self.state[v] == VisitState::OnStack {
+                continue; // a back edge closes a cycle
+            }
+            self.dag[u].push(v);
+            self.dfs(v);
+        }
+        self.state[u] = VisitState::Done;
+        self.postorder.push(u);
+    }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
+    /// The coffee machine example from the crate docs, declared with the DSL.
+    pub fn coffee_machine(p: &mut dyn Dsl) {
+        p.cell("Water", Option::from(1), Option::from(1), 100, 200);
+        p.cell("BoiledWater", None, Option::from(1), 260, 200);
+        p.cell("CoffeeBeans", Option::from(1), Option::from(1), 376, 434);
+        p.cell("GroundCoffee", None, Option::from(1), 541, 469);
+        p.cell("Filter", Option::from(1), Option::from(1), 660, 200);
+        p.cell("CoffeeInPot", None, Option::from(1), 740, 200);
+        p.cell("Cup", Option::from(1), Option::from(1), 900, 200);
+        p.func("boil_water", "default", 191, 489);
+        p.func("brew_coffee", "default", 548, 118);
+        p.func("grind_beans", "default", 420, 200);
+        p.func("pour_coffee", "default", 820, 200);
+        p.arrow("Water", "boil_water", 1);
+        p.arrow("boil_water", "BoiledWater", 1);
+        p.arrow("CoffeeBeans", "grind_beans", 1);
+        p.arrow("grind_beans", "GroundCoffee", 1);
+        p.arrow("BoiledWater", "brew_coffee", 1);
+        p.arrow("GroundCoffee", "brew_coffee", 1);
+        p.arrow("Filter", "brew_coffee", 1);
+        p.arrow("brew_coffee", "CoffeeInPot", 1);
+        p.arrow("CoffeeInPot", "pour_coffee", 1);
+        p.arrow("Cup", "pour_coffee", 1);
+    }
+
     pub const DINING_PHILOSOPHERS: &str = r#"
     {
         "modelType": "petriNet",
@@ -456,6 +1287,350 @@ mod tests {
         assert_eq!(net.places.len(), 15);
     }
 
+    #[test]
+    fn test_arcs_by_endpoint() {
+        let mut net = PetriNet::new();
+        net.declare(coffee_machine);
+
+        let sources: Vec<&str> = net
+            .in_arcs("brew_coffee")
+            .iter()
+            .map(|arc| arc.source.as_str())
+            .collect();
+        assert_eq!(sources, vec!["BoiledWater", "GroundCoffee", "Filter"]);
+
+        let targets: Vec<&str> = net
+            .out_arcs("brew_coffee")
+            .iter()
+            .map(|arc| arc.target.as_str())
+            .collect();
+        assert_eq!(targets, vec!["CoffeeInPot"]);
+        assert!(net.in_arcs("missing").is_empty());
+    }
+
+    #[test]
+    fn test_lenient_json_defaults() {
+        let net = PetriNet::from_json_str(
+            r#"{
+                "modelType": "petriNet",
+                "places": { "p": { "offset": 0, "initial": 1 } },
+                "transitions": { "t": { "offset": 0 } },
+                "arcs": [ { "source": "p", "target": "t" } ]
+            }"#,
+        )
+        .expect("minimal json should load");
+        assert_eq!(net.version, "v0");
+        // a missing role stays missing so the CID is unchanged; it compiles as "default"
+        assert_eq!(net.transitions["t"].role, None);
+        assert_eq!((net.places["p"].x, net.places["p"].y), (0, 0));
+        assert_eq!((net.transitions["t"].x, net.transitions["t"].y), (0, 0));
+    }
+
+    #[test]
+    fn test_zero_capacity_round_trips() {
+        let json = serde_json::json!({
+            "modelType": "petriNet",
+            "version": "v0",
+            "places": { "p": { "offset": 0, "initial": 1, "capacity": 0, "x": 0, "y": 0 } },
+            "transitions": {},
+            "arcs": []
+        });
+        let mut net = PetriNet::from_json_value(json.clone()).expect("valid net");
+        assert_eq!(net.places["p"].capacity, Some(0));
+        assert_eq!(net.to_json().expect("serializes"), json);
+
+        let vm = crate::vasm::StateMachine::from_model(&mut net);
+        assert_eq!(vm.place_capacity(0), None);
+    }
+
+    const FRACTIONAL_WEIGHT: &str = r#"{
+                "modelType": "petriNet",
+                "places": { "p": { "offset": 0, "initial": 1 }, "q": { "offset": 1 } },
+                "transitions": { "t": { "offset": 0 } },
+                "arcs": [
+                    { "source": "p", "target": "t" },
+                    { "source": "t", "target": "q" },
+                    { "source": "q", "target": "t", "inhibit": true },
+                    { "source": "t", "target": "p", "weight": 1.5 }
+                ]
+            }"#;
+
+    #[test]
+    fn test_json_error_position() {
+        let err = PetriNet::from_json_str(FRACTIONAL_WEIGHT).expect_err("fractional weight");
+        assert!(err.to_string().ends_with(" at line 9 column 65"), "{err}");
+
+        let err = PetriNet::from_json_str("{\n  \"modelType\": 7\n}").expect_err("bad type");
+        assert!(err.to_string().ends_with(" at line 2 column 16"), "{err}");
+        #[cfg(not(feature = "json-path"))]
+        assert_eq!((err.line(), err.column()), (2, 16));
+    }
+
+    #[cfg(feature = "json-path")]
+    #[test]
+    fn test_json_error_path() {
+        let err = PetriNet::from_json_str(FRACTIONAL_WEIGHT).expect_err("fractional weight");
+        assert!(err.to_string().starts_with("arcs[3].weight: "), "{err}");
+
+        let err = PetriNet::from_json_value(serde_json::json!({
+            "modelType": "petriNet",
+            "version": "v1",
+            "cells": { "p": { "offset": 0, "initial": "one" } },
+            "funcs": {},
+            "arrows": []
+        }))
+        .expect_err("string marking");
+        assert!(err.to_string().starts_with("cells.p.initial: "), "{err}");
+
+        let err = PetriNet::from_json_value(serde_json::json!({
+            "modelType": 7, "places": {}, "transitions": {}, "arcs": []
+        }))
+        .expect_err("numeric model type");
+        assert!(err.to_string().starts_with("modelType: "), "{err}");
+    }
+
+    #[test]
+    fn test_arc_type_key() {
+        use crate::vasm::Vasm;
+
+        let load = |initial: i32, kind: &str| {
+            // a read arc is stored transition-first, like the DSL's
+            let (source, target) = if kind == "read" {
+                ("t", "q")
+            } else {
+                ("q", "t")
+            };
+            let mut net = PetriNet::from_json_str(&format!(
+                r#"{{
+                    "modelType": "petriNet",
+                    "places": {{
+                        "p": {{ "offset": 0, "initial": 1 }},
+                        "q": {{ "offset": 1, "initial": {initial} }}
+                    }},
+                    "transitions": {{ "t": {{ "offset": 0 }} }},
+                    "arcs": [
+                        {{ "source": "p", "target": "t", "type": "normal" }},
+                        {{ "source": "{source}", "target": "{target}", "type": "{kind}" }}
+                    ]
+                }}"#
+            ))
+            .expect("typed arcs should load");
+            assert_eq!(net.arcs[0].inhibit, Some(false));
+            assert_eq!(net.arcs[1].inhibit, Some(true));
+            crate::vasm::StateMachine::from_model(&mut net)
+        };
+
+        let vm = load(1, "inhibitor");
+        let tx = vm.transform(&vm.initial_vector(), "t", 1);
+        assert!(tx.is_err() && tx.inhibited);
+        let vm = load(0, "inhibitor");
+        assert_eq!(
+            vm.transform(&vm.initial_vector(), "t", 1).output,
+            vec![0, 0]
+        );
+
+        let vm = load(0, "read");
+        assert!(vm.transform(&vm.initial_vector(), "t", 1).inhibited);
+        let vm = load(1, "read");
+        assert_eq!(
+            vm.transform(&vm.initial_vector(), "t", 1).output,
+            vec![0, 1]
+        );
+
+        let net = PetriNet::from_json_value(serde_json::json!({
+            "modelType": "petriNet",
+            "places": { "p": { "offset": 0, "initial": 1 } },
+            "transitions": { "t": { "offset": 0 } },
+            "arcs": [
+                { "source": "p", "target": "t", "type": "reset" },
+                { "source": "p", "target": "t", "type": 3 }
+            ]
+        }))
+        .expect("an unknown type is ignored");
+        for arc in &net.arcs {
+            assert_eq!(arc.inhibit, None);
+            assert_eq!(arc.consume, Some(true));
+        }
+    }
+
+    #[test]
+    fn test_sequence_summary() {
+        let mut net = PetriNet::new();
+        net.declare(coffee_machine);
+        assert_eq!(
+            net.to_sequence_summary(),
+            vec![
+                vec!["boil_water", "grind_beans"],
+                vec!["brew_coffee"],
+                vec!["pour_coffee"],
+            ]
+        );
+
+        let mut cycle = PetriNet::new();
+        cycle.declare(|p| {
+            p.cell("idle", Option::from(1), None, 100, 100);
+            p.cell("busy", None, None, 200, 100);
+            p.func("start", "default", 150, 50);
+            p.func("stop", "default", 150, 150);
+            p.arrow("idle", "start", 1);
+            p.arrow("start", "busy", 1);
+            p.arrow("busy", "stop", 1);
+            p.arrow("stop", "idle", 1);
+        });
+        assert_eq!(
+            cycle.to_sequence_summary(),
+            vec![vec!["start"], vec!["stop"]]
+        );
+    }
+
+    #[test]
+    fn test_top_level_initial_map() {
+        let load = |initial: &str| {
+            PetriNet::from_json_str(&format!(
+                r#"{{
+                    "modelType": "petriNet",
+                    "places": {{
+                        "Water": {{ "offset": 0, "initial": 3 }},
+                        "Steam": {{ "offset": 1 }}
+                    }},
+                    "transitions": {{}},
+                    "arcs": [],
+                    "initial": {initial}
+                }}"#
+            ))
+        };
+        let net = load(r#"{ "Water": 1, "Steam": 2 }"#).expect("initial map should load");
+        assert_eq!(net.places["Water"].initial, Some(1));
+        assert_eq!(net.places["Steam"].initial, Some(2));
+
+        let err = load(r#"{ "Ice": 1 }"#).expect_err("unknown place");
+        assert!(err.to_string().contains("unknown place: Ice"));
+    }
+
+    #[test]
+    fn test_normalize_fills_weights() {
+        let mut net =
+            PetriNet::from_json_str(DINING_PHILOSOPHERS).expect("Failed to create PetriNet");
+        assert!(net.arcs.iter().any(|arc| arc.weight.is_none()));
+
+        net.normalize();
+        assert!(net.arcs.iter().all(|arc| arc.weight == Some(1)));
+        assert!(net.arcs.iter().all(|arc| arc.consume.is_some()));
+    }
+
+    #[test]
+    fn test_snap_to_grid() {
+        let original =
+            PetriNet::from_json_str(DINING_PHILOSOPHERS).expect("Failed to create PetriNet");
+        let mut net = original.clone();
+        net.snap_to_grid(10);
+        assert!(net.places.values().all(|p| p.x % 10 == 0 && p.y % 10 == 0));
+        assert!(net
+            .transitions
+            .values()
+            .all(|t| t.x % 10 == 0 && t.y % 10 == 0));
+        assert_eq!(net.arcs, original.arcs);
+        for (label, place) in &net.places {
+            let before = &original.places[label];
+            assert_eq!(place.offset, before.offset);
+            assert!((place.x - before.x).abs() <= 5 && (place.y - before.y).abs() <= 5);
+        }
+        assert_ne!(net.to_zblob().ipfs_cid, original.to_zblob().ipfs_cid);
+
+        let mut edges = PetriNet::new();
+        edges.add_place("p", 0, None, None, -15, 24);
+        edges.add_transition("t", "default", 25, i32::MAX);
+        edges.snap_to_grid(10);
+        assert_eq!((edges.places["p"].x, edges.places["p"].y), (-10, 20));
+        assert_eq!(
+            (edges.transitions["t"].x, edges.transitions["t"].y),
+            (30, 2_147_483_640)
+        );
+    }
+
+    #[test]
+    fn test_similarity() {
+        let mut coffee = PetriNet::new();
+        coffee.declare(coffee_machine);
+        let dining =
+            PetriNet::from_json_str(DINING_PHILOSOPHERS).expect("Failed to create PetriNet");
+        assert!((coffee.similarity(&coffee) - 1.0).abs() < f64::EPSILON);
+        assert!(coffee.similarity(&dining) < 0.1);
+
+        let mut tweaked = coffee.clone();
+        tweaked.arcs.pop();
+        let score = coffee.similarity(&tweaked);
+        assert!(score > 0.9 && score < 1.0);
+        assert!((score - tweaked.similarity(&coffee)).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_version_migration() {
+        let v0 = PetriNet::from_json_str(
+            r#"{
+                "modelType": "petriNet",
+                "version": "v0",
+                "places": { "p": { "offset": 0, "initial": 1, "x": 10, "y": 20 } },
+                "transitions": { "t": { "offset": 0, "x": 30, "y": 20 } },
+                "arcs": [ { "source": "p", "target": "t" } ]
+            }"#,
+        )
+        .expect("v0 loads");
+        let v1 = PetriNet::from_json_str(
+            r#"{
+                "modelType": "petriNet",
+                "version": "v1",
+                "cells": { "p": { "offset": 0, "initial": 1, "x": 10, "y": 20 } },
+                "funcs": { "t": { "offset": 0, "x": 30, "y": 20 } },
+                "arrows": [ { "source": "p", "target": "t" } ]
+            }"#,
+        )
+        .expect("v1 loads");
+        assert_eq!(v1.version, "v1");
+        assert_eq!(v0.places, v1.places);
+        assert_eq!(v0.transitions, v1.transitions);
+        assert_eq!(v0.arcs, v1.arcs);
+
+        let mut upgraded = v0;
+        upgraded.upgrade_version();
+        assert_eq!(upgraded.version, LATEST_VERSION);
+        assert_eq!(upgraded.arcs[0].weight, Some(1));
+        let json = upgraded.to_json_str().expect("serializes");
+        assert_eq!(PetriNet::from_json_str(&json).expect("reloads"), upgraded);
+
+        let err = PetriNet::from_json_str(
+            r#"{ "modelType": "petriNet", "version": "v9", "places": {}, "transitions": {}, "arcs": [] }"#,
+        )
+        .expect_err("unknown version");
+        assert!(err
+            .to_string()
+            .contains("unsupported petri-net version: v9"));
+    }
+
+    #[test]
+    fn test_meta_round_trip() {
+        let net = PetriNet::from_json_str(
+            r#"{
+                "modelType": "petriNet",
+                "places": { "milk": { "offset": 0, "initial": 250, "meta": { "unit": "ml" } } },
+                "transitions": { "pour": { "offset": 0, "meta": { "id": "db-42" } } },
+                "arcs": [ { "source": "milk", "target": "pour", "weight": 50 } ]
+            }"#,
+        )
+        .expect("annotated json loads");
+        assert_eq!(net.places["milk"].meta["unit"], "ml");
+        assert_eq!(net.transitions["pour"].meta["id"], "db-42");
+
+        let json = net.to_json_str().expect("serializes");
+        let reloaded = PetriNet::from_json_str(&json).expect("reloads");
+        assert_eq!(reloaded, net);
+        assert!(!PetriNet::from_json_str(DINING_PHILOSOPHERS)
+            .expect("Failed to create PetriNet")
+            .to_json_str()
+            .expect("serializes")
+            .contains("meta"));
+    }
+
     #[test]
     fn test_zblob() {
         let petri_net =
@@ -490,6 +1665,100 @@ mod tests {
         print!("{pretty_json}");
     }
 
+    #[test]
+    fn test_json_pretty_keeps_authored_order() {
+        let mut net = PetriNet::new();
+        net.declare(|p| {
+            p.cell("zebra", Option::from(1), None, 0, 0);
+            p.cell("apple", None, None, 0, 0);
+            p.cell("mango", None, None, 0, 0);
+            p.func("walk", "default", 0, 0);
+            p.func("eat", "default", 0, 0);
+            p.arrow("zebra", "walk", 1);
+            p.arrow("walk", "apple", 1);
+        });
+        let pretty = net.to_json_pretty().expect("pretty json");
+        let position = |key: &str| pretty.find(&format!("\"{key}\": {{")).expect(key);
+        assert!(position("zebra") < position("apple"));
+        assert!(position("apple") < position("mango"));
+        assert!(position("walk") < position("eat"));
+        assert!(pretty.starts_with("{\n  \"modelType\""));
+        let read = PetriNet::from_json_str(&pretty).expect("reads back");
+        net.populate_arc_attributes();
+        assert_eq!(read, net);
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn test_to_json_pretty_keeps_document_order() {
+        let net = PetriNet::from_json_str(
+            r#"{
+                "modelType": "petriNet",
+                "places": { "late": { "offset": 1 }, "early": { "offset": 0 } },
+                "transitions": { "second": { "offset": 1 }, "first": { "offset": 0 } },
+                "arcs": [{ "source": "early", "target": "first" }]
+            }"#,
+        )
+        .expect("valid net");
+        let pretty = net.to_json_pretty().expect("pretty json");
+        let position = |key: &str| pretty.find(&format!("\"{key}\": {{")).expect(key);
+        assert!(position("late") < position("early"));
+        assert!(position("second") < position("first"));
+    }
+
+    #[test]
+    fn test_to_json_pretty_matches_to_json() {
+        for net in [
+            PetriNet::from_json_str(DINING_PHILOSOPHERS).expect("valid net"),
+            crate::model::Model::new(coffee_machine).net,
+        ] {
+            let pretty: Value = serde_json::from_str(&net.to_json_pretty().expect("pretty json"))
+                .expect("valid json");
+            assert_eq!(pretty, net.to_json().expect("json"));
+        }
+    }
+
+    #[test]
+    fn test_weighted_diagram_arcs() {
+        let mut net = PetriNet::from_state_diagram(
+            "[*] --> Idle; Idle --3--> Busy; Busy --> Idle;".to_string(),
+        );
+        let vm = crate::vasm::StateMachine::from_model(&mut net);
+        let offset = |label: &str| vm.places.iter().position(|p| p == label).expect(label);
+        let delta = &vm.transitions["Idle--3-->Busy"].delta;
+        assert_eq!(delta[offset("Idle")], -3);
+        assert_eq!(delta[offset("Busy")], 3);
+        assert_eq!(vm.transitions["Busy-->Idle"].delta[offset("Busy")], -1);
+
+        let net =
+            PetriNet::try_from_diagram("ModelType::PetriNet; Water --2--> boil; boil --> Steam;")
+                .expect("valid diagram");
+        let weight = |source: &str| {
+            net.arcs
+                .iter()
+                .find(|a| a.source == source)
+                .and_then(|a| a.weight)
+        };
+        assert_eq!(weight("Water"), Some(2));
+        assert_eq!(weight("boil"), Some(1));
+        assert!(net.places.contains_key("Water"));
+
+        for arc in ["boil --x--> Ash", "boil --0--> Ash", "boil ---1--> Ash"] {
+            let diagram = format!("ModelType::PetriNet; Water --> boil; {arc};");
+            assert!(
+                matches!(
+                    PetriNet::try_from_diagram(&diagram),
+                    Err(ModelError::Parse(err)) if err.contains("invalid arc weight")
+                ),
+                "{arc}"
+            );
+        }
+        assert!(matches!(
+            PetriNet::try_from("A --x--> B"),
+            Err(ModelError::Parse(err)) if err.contains("invalid arc weight")
+        ));
+    }
+
     #[test]
     fn test_state_machine_diagram() {
         let contents = r"
@@ -506,4 +1775,71 @@ mod tests {
         println!("{:?}", net.places);
         println!("https://pflow.dev/?z={}", zblob.base64_zipped);
     }
+
+    #[test]
+    fn test_try_from_str_detects_format() {
+        let json = PetriNet::try_from(DINING_PHILOSOPHERS).expect("json");
+        assert_eq!(json.places.len(), 15);
+
+        let diagram =
+            PetriNet::try_from("ModelType::Workflow; Water --> boil_water; boil_water --> Steam;")
+                .expect("diagram");
+        assert_eq!(diagram.model_type, "workflow");
+        assert_eq!(diagram.places.len(), 2);
+
+        let states = PetriNet::try_from("[*] --> Still; Still --> Moving;").expect("state diagram");
+        assert_eq!(states.transitions.len(), 2);
+        assert!(states.transitions.contains_key("Still-->Moving"));
+
+        let err = PetriNet::try_from("not a net").expect_err("unknown format");
+        assert!(matches!(err, ModelError::Parse(_)));
+        let err = PetriNet::try_from("ModelType::Nope; A --> b;").expect_err("bad model type");
+        assert!(err.to_string().contains("Invalid ModelType"));
+        assert!(PetriNet::try_from("ModelType::PetriNet; a --> b;").is_err());
+        let err = PetriNet::try_from("[*] --> Still; Still --0--> Moving;")
+            .expect_err("bad state diagram weight");
+        assert!(matches!(err, ModelError::Parse(_)));
+    }
+
+    #[test]
+    fn test_json_with_params() {
+        let template = r#"{
+            "modelType": "petriNet",
+            "version": "v0",
+            "places": {
+                "pool": { "offset": 0, "initial": 10, "x": 100, "y": 100 },
+                "batch": { "offset": 1, "x": 200, "y": 100 }
+            },
+            "transitions": {
+                "take": { "offset": 0, "x": 150, "y": 100 }
+            },
+            "arcs": [
+                { "source": "pool", "target": "take", "weight": "$n" },
+                { "source": "take", "target": "batch", "weight": 1 }
+            ]
+        }"#;
+
+        for n in [3, 5] {
+            let params = HashMap::from([("n".to_string(), n)]);
+            let mut net =
+                PetriNet::from_json_with_params(template, &params).expect("template resolves");
+            assert_eq!(net.arcs[0].weight, Some(n));
+            let vm = crate::vasm::StateMachine::from_model(&mut net);
+            assert_eq!(vm.transitions["take"].delta, vec![-n, 1]);
+        }
+
+        let err = PetriNet::from_json_with_params(template, &HashMap::new())
+            .expect_err("missing parameter");
+        assert!(err.to_string().contains("unknown weight parameter: $n"));
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let net = PetriNet::from_json_str(DINING_PHILOSOPHERS).expect("Failed to create PetriNet");
+        let bytes = net.to_bytes().expect("encode failed");
+        assert!(bytes.len() < net.to_json_str().expect("json").len());
+
+        let decoded = PetriNet::from_bytes(&bytes).expect("decode failed");
+        assert_eq!(decoded, net);
+    }
 }
@@ -0,0 +1,1029 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fmt::Write;
+
+use crate::vasm::{StateMachine, Vasm, Vector};
+
+/// The default number of markings a state space search will visit before giving up.
+pub const DEFAULT_STATE_LIMIT: usize = 10_000;
+
+/// The token count standing in for omega, an unbounded place, in a coverability search.
+///
+/// It is large enough to cover any arc weight yet leaves headroom so firing cannot overflow.
+const OMEGA: i32 = i32::MAX / 2;
+
+/// `Truncated` is returned when a state space search exceeds its node limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Truncated {
+    /// The limit that was exceeded.
+    pub limit: usize,
+}
+
+impl fmt::Display for Truncated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "state space exceeds {} markings", self.limit)
+    }
+}
+
+impl std::error::Error for Truncated {}
+
+/// `ReachabilityGraph` holds the markings reachable from the initial state and the firings between them.
+#[derive(Debug, Clone)]
+pub struct ReachabilityGraph {
+    /// The reachable markings, index 0 is the initial marking.
+    pub states: Vec<Vector>,
+    /// The firings as `(from, action, to)` indices into `states`.
+    pub edges: Vec<(usize, String, usize)>,
+}
+
+impl ReachabilityGraph {
+    /// Writes the graph in Graphviz DOT format.
+    ///
+    /// Each marking is a node labelled with its index and token counts, and each firing is an
+    /// edge labelled with its action. The initial marking is drawn filled.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph reachability {\n");
+        for (i, state) in self.states.iter().enumerate() {
+            let tokens: Vec<String> = state.iter().map(ToString::to_string).collect();
+            let style = if i == 0 {
+                ", style=filled, fillcolor=lightgrey"
+            } else {
+                ""
+            };
+            writeln!(
+                dot,
+                "  s{i} [label=\"s{i}\\n[{}]\"{style}];",
+                tokens.join(",")
+            )
+            .expect("write failed");
+        }
+        for (from, action, to) in &self.edges {
+            let action = action.replace('"', "\\\"");
+            writeln!(dot, "  s{from} -> s{to} [label=\"{action}\"];").expect("write failed");
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// `DeterminismConflict` records a marking where more than one transition of a role is enabled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeterminismConflict {
+    /// The marking where the choice occurs.
+    pub marking: Vector,
+    /// The role (input symbol) shared by the enabled transitions.
+    pub role: String,
+    /// The enabled transitions, ordered by offset.
+    pub actions: Vec<String>,
+}
+
+/// `Divergence` is a marking where two machines enable different actions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The marking, reachable in both machines.
+    pub marking: Vector,
+    /// Actions enabled by the first machine only, ordered by its offsets.
+    pub only_self: Vec<String>,
+    /// Actions enabled by the second machine only, ordered by its offsets.
+    pub only_other: Vec<String>,
+}
+
+/// `SemanticDiff` lists the markings where two machines over the same places behave differently.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SemanticDiff {
+    /// The divergent markings, in the first machine's breadth-first order.
+    pub divergences: Vec<Divergence>,
+    /// How many markings are reachable in both machines.
+    pub common_markings: usize,
+    /// The machines have different places, so their markings were not compared.
+    pub incompatible: bool,
+}
+
+impl SemanticDiff {
+    /// Checks that the machines were compared and every common marking enables the same actions
+    /// in both.
+    pub const fn is_empty(&self) -> bool {
+        !self.incompatible && self.divergences.is_empty()
+    }
+}
+
+impl StateMachine {
+    /// Lists the actions enabled in the given state, ordered by offset.
+    pub fn enabled_actions(&self, state: &Vector) -> Vec<String> {
+        self.actions
+            .iter()
+            .filter(|action| self.transform(state, action, 1).is_ok())
+            .cloned()
+            .collect()
+    }
+
+    /// Builds the reachability graph by breadth-first search from the initial marking.
+    ///
+    /// Returns `Err(Truncated)` if more than `limit` markings are reachable.
+    pub fn reachability_graph(&self, limit: usize) -> Result<ReachabilityGraph, Truncated> {
+        let initial = self.initial_vector();
+        let mut index: HashMap<Vector, usize> = HashMap::new();
+        index.insert(initial.clone(), 0);
+        let mut graph = ReachabilityGraph {
+            states: vec![initial],
+            edges: Vec::new(),
+        };
+
+        let mut queue = VecDeque::from([0]);
+        while let Some(from) = queue.pop_front() {
+            let state = graph.states[from].clone();
+            for (action, output) in self.successors(&state) {
+                let to = if let Some(&to) = index.get(&output) {
+                    to
+                } else {
+                    if graph.states.len() >= limit {
+                        return Err(Truncated { limit });
+                    }
+                    let to = graph.states.len();
+                    index.insert(output.clone(), to);
+                    graph.states.push(output);
+                    queue.push_back(to);
+                    to
+                };
+                graph.edges.push((from, action, to));
+            }
+        }
+        Ok(graph)
+    }
+
+    /// Compares which actions two machines enable in every marking reachable in both.
+    ///
+    /// This shows what changes when a net is run under another model type, such as the
+    /// single-output rule of `elementary` or the clamping of `workflow`. Markings are compared
+    /// as vectors, so unless both machines have the same places in the same order the diff is
+    /// returned empty and flagged `incompatible`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(Truncated)` if either machine has more than `DEFAULT_STATE_LIMIT`
+    /// reachable markings.
+    pub fn semantic_diff(&self, other: &Self) -> Result<SemanticDiff, Truncated> {
+        if self.places != other.places {
+            return Ok(SemanticDiff {
+                incompatible: true,
+                ..SemanticDiff::default()
+            });
+        }
+        let ours = self.reachability_graph(DEFAULT_STATE_LIMIT)?;
+        let theirs: HashSet<Vector> = other
+            .reachability_graph(DEFAULT_STATE_LIMIT)?
+            .states
+            .into_iter()
+            .collect();
+        let mut diff = SemanticDiff::default();
+        for marking in ours.states.iter().filter(|m| theirs.contains(*m)) {
+            diff.common_markings += 1;
+            let (a, b) = (
+                self.enabled_actions(marking),
+                other.enabled_actions(marking),
+            );
+            let only_self: Vec<String> = a.iter().filter(|x| !b.contains(x)).cloned().collect();
+            let only_other: Vec<String> = b.iter().filter(|x| !a.contains(x)).cloned().collect();
+            if !only_self.is_empty() || !only_other.is_empty() {
+                diff.divergences.push(Divergence {
+                    marking: marking.clone(),
+                    only_self,
+                    only_other,
+                });
+            }
+        }
+        Ok(diff)
+    }
+
+    /// Lists every reachable marking in which `action` is enabled, in breadth-first order.
+    ///
+    /// An unknown action is never enabled. Returns `Err(Truncated)` if more than
+    /// `DEFAULT_STATE_LIMIT` markings are reachable.
+    pub fn enabling_markings(&self, action: &str) -> Result<Vec<Vector>, Truncated> {
+        let graph = self.reachability_graph(DEFAULT_STATE_LIMIT)?;
+        let mut enabling: Vec<usize> = graph
+            .edges
+            .iter()
+            .filter(|(_, a, _)| a == action)
+            .map(|&(from, _, _)| from)
+            .collect();
+        enabling.dedup();
+        Ok(enabling
+            .into_iter()
+            .map(|i| graph.states[i].clone())
+            .collect())
+    }
+
+    /// Classifies how live `action` is: 0 if it is dead, 1 if it can fire in some reachable
+    /// marking, or 4 if it is live, so that it can still fire eventually from every reachable marking.
+    ///
+    /// Levels 2 and 3 of the classical hierarchy need unbounded firing counts and are not reported.
+    /// Returns `Err(Truncated)` if more than `DEFAULT_STATE_LIMIT` markings are reachable.
+    pub fn liveness_level(&self, action: &str) -> Result<u8, Truncated> {
+        let graph = self.reachability_graph(DEFAULT_STATE_LIMIT)?;
+        let mut predecessors = vec![Vec::new(); graph.states.len()];
+        let mut can_fire = vec![false; graph.states.len()];
+        let mut queue = VecDeque::new();
+        for (from, a, to) in &graph.edges {
+            predecessors[*to].push(*from);
+            if a == action && !can_fire[*from] {
+                can_fire[*from] = true;
+                queue.push_back(*from);
+            }
+        }
+        if queue.is_empty() {
+            return Ok(0);
+        }
+        // walk back from the enabling markings to every marking that can still reach one
+        while let Some(state) = queue.pop_front() {
+            for &from in &predecessors[state] {
+                if !can_fire[from] {
+                    can_fire[from] = true;
+                    queue.push_back(from);
+                }
+            }
+        }
+        Ok(if can_fire.iter().all(|&c| c) { 4 } else { 1 })
+    }
+
+    /// Counts the reachable markings without storing the firings between them.
+    ///
+    /// This is the same search as `reachability_graph`, keeping only the set of visited markings.
+    ///
+    /// Returns `Err(Truncated)` if more than `limit` markings are reachable.
+    pub fn reachable_count(&self, limit: usize) -> Result<usize, Truncated> {
+        let initial = self.initial_vector();
+        let mut visited: HashSet<Vector> = HashSet::from([initial.clone()]);
+        let mut queue = VecDeque::from([initial]);
+        while let Some(state) = queue.pop_front() {
+            for action in &self.actions {
+                let tx = self.transform(&state, action, 1);
+                if tx.is_err() || visited.contains(&tx.output) {
+                    continue;
+                }
+                if visited.len() >= limit {
+                    return Err(Truncated { limit });
+                }
+                visited.insert(tx.output.clone());
+                queue.push_back(tx.output);
+            }
+        }
+        Ok(visited.len())
+    }
+
+    /// Searches breadth-first from the initial marking for one that satisfies `found`.
+    ///
+    /// Returns as soon as a match is seen, so an unbounded net can still answer if the match is near.
+    fn find_reachable(&self, found: impl Fn(&Vector) -> bool) -> Result<Option<Vector>, Truncated> {
+        let limit = DEFAULT_STATE_LIMIT;
+        let initial = self.initial_vector();
+        if found(&initial) {
+            return Ok(Some(initial));
+        }
+        let mut visited: HashSet<Vector> = HashSet::from([initial.clone()]);
+        let mut queue = VecDeque::from([initial]);
+        while let Some(state) = queue.pop_front() {
+            for (_, output) in self.successors(&state) {
+                if visited.contains(&output) {
+                    continue;
+                }
+                if found(&output) {
+                    return Ok(Some(output));
+                }
+                if visited.len() >= limit {
+                    return Err(Truncated { limit });
+                }
+                visited.insert(output.clone());
+                queue.push_back(output);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Finds the shortest firing sequence from the initial marking to a dead marking.
+    ///
+    /// A dead marking enables no action. The search is breadth-first, so the sequence is as
+    /// short as any, ties going to actions with lower offsets. An initially dead net gives an
+    /// empty sequence. Returns `Ok(None)` if the net is deadlock-free, or `Err(Truncated)` if no
+    /// dead marking is found within `DEFAULT_STATE_LIMIT` markings.
+    pub fn shortest_path_to_deadlock(&self) -> Result<Option<Vec<String>>, Truncated> {
+        let limit = DEFAULT_STATE_LIMIT;
+        let initial = self.initial_vector();
+        let mut parents: HashMap<Vector, Option<(Vector, String)>> =
+            HashMap::from([(initial.clone(), None)]);
+        let mut queue = VecDeque::from([initial]);
+        while let Some(state) = queue.pop_front() {
+            let successors = self.successors(&state);
+            if successors.is_empty() {
+                let mut path = Vec::new();
+                let mut at = &state;
+                while let Some((parent, action)) = &parents[at] {
+                    path.push(action.clone());
+                    at = parent;
+                }
+                path.reverse();
+                return Ok(Some(path));
+            }
+            for (action, output) in successors {
+                if parents.contains_key(&output) {
+                    continue;
+                }
+                if parents.len() >= limit {
+                    return Err(Truncated { limit });
+                }
+                parents.insert(output.clone(), Some((state.clone(), action)));
+                queue.push_back(output);
+            }
+        }
+        Ok(None)
+    }
+
+    /// Checks whether some reachable marking holds at least `k` tokens in the place at `place_offset`.
+    ///
+    /// Returns `Err(Truncated)` if no such marking is found within `DEFAULT_STATE_LIMIT` markings.
+    pub fn can_exceed(&self, place_offset: usize, k: i32) -> Result<bool, Truncated> {
+        self.find_reachable(|state| state.get(place_offset).is_some_and(|&t| t >= k))
+            .map(|marking| marking.is_some())
+    }
+
+    /// Computes the most tokens each place can hold, indexed by offset, or `None` if it is unbounded.
+    ///
+    /// The Karp-Miller coverability tree is explored from the initial marking: whenever a
+    /// marking strictly covers one of its ancestors, the places that grew are set to omega, so
+    /// an unbounded net still gives a finite tree. Places with a declared capacity are never
+    /// accelerated. Inhibitor arcs treat omega as more tokens than any guard allows, so for
+    /// nets with guards on unbounded places the result is an approximation.
+    ///
+    /// Returns `Err(Truncated)` if the tree has more than `DEFAULT_STATE_LIMIT` nodes.
+    pub fn place_bounds(&self) -> Result<Vec<Option<i32>>, Truncated> {
+        let limit = DEFAULT_STATE_LIMIT;
+        let initial = self.initial_vector();
+        let mut bounds: Vec<Option<i32>> = initial.iter().map(|&t| Some(t)).collect();
+        let mut nodes: Vec<(Vector, Option<usize>)> = vec![(initial.clone(), None)];
+        let mut visited: HashSet<Vector> = HashSet::from([initial]);
+        let mut queue = VecDeque::from([0]);
+        while let Some(node) = queue.pop_front() {
+            let state = nodes[node].0.clone();
+            for action in &self.actions {
+                let tx = self.transform(&state, action, 1);
+                if tx.is_err() {
+                    continue;
+                }
+                let mut next = tx.output;
+                for (i, tokens) in next.iter_mut().enumerate() {
+                    if state[i] >= OMEGA {
+                        *tokens = OMEGA;
+                    }
+                }
+                let mut ancestor = Some(node);
+                while let Some(a) = ancestor {
+                    let (marking, parent) = &nodes[a];
+                    if marking != &next && marking.iter().zip(&next).all(|(m, n)| m <= n) {
+                        for i in 0..next.len() {
+                            if marking[i] < next[i] && self.place_capacity(i).is_none() {
+                                next[i] = OMEGA;
+                            }
+                        }
+                    }
+                    ancestor = *parent;
+                }
+                if !visited.insert(next.clone()) {
+                    continue;
+                }
+                if nodes.len() >= limit {
+                    return Err(Truncated { limit });
+                }
+                for (bound, &tokens) in bounds.iter_mut().zip(&next) {
+                    *bound = match *bound {
+                        Some(_) if tokens >= OMEGA => None,
+                        Some(b) => Some(b.max(tokens)),
+                        None => None,
+                    };
+                }
+                nodes.push((next, Some(node)));
+                queue.push_back(nodes.len() - 1);
+            }
+        }
+        Ok(bounds)
+    }
+
+    /// Finds a reachable marking where the places at offsets `a` and `b` are both marked.
+    ///
+    /// Returns `Ok(None)` when the two places are mutually exclusive, or `Err(Truncated)` if no
+    /// counterexample is found within `DEFAULT_STATE_LIMIT` markings.
+    pub fn mutual_exclusion_violation(
+        &self,
+        a: usize,
+        b: usize,
+    ) -> Result<Option<Vector>, Truncated> {
+        let marked = |state: &Vector, i: usize| state.get(i).is_some_and(|&t| t > 0);
+        self.find_reachable(|state| marked(state, a) && marked(state, b))
+    }
+
+    /// Checks that no reachable marking has both places `a` and `b` marked.
+    ///
+    /// Use `mutual_exclusion_violation` to get the offending marking.
+    pub fn mutual_exclusion_holds(&self, a: usize, b: usize) -> Result<bool, Truncated> {
+        self.mutual_exclusion_violation(a, b)
+            .map(|violation| violation.is_none())
+    }
+
+    /// Finds every reachable single-token marking where two or more transitions
+    /// sharing a role (input symbol) are enabled at once.
+    pub fn determinism_conflicts(&self) -> Result<Vec<DeterminismConflict>, Truncated> {
+        let graph = self.reachability_graph(DEFAULT_STATE_LIMIT)?;
+        let mut conflicts = Vec::new();
+        for state in &graph.states {
+            if state.iter().filter(|&&tokens| tokens > 0).count() != 1 {
+                continue;
+            }
+            let mut by_role: Vec<(String, Vec<String>)> = Vec::new();
+            for action in self.enabled_actions(state) {
+                let role = self.transitions[&action].role.clone();
+                match by_role.iter_mut().find(|(r, _)| *r == role) {
+                    Some((_, actions)) => actions.push(action),
+                    None => by_role.push((role, vec![action])),
+                }
+            }
+            for (role, actions) in by_role {
+                if actions.len() > 1 {
+                    conflicts.push(DeterminismConflict {
+                        marking: state.clone(),
+                        role,
+                        actions,
+                    });
+                }
+            }
+        }
+        Ok(conflicts)
+    }
+
+    /// Checks whether two state machines have bisimilar reachability graphs.
+    ///
+    /// Both graphs are explored in lock-step from their initial markings, pairing the markings reached
+    /// by firing the same action in each. Since firing is deterministic, the machines are bisimilar
+    /// exactly when every reachable pair enables the same set of actions. Places are never compared,
+    /// so nets with different place labels or an extra implicit place can still be equivalent.
+    ///
+    /// Returns `Err(Truncated)` if more than `DEFAULT_STATE_LIMIT` marking pairs are reachable.
+    pub fn reachability_equivalent(&self, other: &StateMachine) -> Result<bool, Truncated> {
+        let limit = DEFAULT_STATE_LIMIT;
+        let start = (self.initial_vector(), other.initial_vector());
+        let mut visited: HashSet<(Vector, Vector)> = HashSet::new();
+        visited.insert(start.clone());
+        let mut queue = VecDeque::from([start]);
+
+        while let Some((left, right)) = queue.pop_front() {
+            let mut left_enabled = self.enabled_actions(&left);
+            let mut right_enabled = other.enabled_actions(&right);
+            left_enabled.sort();
+            right_enabled.sort();
+            if left_enabled != right_enabled {
+                return Ok(false);
+            }
+            for action in &left_enabled {
+                let pair = (
+                    self.transform(&left, action, 1).output,
+                    other.transform(&right, action, 1).output,
+                );
+                if !visited.contains(&pair) {
+                    if visited.len() >= limit {
+                        return Err(Truncated { limit });
+                    }
+                    visited.insert(pair.clone());
+                    queue.push_back(pair);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Fires the enabled actions of `state` and records which pairs conflict.
+    ///
+    /// Returns the marking after each action fires alone, and a matrix where `conflicts[a][b]`
+    /// is set when firing either of `enabled[a]` and `enabled[b]` disables the other.
+    fn conflicts(&self, state: &Vector, enabled: &[String]) -> (Vec<Vector>, Vec<Vec<bool>>) {
+        let outputs: Vec<Vector> = enabled
+            .iter()
+            .map(|action| self.transform(state, action, 1).output)
+            .collect();
+        let conflicts = (0..enabled.len())
+            .map(|a| {
+                (0..enabled.len())
+                    .map(|b| {
+                        a != b
+                            && (self.transform(&outputs[a], &enabled[b], 1).is_err()
+                                || self.transform(&outputs[b], &enabled[a], 1).is_err())
+                    })
+                    .collect()
+            })
+            .collect();
+        (outputs, conflicts)
+    }
+
+    /// Fires a maximal set of mutually non-conflicting enabled actions as one step.
+    ///
+    /// Actions are chosen greedily in offset order, skipping any that conflicts with one already
+    /// chosen or that the tokens left by the chosen actions can no longer enable. Returns the
+    /// marking after the step and the actions that fired; a deadlocked `state` is returned as is.
+    pub fn step(&self, state: &Vector) -> (Vector, Vec<String>) {
+        let enabled = self.enabled_actions(state);
+        let (_, conflicts) = self.conflicts(state, &enabled);
+        let mut chosen: Vec<usize> = Vec::new();
+        let mut next = state.clone();
+        for (i, action) in enabled.iter().enumerate() {
+            if chosen.iter().any(|&c| conflicts[c][i]) {
+                continue;
+            }
+            let tx = self.transform(&next, action, 1);
+            if tx.is_ok() {
+                next = tx.output;
+                chosen.push(i);
+            }
+        }
+        (
+            next,
+            chosen.into_iter().map(|i| enabled[i].clone()).collect(),
+        )
+    }
+
+    /// Returns the peak number of transitions that can fire together in any reachable marking.
+    ///
+    /// Two enabled transitions conflict when firing either one disables the other. At each marking
+    /// the largest set of pairwise non-conflicting enabled transitions is counted.
+    ///
+    /// Conflicts are only checked in pairs, so this is an upper bound: three transitions that
+    /// each take one of two tokens from a shared place are counted as concurrent, though only two
+    /// of them can fire together.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(Truncated)` if more than `DEFAULT_STATE_LIMIT` markings are reachable.
+    pub fn max_concurrency(&self) -> Result<usize, Truncated> {
+        let initial = self.initial_vector();
+        let mut visited: HashSet<Vector> = HashSet::from([initial.clone()]);
+        let mut queue = VecDeque::from([initial]);
+        let mut peak = 0;
+
+        while let Some(state) = queue.pop_front() {
+            let enabled = self.enabled_actions(&state);
+            let (outputs, conflicts) = self.conflicts(&state, &enabled);
+            peak = peak.max(largest_independent_set(&conflicts, 0, &mut Vec::new()));
+
+            for output in outputs {
+                if visited.insert(output.clone()) {
+                    if visited.len() > DEFAULT_STATE_LIMIT {
+                        return Err(Truncated {
+                            limit: DEFAULT_STATE_LIMIT,
+                        });
+                    }
+                    queue.push_back(output);
+                }
+            }
+        }
+        Ok(peak)
+    }
+
+    /// Checks that from every reachable single-token marking at most one transition
+    /// per role is enabled, i.e. the net behaves as a DFA.
+    ///
+    /// Use `determinism_conflicts` to see the offending markings.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Truncated` if the state space is too large to check.
+    pub fn is_deterministic(&self) -> Result<bool, Truncated> {
+        self.determinism_conflicts()
+            .map(|conflicts| conflicts.is_empty())
+    }
+}
+
+/// Finds the size of the largest set of vertices from `next` on with no conflict edge between them.
+fn largest_independent_set(conflicts: &[Vec<bool>], next: usize, chosen: &mut Vec<usize>) -> usize {
+    if next == conflicts.len() {
+        return chosen.len();
+    }
+    let skip = largest_independent_set(conflicts, next + 1, chosen);
+    if chosen.iter().any(|&c| conflicts[c][next]) {
+        return skip;
+    }
+    chosen.push(next);
+    let take = largest_independent_set(conflicts, next + 1, chosen);
+    chosen.pop();
+    skip.max(take)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::Model;
+    use crate::reachability::{Truncated, DEFAULT_STATE_LIMIT};
+    use crate::vasm::Vasm;
+
+    #[test]
+    fn test_nondeterministic_elementary_net() {
+        let m = Model::new(|p| {
+            p.model_type("elementary");
+            p.cell("A", Option::from(1), None, 100, 100);
+            p.cell("B", None, None, 200, 50);
+            p.cell("C", None, None, 200, 150);
+            p.func("a_to_b", "a", 150, 50);
+            p.func("a_to_c", "a", 150, 150);
+            p.arrow("A", "a_to_b", 1);
+            p.arrow("a_to_b", "B", 1);
+            p.arrow("A", "a_to_c", 1);
+            p.arrow("a_to_c", "C", 1);
+        });
+        assert_eq!(m.vm.is_deterministic(), Ok(false));
+
+        let conflicts = m.vm.determinism_conflicts().expect("state space fits");
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].marking, vec![1, 0, 0]);
+        assert_eq!(conflicts[0].role, "a");
+        assert_eq!(conflicts[0].actions, vec!["a_to_b", "a_to_c"]);
+    }
+
+    fn cycle(p: &mut dyn crate::dsl::Dsl) {
+        p.cell("idle", Option::from(1), None, 100, 100);
+        p.cell("busy", None, None, 200, 100);
+        p.func("start", "default", 150, 50);
+        p.func("stop", "default", 150, 150);
+        p.arrow("idle", "start", 1);
+        p.arrow("start", "busy", 1);
+        p.arrow("busy", "stop", 1);
+        p.arrow("stop", "idle", 1);
+    }
+
+    fn drain(p: &mut dyn crate::dsl::Dsl) {
+        p.cell("source", Option::from(3), None, 100, 100);
+        p.cell("sink", None, None, 300, 100);
+        p.func("move", "default", 200, 100);
+        p.arrow("source", "move", 1);
+        p.arrow("move", "sink", 1);
+    }
+
+    #[test]
+    fn test_inhibitor_shrinks_reachable_set() {
+        let unguarded = Model::new(drain);
+        let guarded = Model::new(|p| {
+            drain(p);
+            p.guard("sink", "move", 2);
+        });
+
+        let all = unguarded
+            .vm
+            .reachability_graph(DEFAULT_STATE_LIMIT)
+            .expect("finite");
+        let limited = guarded
+            .vm
+            .reachability_graph(DEFAULT_STATE_LIMIT)
+            .expect("finite");
+        assert_eq!(all.states.len(), 4);
+        // the inhibitor stops "move" once the sink holds two tokens
+        assert_eq!(limited.states.len(), 3);
+        assert!(limited.states.iter().all(|s| all.states.contains(s)));
+        assert!(!limited.states.contains(&vec![0, 3]));
+    }
+
+    #[test]
+    fn test_reachability_equivalent() {
+        let reduced = Model::new(cycle);
+        let with_implicit_place = Model::new(|p| {
+            cycle(p);
+            p.cell("running", None, None, 150, 100);
+            p.arrow("start", "running", 1);
+            p.arrow("running", "stop", 1);
+        });
+        let extra_token = Model::new(|p| {
+            cycle(p);
+            p.cell("spare", Option::from(1), None, 50, 100);
+            p.arrow("spare", "start", 1);
+            p.arrow("start", "idle", 1);
+        });
+
+        assert_eq!(
+            reduced.vm.reachability_equivalent(&with_implicit_place.vm),
+            Ok(true)
+        );
+        assert_eq!(
+            with_implicit_place.vm.reachability_equivalent(&reduced.vm),
+            Ok(true)
+        );
+        assert_eq!(
+            reduced.vm.reachability_equivalent(&extra_token.vm),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn test_max_concurrency() {
+        let branches = Model::new(|p| {
+            p.cell("A", Option::from(1), None, 100, 50);
+            p.cell("B", None, None, 200, 50);
+            p.cell("C", Option::from(1), None, 100, 150);
+            p.cell("D", None, None, 200, 150);
+            p.func("left", "default", 150, 50);
+            p.func("right", "default", 150, 150);
+            p.arrow("A", "left", 1);
+            p.arrow("left", "B", 1);
+            p.arrow("C", "right", 1);
+            p.arrow("right", "D", 1);
+        });
+        assert_eq!(branches.vm.max_concurrency(), Ok(2));
+
+        let choice = Model::new(|p| {
+            p.cell("A", Option::from(1), None, 100, 100);
+            p.cell("B", None, None, 200, 50);
+            p.cell("C", None, None, 200, 150);
+            p.func("a_to_b", "default", 150, 50);
+            p.func("a_to_c", "default", 150, 150);
+            p.arrow("A", "a_to_b", 1);
+            p.arrow("a_to_b", "B", 1);
+            p.arrow("A", "a_to_c", 1);
+            p.arrow("a_to_c", "C", 1);
+        });
+        assert_eq!(choice.vm.max_concurrency(), Ok(1));
+
+        // every pair can share the two tokens, so all three count though only two can fire
+        let shared = Model::new(|p| {
+            p.cell("pool", Option::from(2), None, 100, 100);
+            for (i, name) in ["a", "b", "c"].into_iter().enumerate() {
+                let y = 50 * i32::try_from(i).expect("small index");
+                p.func(name, "default", 200, y);
+                p.arrow("pool", name, 1);
+            }
+        });
+        assert_eq!(shared.vm.max_concurrency(), Ok(3));
+        let (_, fired) = shared.vm.step(&shared.vm.initial_vector());
+        assert_eq!(fired.len(), 2);
+    }
+
+    #[test]
+    fn test_max_concurrency_truncated() {
+        let counter = Model::new(|p| {
+            p.cell("count", None, None, 100, 100);
+            p.func("inc", "default", 200, 100);
+            p.arrow("inc", "count", 1);
+        });
+        assert_eq!(
+            counter.vm.max_concurrency(),
+            Err(Truncated {
+                limit: DEFAULT_STATE_LIMIT
+            })
+        );
+    }
+
+    #[test]
+    fn test_step_fires_independent_branches_together() {
+        let m = Model::new(|p| {
+            p.cell("A", Option::from(1), None, 100, 50);
+            p.cell("B", None, None, 200, 50);
+            p.cell("C", Option::from(1), None, 100, 150);
+            p.cell("D", None, None, 200, 150);
+            p.func("left", "default", 150, 50);
+            p.func("right", "default", 150, 150);
+            p.func("join", "default", 250, 100);
+            p.arrow("A", "left", 1);
+            p.arrow("left", "B", 1);
+            p.arrow("C", "right", 1);
+            p.arrow("right", "D", 1);
+            p.arrow("B", "join", 1);
+            p.arrow("D", "join", 1);
+        });
+        let (state, fired) = m.vm.step(&m.reset_state());
+        assert_eq!(fired, vec!["left", "right"]);
+        assert_eq!(state, vec![0, 1, 0, 1]);
+
+        let (state, fired) = m.vm.step(&state);
+        assert_eq!(fired, vec!["join"]);
+        assert_eq!(m.vm.step(&state), (state, Vec::new()));
+    }
+
+    #[test]
+    fn test_can_exceed() {
+        let safe = Model::new(cycle);
+        let idle = safe
+            .vm
+            .places
+            .iter()
+            .position(|p| p == "idle")
+            .expect("idle");
+        assert_eq!(safe.vm.can_exceed(idle, 1), Ok(true));
+        assert_eq!(safe.vm.can_exceed(idle, 2), Ok(false));
+
+        let accumulating = Model::new(|p| {
+            cycle(p);
+            p.cell("done", None, None, 200, 200);
+            p.arrow("stop", "done", 1);
+        });
+        let done = accumulating
+            .vm
+            .places
+            .iter()
+            .position(|p| p == "done")
+            .expect("done");
+        assert_eq!(accumulating.vm.can_exceed(done, 3), Ok(true));
+    }
+
+    #[test]
+    fn test_shortest_path_to_deadlock() {
+        let coffee = Model::new(crate::petri_net::tests::coffee_machine);
+        assert_eq!(
+            coffee.vm.shortest_path_to_deadlock(),
+            Ok(Some(vec![
+                "boil_water".to_string(),
+                "grind_beans".to_string(),
+                "brew_coffee".to_string(),
+                "pour_coffee".to_string()
+            ]))
+        );
+
+        // each philosopher picks up one chopstick at a time, so all can hold their left one
+        let philosophers = Model::new(|p| {
+            for i in 0..3 {
+                let (left, right) = (format!("chopstick{i}"), format!("chopstick{}", (i + 1) % 3));
+                let (take, holding) = (format!("take_left{i}"), format!("holding{i}"));
+                let (eat, eating) = (format!("take_right{i}"), format!("eating{i}"));
+                let done = format!("put_down{i}");
+                p.cell(&left, Option::from(1), None, 0, 0);
+                p.cell(&holding, None, None, 0, 0);
+                p.cell(&eating, None, None, 0, 0);
+                p.func(&take, "default", 0, 0);
+                p.func(&eat, "default", 0, 0);
+                p.func(&done, "default", 0, 0);
+                p.arrow(&left, &take, 1);
+                p.arrow(&take, &holding, 1);
+                p.arrow(&holding, &eat, 1);
+                p.arrow(&right, &eat, 1);
+                p.arrow(&eat, &eating, 1);
+                p.arrow(&eating, &done, 1);
+                p.arrow(&done, &left, 1);
+                p.arrow(&done, &right, 1);
+            }
+        });
+        let path = philosophers
+            .vm
+            .shortest_path_to_deadlock()
+            .expect("finite")
+            .expect("philosophers can deadlock");
+        assert_eq!(path, vec!["take_left0", "take_left1", "take_left2"]);
+
+        let atomic = Model::from_json_str(crate::petri_net::tests::DINING_PHILOSOPHERS);
+        assert_eq!(atomic.vm.shortest_path_to_deadlock(), Ok(None));
+        assert_eq!(Model::new(cycle).vm.shortest_path_to_deadlock(), Ok(None));
+    }
+
+    #[test]
+    fn test_semantic_diff() {
+        let petri_net = Model::new(crate::petri_net::tests::coffee_machine);
+        let mut elementary = petri_net.vm.as_ref().clone();
+        elementary.model_type = crate::vasm::ModelType::Elementary;
+
+        // the initial marking holds four tokens, so no elementary firing leaves exactly one
+        let diff = petri_net.vm.semantic_diff(&elementary).expect("finite");
+        assert_eq!(diff.common_markings, 1);
+        assert_eq!(
+            diff.divergences,
+            vec![super::Divergence {
+                marking: petri_net.reset_state(),
+                only_self: vec!["boil_water".to_string(), "grind_beans".to_string()],
+                only_other: Vec::new(),
+            }]
+        );
+        assert!(elementary
+            .semantic_diff(&elementary)
+            .expect("finite")
+            .is_empty());
+
+        let cycle = Model::new(cycle);
+        let mut as_elementary = cycle.vm.as_ref().clone();
+        as_elementary.model_type = crate::vasm::ModelType::Elementary;
+        let diff = cycle.vm.semantic_diff(&as_elementary).expect("finite");
+        assert_eq!(diff.common_markings, 2);
+        assert!(diff.is_empty());
+
+        let diff = cycle.vm.semantic_diff(&elementary).expect("finite");
+        assert!(diff.incompatible);
+        assert_eq!(diff.common_markings, 0);
+        assert!(!diff.is_empty());
+    }
+
+    #[test]
+    fn test_place_bounds() {
+        let m = Model::from_json_str(crate::petri_net::tests::DINING_PHILOSOPHERS);
+        let bounds = m.vm.place_bounds().expect("finite tree");
+        for (place, bound) in m.vm.places.iter().zip(&bounds) {
+            if place.starts_with("chopstick") {
+                assert_eq!(*bound, Some(1), "{place}");
+            }
+        }
+
+        let accumulating = Model::new(|p| {
+            cycle(p);
+            p.cell("done", None, None, 200, 200);
+            p.arrow("stop", "done", 1);
+        });
+        let bounds = accumulating.vm.place_bounds().expect("finite tree");
+        let bound = |label: &str| {
+            let offset = accumulating.vm.places.iter().position(|p| p == label);
+            bounds[offset.expect("place")]
+        };
+        assert_eq!(bound("idle"), Some(1));
+        assert_eq!(bound("busy"), Some(1));
+        assert_eq!(bound("done"), None);
+    }
+
+    #[test]
+    fn test_mutual_exclusion() {
+        let m = Model::new(|p| {
+            p.cell("mutex", Option::from(1), None, 200, 100);
+            p.cell("idle_a", Option::from(1), None, 100, 50);
+            p.cell("critical_a", None, None, 300, 50);
+            p.cell("idle_b", Option::from(1), None, 100, 150);
+            p.cell("critical_b", None, None, 300, 150);
+            p.func("enter_a", "a", 200, 20);
+            p.func("leave_a", "a", 200, 80);
+            p.func("enter_b", "b", 200, 120);
+            p.func("leave_b", "b", 200, 180);
+            p.arrow("idle_a", "enter_a", 1);
+            p.arrow("mutex", "enter_a", 1);
+            p.arrow("enter_a", "critical_a", 1);
+            p.arrow("critical_a", "leave_a", 1);
+            p.arrow("leave_a", "idle_a", 1);
+            p.arrow("leave_a", "mutex", 1);
+            p.arrow("idle_b", "enter_b", 1);
+            p.arrow("mutex", "enter_b", 1);
+            p.arrow("enter_b", "critical_b", 1);
+            p.arrow("critical_b", "leave_b", 1);
+            p.arrow("leave_b", "idle_b", 1);
+            p.arrow("leave_b", "mutex", 1);
+        });
+        let at = |label: &str| m.vm.places.iter().position(|p| p == label).expect(label);
+        assert_eq!(
+            m.vm.mutual_exclusion_holds(at("critical_a"), at("critical_b")),
+            Ok(true)
+        );
+
+        let violation =
+            m.vm.mutual_exclusion_violation(at("critical_a"), at("idle_b"))
+                .expect("state space fits")
+                .expect("both can be marked");
+        assert!(violation[at("critical_a")] > 0 && violation[at("idle_b")] > 0);
+        assert_eq!(
+            m.vm.mutual_exclusion_holds(at("critical_a"), at("idle_b")),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn test_enabling_markings() {
+        let m = Model::new(crate::petri_net::tests::coffee_machine);
+        let markings =
+            m.vm.enabling_markings("pour_coffee")
+                .expect("state space fits");
+        let marking = m.vm.marking_map(&markings[0]);
+        assert_eq!(markings.len(), 1);
+        assert_eq!(marking["CoffeeInPot"], 1);
+        assert_eq!(marking["Cup"], 1);
+        assert_eq!(markings[0].iter().sum::<i32>(), 2);
+
+        assert_eq!(m.vm.enabling_markings("boil_water").map(|m| m.len()), Ok(2));
+        assert_eq!(m.vm.enabling_markings("no_such_action"), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn test_liveness_level() {
+        let live = Model::new(cycle);
+        assert_eq!(live.vm.liveness_level("start"), Ok(4));
+        assert_eq!(live.vm.liveness_level("stop"), Ok(4));
+
+        let once = Model::new(|p| {
+            cycle(p);
+            p.cell("ticket", Option::from(1), None, 50, 50);
+            p.func("punch", "default", 50, 150);
+            p.arrow("ticket", "punch", 1);
+            p.cell("guard", None, None, 250, 50);
+            p.func("never", "default", 250, 150);
+            p.arrow("guard", "never", 1);
+        });
+        assert_eq!(once.vm.liveness_level("punch"), Ok(1));
+        assert_eq!(once.vm.liveness_level("start"), Ok(4));
+        assert_eq!(once.vm.liveness_level("never"), Ok(0));
+    }
+
+    #[test]
+    fn test_deterministic_elementary_net() {
+        let m = Model::new(|p| {
+            p.model_type("elementary");
+            p.cell("A", Option::from(1), None, 100, 100);
+            p.cell("B", None, None, 200, 50);
+            p.cell("C", None, None, 200, 150);
+            p.func("a_to_b", "a", 150, 50);
+            p.func("a_to_c", "b", 150, 150);
+            p.arrow("A", "a_to_b", 1);
+            p.arrow("a_to_b", "B", 1);
+            p.arrow("A", "a_to_c", 1);
+            p.arrow("a_to_c", "C", 1);
+        });
+        assert_eq!(m.vm.is_deterministic(), Ok(true));
+    }
+}
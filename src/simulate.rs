@@ -0,0 +1,213 @@
+use std::ops::ControlFlow;
+
+use crate::vasm::{StateMachine, Vasm, Vector};
+
+/// `XorShift64` is a small seeded pseudo-random generator, so simulations are reproducible
+/// without pulling in a random number crate. It is not suitable for cryptography.
+#[derive(Debug, Clone, Copy)]
+pub struct XorShift64 {
+    state: u64,
+}
+
+impl XorShift64 {
+    /// Creates a generator from a seed; a seed of 0, which xorshift cannot use, is replaced.
+    pub const fn new(seed: u64) -> Self {
+        Self {
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    /// Returns the next 64 random bits.
+    pub const fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Returns a value in `0..n`, which must not be zero.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0, "range must not be empty");
+        let n = u64::try_from(n).expect("range fits in u64");
+        usize::try_from(self.next_u64() % n).expect("index fits in usize")
+    }
+
+    /// Returns an index into `weights`, each chosen with probability proportional to its weight.
+    ///
+    /// If every weight is zero the choice is uniform.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weights` is empty.
+    pub fn weighted(&mut self, weights: &[u64]) -> usize {
+        let total: u64 = weights.iter().sum();
+        if total == 0 {
+            return self.below(weights.len());
+        }
+        let mut pick = self.next_u64() % total;
+        weights
+            .iter()
+            .position(|&w| {
+                if pick < w {
+                    return true;
+                }
+                pick -= w;
+                false
+            })
+            .expect("pick is below the total weight")
+    }
+}
+
+impl StateMachine {
+    /// Fires up to `steps` randomly chosen enabled actions from the initial marking.
+    ///
+    /// Each step picks uniformly among the enabled actions using a generator seeded with `seed`,
+    /// so the same seed gives the same run. The run stops early at a deadlock. Each step is
+    /// returned as the action fired and the marking it produced.
+    pub fn simulate(&self, steps: usize, seed: u64) -> Vec<(String, Vector)> {
+        let mut trace = Vec::new();
+        self.simulate_with(steps, seed, |action, state| {
+            trace.push((action.to_string(), state.clone()));
+            ControlFlow::Continue(())
+        });
+        trace
+    }
+
+    /// Runs the same simulation as `simulate`, streaming each firing to `observer` instead of
+    /// collecting them.
+    ///
+    /// The observer is called after every firing with the action and the new marking, and may
+    /// return `ControlFlow::Break` to stop the run. Returns the number of firings.
+    pub fn simulate_with<F>(&self, steps: usize, seed: u64, observer: F) -> usize
+    where
+        F: FnMut(&str, &Vector) -> ControlFlow<()>,
+    {
+        self.run_simulation(steps, seed, false, observer)
+    }
+
+    /// Runs the same simulation as `simulate`, but picks among the enabled actions in proportion
+    /// to each transition's `weight`, so a transition of weight 3 fires three times as often as
+    /// a competing one of weight 1. Transitions without a weight count as 1.
+    pub fn simulate_weighted(&self, steps: usize, seed: u64) -> Vec<(String, Vector)> {
+        let mut trace = Vec::new();
+        self.run_simulation(steps, seed, true, |action, state| {
+            trace.push((action.to_string(), state.clone()));
+            ControlFlow::Continue(())
+        });
+        trace
+    }
+
+    /// Fires up to `steps` random actions, choosing by transition weight if `weighted` is set.
+    fn run_simulation<F>(&self, steps: usize, seed: u64, weighted: bool, mut observer: F) -> usize
+    where
+        F: FnMut(&str, &Vector) -> ControlFlow<()>,
+    {
+        let mut rng = XorShift64::new(seed);
+        let mut state = self.initial_vector();
+        for fired in 0..steps {
+            let enabled = self.enabled_actions(&state);
+            if enabled.is_empty() {
+                return fired;
+            }
+            let pick = if weighted {
+                let weights: Vec<u64> = enabled
+                    .iter()
+                    .map(|action| u64::from(self.transitions[action].weight.unwrap_or(1)))
+                    .collect();
+                rng.weighted(&weights)
+            } else {
+                rng.below(enabled.len())
+            };
+            let action = &enabled[pick];
+            state = self.transform(&state, action, 1).output;
+            if observer(action, &state).is_break() {
+                return fired + 1;
+            }
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::ControlFlow;
+
+    use crate::model::Model;
+    use crate::petri_net::tests::DINING_PHILOSOPHERS;
+
+    #[test]
+    fn test_simulate_is_reproducible() {
+        let m = Model::from_json_str(DINING_PHILOSOPHERS);
+        let run = m.vm.simulate(50, 7);
+        assert_eq!(run.len(), 50);
+        assert_eq!(run, m.vm.simulate(50, 7));
+        for (action, state) in &run {
+            assert!(m.vm.transitions.contains_key(action));
+            assert!(state.iter().all(|&t| t >= 0));
+        }
+    }
+
+    #[test]
+    fn test_simulate_weighted_branch() {
+        let mut net = crate::petri_net::PetriNet::new();
+        net.declare(|p| {
+            p.cell("ready", Option::from(1), None, 100, 100);
+            p.cell("left", None, None, 200, 50);
+            p.cell("right", None, None, 200, 150);
+            p.func("go_left", "default", 150, 50);
+            p.func("go_right", "default", 150, 150);
+            p.func("back_left", "default", 250, 50);
+            p.func("back_right", "default", 250, 150);
+            p.arrow("ready", "go_left", 1);
+            p.arrow("go_left", "left", 1);
+            p.arrow("left", "back_left", 1);
+            p.arrow("back_left", "ready", 1);
+            p.arrow("ready", "go_right", 1);
+            p.arrow("go_right", "right", 1);
+            p.arrow("right", "back_right", 1);
+            p.arrow("back_right", "ready", 1);
+        });
+        net.transitions.get_mut("go_left").expect("go_left").weight = Some(3);
+        let vm = crate::vasm::StateMachine::from_model(&mut net);
+
+        let run = vm.simulate_weighted(8000, 42);
+        let count = |name: &str| run.iter().filter(|(action, _)| action == name).count();
+        let (left, right) = (count("go_left"), count("go_right"));
+        assert_eq!(left + right, 4000);
+        let ratio = f64::from(u32::try_from(left).expect("fits"))
+            / f64::from(u32::try_from(right).expect("fits"));
+        assert!((2.7..3.3).contains(&ratio), "ratio {ratio}");
+        assert_eq!(run, vm.simulate_weighted(8000, 42));
+    }
+
+    #[test]
+    fn test_simulate_with_observer() {
+        let m = Model::from_json_str(DINING_PHILOSOPHERS);
+        let mut seen = Vec::new();
+        let fired = m.vm.simulate_with(20, 7, |action, state| {
+            seen.push((action.to_string(), state.clone()));
+            ControlFlow::Continue(())
+        });
+        assert_eq!(fired, 20);
+        assert_eq!(seen, m.vm.simulate(20, 7));
+
+        let mut calls = 0;
+        let fired = m.vm.simulate_with(20, 7, |_, _| {
+            calls += 1;
+            if calls == 3 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!((fired, calls), (3, 3));
+    }
+}
@@ -0,0 +1,644 @@
+use serde::Serialize;
+
+use crate::petri_net::Arrow;
+use crate::vasm::{ModelType, StateMachine, Vector};
+
+/// `ActionInfo` describes one action's preconditions and effects, for documenting it as an endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActionInfo {
+    /// The action name.
+    pub name: String,
+    /// The role allowed to fire the action.
+    pub role: String,
+    /// The places tokens are taken from, with how many.
+    pub input_places: Vec<(String, i32)>,
+    /// The places tokens are put into, with how many.
+    pub output_places: Vec<(String, i32)>,
+    /// The places guarding the action, with the guard weight.
+    pub guards: Vec<(String, i32)>,
+}
+
+impl StateMachine {
+    /// Lists the offsets of the places a transition consumes from and produces into.
+    ///
+    /// Both sets are read from the transition delta, so a place that is consumed and
+    /// produced by the same weight (a self-loop) and guard places are not included.
+    fn pre_post(&self, action: &str) -> (Vec<usize>, Vec<usize>) {
+        let delta = &self.transitions[action].delta;
+        let pre = (0..delta.len()).filter(|&i| delta[i] < 0).collect();
+        let post = (0..delta.len()).filter(|&i| delta[i] > 0).collect();
+        (pre, post)
+    }
+
+    /// Rebuilds the arcs of a single transition from its compiled form.
+    ///
+    /// Consuming arcs come first, then producing arcs, each in place offset order, then one
+    /// inhibitor arc per guard. A self-loop yields both a consuming and a producing arc, and a
+    /// read guard points from the transition to its place, as in the source net. Place labels
+    /// are taken from `places`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the action does not exist.
+    pub fn transition_arcs(&self, action: &str) -> Vec<Arrow> {
+        let transition = &self.transitions[action];
+        let consumed = |i: usize| {
+            transition
+                .consume
+                .get(i)
+                .copied()
+                .unwrap_or_else(|| (-transition.delta[i]).max(0))
+        };
+        let arc = |source: &str, target: &str, weight: i32| Arrow {
+            source: source.to_string(),
+            target: target.to_string(),
+            weight: Some(weight),
+            consume: Some(false),
+            produce: Some(false),
+            inhibit: Some(false),
+            read: Some(false),
+            group: None,
+        };
+
+        let mut arcs = Vec::new();
+        for (i, place) in self.places.iter().enumerate() {
+            if consumed(i) > 0 {
+                arcs.push(Arrow {
+                    consume: Some(true),
+                    ..arc(place, action, consumed(i))
+                });
+            }
+        }
+        for (i, place) in self.places.iter().enumerate() {
+            let produced = transition.delta[i] + consumed(i);
+            if produced > 0 {
+                arcs.push(Arrow {
+                    produce: Some(true),
+                    ..arc(action, place, produced)
+                });
+            }
+        }
+
+        let grouped = transition
+            .guard_groups
+            .iter()
+            .flat_map(|(name, group)| group.guards.values().map(move |g| (Some(name), g)));
+        let mut inhibitors: Vec<(usize, Arrow)> = transition
+            .guards
+            .values()
+            .map(|g| (None, g))
+            .chain(grouped)
+            .filter_map(|(group, guard)| {
+                let i = guard.delta.iter().position(|&w| w != 0)?;
+                let place = &self.places[i];
+                let (source, target) = if guard.read {
+                    (action, place.as_str())
+                } else {
+                    (place.as_str(), action)
+                };
+                let arrow = Arrow {
+                    inhibit: Some(true),
+                    read: Some(guard.read),
+                    group: group.cloned(),
+                    ..arc(source, target, -guard.delta[i])
+                };
+                Some((i, arrow))
+            })
+            .collect();
+        inhibitors.sort_by(|(a, x), (b, y)| a.cmp(b).then_with(|| x.group.cmp(&y.group)));
+        arcs.extend(inhibitors.into_iter().map(|(_, arrow)| arrow));
+        arcs
+    }
+
+    /// Computes the smallest marking in which `action` is enabled, from its arcs alone.
+    ///
+    /// Each place needs the tokens the action consumes from it, and at least the threshold of
+    /// any read guard on it. Inhibitor guards are met by an empty place, as is a guard group
+    /// containing one; otherwise a group is met by its lightest read guard. Places that may go
+    /// negative need nothing. A transition whose guards contradict its inputs has no enabling
+    /// marking, and the requirement returned for it will still be inhibited.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the action does not exist.
+    pub fn min_enabling_marking(&self, action: &str) -> Vector {
+        let transition = &self.transitions[action];
+        let mut marking: Vector = (0..self.places.len())
+            .map(|i| {
+                if self.allow_negative.get(i).copied().unwrap_or(false) {
+                    return 0;
+                }
+                transition
+                    .consume
+                    .get(i)
+                    .copied()
+                    .unwrap_or_else(|| (-transition.delta[i]).max(0))
+            })
+            .collect();
+        let threshold = |delta: &Vector| {
+            delta
+                .iter()
+                .enumerate()
+                .find(|(_, &w)| w != 0)
+                .map(|(i, &w)| (i, -w))
+        };
+        let mut require = |(i, tokens): (usize, i32)| marking[i] = marking[i].max(tokens);
+
+        for guard in transition.guards.values().filter(|g| g.read) {
+            if let Some(needed) = threshold(&guard.delta) {
+                require(needed);
+            }
+        }
+        for group in transition.guard_groups.values() {
+            if group.guards.values().any(|g| !g.read) {
+                continue;
+            }
+            let lightest = group
+                .guards
+                .values()
+                .filter_map(|g| threshold(&g.delta))
+                .min_by_key(|&(i, tokens)| (tokens, i));
+            if let Some(needed) = lightest {
+                require(needed);
+            }
+        }
+        marking
+    }
+
+    /// Describes every action, ordered by offset, from the arcs `transition_arcs` rebuilds.
+    pub fn action_catalog(&self) -> Vec<ActionInfo> {
+        self.actions
+            .iter()
+            .map(|action| {
+                let mut info = ActionInfo {
+                    name: action.clone(),
+                    role: self.transitions[action].role.clone(),
+                    input_places: Vec::new(),
+                    output_places: Vec::new(),
+                    guards: Vec::new(),
+                };
+                for arc in self.transition_arcs(action) {
+                    let weight = arc.weight.unwrap_or(1);
+                    if arc.inhibit == Some(true) {
+                        let place = if arc.read == Some(true) {
+                            arc.target
+                        } else {
+                            arc.source
+                        };
+                        info.guards.push((place, weight));
+                    } else if arc.consume == Some(true) {
+                        info.input_places.push((arc.source, weight));
+                    } else {
+                        info.output_places.push((arc.target, weight));
+                    }
+                }
+                info
+            })
+            .collect()
+    }
+
+    /// Checks that every transition has exactly one input place and exactly one output place.
+    pub fn is_state_machine(&self) -> bool {
+        self.actions.iter().all(|action| {
+            let (pre, post) = self.pre_post(action);
+            pre.len() == 1 && post.len() == 1
+        })
+    }
+
+    /// Checks that every place has exactly one input transition and exactly one output transition.
+    pub fn is_marked_graph(&self) -> bool {
+        let mut inputs = vec![0; self.places.len()];
+        let mut outputs = vec![0; self.places.len()];
+        for action in &self.actions {
+            let (pre, post) = self.pre_post(action);
+            for p in pre {
+                outputs[p] += 1;
+            }
+            for p in post {
+                inputs[p] += 1;
+            }
+        }
+        inputs.iter().zip(&outputs).all(|(&i, &o)| i == 1 && o == 1)
+    }
+
+    /// Checks that firing any transition leaves the total token count unchanged.
+    pub fn is_conservative(&self) -> bool {
+        self.is_conservative_weighted(&vec![1; self.places.len()])
+    }
+
+    /// Checks that firing any transition leaves the weighted token count unchanged.
+    ///
+    /// `weights` holds one weight per place, indexed by offset.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `weights` does not have one entry per place.
+    pub fn is_conservative_weighted(&self, weights: &Vector) -> bool {
+        assert_eq!(weights.len(), self.places.len(), "one weight per place");
+        self.transitions.values().all(|t| {
+            t.delta
+                .iter()
+                .zip(weights)
+                .map(|(&d, &w)| i64::from(d) * i64::from(w))
+                .sum::<i64>()
+                == 0
+        })
+    }
+
+    /// Checks that any two transitions sharing an input place have no other input places.
+    ///
+    /// In a free-choice net a conflict is always resolved by a single place, so whichever
+    /// transition is chosen, its competitors were enabled too.
+    pub fn is_free_choice(&self) -> bool {
+        let presets: Vec<Vec<usize>> = self
+            .actions
+            .iter()
+            .map(|action| self.pre_post(action).0)
+            .collect();
+        (0..self.places.len()).all(|p| {
+            let consumers: Vec<&Vec<usize>> =
+                presets.iter().filter(|pre| pre.contains(&p)).collect();
+            consumers.len() < 2 || consumers.iter().all(|pre| pre.len() == 1)
+        })
+    }
+
+    /// Lists the transitions that add tokens to the place at `place_offset`, ordered by offset.
+    ///
+    /// Like `pre_post`, this reads the sign of each delta, so self-loops and guards are left out.
+    pub fn producers(&self, place_offset: usize) -> Vec<String> {
+        self.actions_by_delta(place_offset, |d| d > 0)
+    }
+
+    /// Lists the transitions that remove tokens from the place at `place_offset`, ordered by offset.
+    pub fn consumers(&self, place_offset: usize) -> Vec<String> {
+        self.actions_by_delta(place_offset, |d| d < 0)
+    }
+
+    /// Lists the transitions that supply tokens to any of `action`'s input places, ordered by offset.
+    ///
+    /// An input place is one the action takes tokens from, including through a self-loop.
+    /// Suppliers are found with `producers`, and the action itself is left out.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the action does not exist.
+    pub fn prerequisites(&self, action: &str) -> Vec<String> {
+        let transition = &self.transitions[action];
+        let inputs: Vec<usize> = (0..self.places.len())
+            .filter(|&i| {
+                transition
+                    .consume
+                    .get(i)
+                    .map_or(transition.delta[i] < 0, |&c| c > 0)
+            })
+            .collect();
+        self.actions
+            .iter()
+            .filter(|a| *a != action)
+            .filter(|a| inputs.iter().any(|&i| self.transitions[*a].delta[i] > 0))
+            .cloned()
+            .collect()
+    }
+
+    /// Lists every transition that `action` transitively depends on through `prerequisites`, ordered by offset.
+    ///
+    /// The action itself is left out even when it lies on a cycle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the action does not exist.
+    pub fn all_prerequisites(&self, action: &str) -> Vec<String> {
+        let mut found: Vec<String> = Vec::new();
+        let mut pending = vec![action.to_string()];
+        while let Some(next) = pending.pop() {
+            for supplier in self.prerequisites(&next) {
+                if supplier != action && !found.contains(&supplier) {
+                    found.push(supplier.clone());
+                    pending.push(supplier);
+                }
+            }
+        }
+        self.actions
+            .iter()
+            .filter(|a| found.contains(a))
+            .cloned()
+            .collect()
+    }
+
+    fn actions_by_delta(&self, place_offset: usize, keep: impl Fn(i32) -> bool) -> Vec<String> {
+        self.actions
+            .iter()
+            .filter(|action| {
+                self.transitions[*action]
+                    .delta
+                    .get(place_offset)
+                    .is_some_and(|&d| keep(d))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Infers the source and sink places of a workflow net, ordered by offset.
+    ///
+    /// A source place is never produced into and a sink place is never consumed from. A sound
+    /// workflow net has exactly one of each, the `i` and `o` places of its start and end.
+    pub fn workflow_endpoints(&self) -> (Vec<String>, Vec<String>) {
+        let mut produced = vec![false; self.places.len()];
+        let mut consumed = vec![false; self.places.len()];
+        for action in &self.actions {
+            let (pre, post) = self.pre_post(action);
+            for p in pre {
+                consumed[p] = true;
+            }
+            for p in post {
+                produced[p] = true;
+            }
+        }
+        let select = |marks: &[bool]| {
+            self.places
+                .iter()
+                .zip(marks)
+                .filter(|(_, &marked)| !marked)
+                .map(|(place, _)| place.clone())
+                .collect()
+        };
+        (select(&produced), select(&consumed))
+    }
+
+    /// Checks that a workflow run finished properly, with one token in the sink and no others.
+    ///
+    /// The sink is found with `workflow_endpoints`, so a net that is not a `workflow` or does not
+    /// have exactly one sink place has no final marking, and neither does a state without one
+    /// entry per place. Unlike a deadlock, this tells a completed run from a stuck one.
+    pub fn is_final_marking(&self, state: &Vector) -> bool {
+        if !matches!(self.model_type, ModelType::Workflow) || state.len() != self.places.len() {
+            return false;
+        }
+        let (_, sinks) = self.workflow_endpoints();
+        let [sink] = sinks.as_slice() else {
+            return false;
+        };
+        self.places
+            .iter()
+            .zip(state)
+            .all(|(place, &tokens)| tokens == i32::from(place == sink))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::Model;
+    use crate::petri_net::tests::{coffee_machine, DINING_PHILOSOPHERS};
+    use crate::vasm::{ModelType, Vasm};
+
+    #[test]
+    fn test_coffee_machine_is_free_choice() {
+        let m = Model::new(coffee_machine);
+        assert!(!m.vm.is_state_machine());
+        assert!(!m.vm.is_marked_graph());
+        assert!(m.vm.is_free_choice());
+    }
+
+    #[test]
+    fn test_producers_and_consumers() {
+        let m = Model::new(coffee_machine);
+        let boiled =
+            m.vm.places
+                .iter()
+                .position(|p| p == "BoiledWater")
+                .expect("place");
+        assert_eq!(m.vm.producers(boiled), vec!["boil_water"]);
+        assert_eq!(m.vm.consumers(boiled), vec!["brew_coffee"]);
+
+        let cup = m.vm.places.iter().position(|p| p == "Cup").expect("place");
+        assert!(m.vm.producers(cup).is_empty());
+        assert_eq!(m.vm.consumers(cup), vec!["pour_coffee"]);
+    }
+
+    #[test]
+    fn test_transition_arcs() {
+        let m = Model::new(coffee_machine);
+        let arcs = m.vm.transition_arcs("brew_coffee");
+        let endpoints: Vec<(&str, &str)> = arcs
+            .iter()
+            .map(|a| (a.source.as_str(), a.target.as_str()))
+            .collect();
+        assert_eq!(
+            endpoints,
+            vec![
+                ("BoiledWater", "brew_coffee"),
+                ("GroundCoffee", "brew_coffee"),
+                ("Filter", "brew_coffee"),
+                ("brew_coffee", "CoffeeInPot"),
+            ]
+        );
+        assert!(arcs[..3].iter().all(|a| a.consume == Some(true)));
+        assert_eq!(arcs[3].produce, Some(true));
+        assert!(arcs.iter().all(|a| a.weight == Some(1)));
+
+        let m = Model::new(|p| {
+            p.cell("p", Option::from(1), None, 0, 0);
+            p.cell("q", None, None, 0, 0);
+            p.func("t", "default", 0, 0);
+            p.arrow("p", "t", 1);
+            p.arrow("t", "p", 1);
+            p.guard("q", "t", 2);
+        });
+        let arcs = m.vm.transition_arcs("t");
+        assert_eq!(arcs.len(), 3);
+        assert_eq!(
+            (arcs[0].source.as_str(), arcs[0].consume),
+            ("p", Some(true))
+        );
+        assert_eq!(
+            (arcs[1].target.as_str(), arcs[1].produce),
+            ("p", Some(true))
+        );
+        assert_eq!(
+            (arcs[2].source.as_str(), arcs[2].inhibit, arcs[2].weight),
+            ("q", Some(true), Some(2))
+        );
+    }
+
+    #[test]
+    fn test_prerequisites() {
+        let m = Model::new(coffee_machine);
+        assert_eq!(
+            m.vm.prerequisites("brew_coffee"),
+            vec!["boil_water", "grind_beans"]
+        );
+        assert_eq!(m.vm.prerequisites("pour_coffee"), vec!["brew_coffee"]);
+        assert_eq!(
+            m.vm.all_prerequisites("pour_coffee"),
+            vec!["boil_water", "brew_coffee", "grind_beans"]
+        );
+        assert!(m.vm.prerequisites("boil_water").is_empty());
+
+        let m = Model::from_json_str(DINING_PHILOSOPHERS);
+        assert_eq!(m.vm.prerequisites("think1"), vec!["eat1"]);
+        assert!(!m.vm.all_prerequisites("eat1").contains(&"eat1".to_string()));
+        assert!(m
+            .vm
+            .all_prerequisites("eat1")
+            .contains(&"think2".to_string()));
+    }
+
+    #[test]
+    fn test_min_enabling_marking() {
+        let m = Model::new(coffee_machine);
+        let marking = m.vm.min_enabling_marking("brew_coffee");
+        for (place, &tokens) in m.vm.places.iter().zip(&marking) {
+            let input = ["BoiledWater", "GroundCoffee", "Filter"].contains(&place.as_str());
+            assert_eq!(tokens, i32::from(input), "{place}");
+        }
+        assert!(m.vm.transform(&marking, "brew_coffee", 1).is_ok());
+
+        let m = Model::new(|p| {
+            p.cell("fuel", None, None, 0, 0);
+            p.cell("key", None, None, 0, 0);
+            p.cell("alarm", None, None, 0, 0);
+            p.func("start", "default", 0, 0);
+            p.arrow("fuel", "start", 2);
+            p.arrow("start", "fuel", 1);
+            p.arrow("key", "start", 1);
+            p.arrow("start", "key", 1);
+            p.guard("alarm", "start", 1);
+        });
+        let marking = m.vm.min_enabling_marking("start");
+        assert_eq!(m.vm.marking_map(&marking)["fuel"], 2);
+        assert_eq!(m.vm.marking_map(&marking)["key"], 1);
+        assert_eq!(m.vm.marking_map(&marking)["alarm"], 0);
+        assert!(m.vm.transform(&marking, "start", 1).is_ok());
+    }
+
+    #[test]
+    fn test_action_catalog() {
+        let m = Model::new(|p| {
+            coffee_machine(p);
+            p.cell("Spill", None, None, 900, 300);
+            p.arrow("pour_coffee", "Spill", 1);
+            p.guard("Spill", "pour_coffee", 1);
+        });
+        let catalog = m.vm.action_catalog();
+        assert_eq!(catalog.len(), 4);
+        let pour = catalog
+            .iter()
+            .find(|info| info.name == "pour_coffee")
+            .expect("pour_coffee is listed");
+        assert_eq!(pour.role, "default");
+        assert_eq!(
+            pour.input_places,
+            vec![("CoffeeInPot".to_string(), 1), ("Cup".to_string(), 1)]
+        );
+        assert_eq!(pour.output_places, vec![("Spill".to_string(), 1)]);
+        assert_eq!(pour.guards, vec![("Spill".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_dining_philosophers_is_not_free_choice() {
+        let m = Model::from_json_str(DINING_PHILOSOPHERS);
+        assert!(!m.vm.is_state_machine());
+        assert!(!m.vm.is_marked_graph());
+        assert!(!m.vm.is_free_choice());
+    }
+
+    #[test]
+    fn test_conservative() {
+        let m = Model::from_json_str(DINING_PHILOSOPHERS);
+        assert!(m.vm.is_conservative());
+
+        let m = Model::new(|p| {
+            p.cell("pair", Option::from(2), None, 100, 100);
+            p.cell("single", None, None, 200, 100);
+            p.func("combine", "default", 150, 100);
+            p.arrow("pair", "combine", 2);
+            p.arrow("combine", "single", 1);
+        });
+        assert!(!m.vm.is_conservative());
+        assert!(m.vm.is_conservative_weighted(&vec![1, 2]));
+
+        let m = Model::new(|p| {
+            p.cell("pool", None, None, 100, 100);
+            p.func("spawn", "default", 150, 100);
+            p.arrow("spawn", "pool", 1);
+        });
+        assert!(!m.vm.is_conservative());
+    }
+
+    #[test]
+    fn test_workflow_endpoints() {
+        let m = Model::new(|p| {
+            p.model_type("workflow");
+            p.cell("i", Option::from(1), None, 100, 100);
+            p.cell("review", None, None, 200, 100);
+            p.cell("o", None, None, 300, 100);
+            p.func("submit", "author", 150, 100);
+            p.func("approve", "editor", 250, 100);
+            p.arrow("i", "submit", 1);
+            p.arrow("submit", "review", 1);
+            p.arrow("review", "approve", 1);
+            p.arrow("approve", "o", 1);
+        });
+        let (sources, sinks) = m.vm.workflow_endpoints();
+        assert_eq!(sources, vec!["i"]);
+        assert_eq!(sinks, vec!["o"]);
+    }
+
+    #[test]
+    fn test_is_final_marking() {
+        let m = Model::new(|p| {
+            p.model_type("workflow");
+            p.cell("i", Option::from(1), None, 100, 100);
+            p.cell("review", None, None, 200, 100);
+            p.cell("o", None, None, 300, 100);
+            p.func("submit", "author", 150, 100);
+            p.func("approve", "editor", 250, 100);
+            p.arrow("i", "submit", 1);
+            p.arrow("submit", "review", 1);
+            p.arrow("review", "approve", 1);
+            p.arrow("approve", "o", 1);
+        });
+        let mut state = m.vm.initial_vector();
+        let mut fired = 0;
+        while !m.vm.is_final_marking(&state) {
+            let (_, next) =
+                m.vm.successors(&state)
+                    .into_iter()
+                    .next()
+                    .expect("not stuck");
+            state = next;
+            fired += 1;
+        }
+        assert_eq!(fired, 2);
+        assert_eq!(state, vec![0, 0, 1]);
+        assert!(m.vm.successors(&state).is_empty());
+
+        assert!(!m.vm.is_final_marking(&m.vm.initial_vector()));
+        assert!(!m.vm.is_final_marking(&vec![0, 1, 1]));
+        // a short state is not read as empty places
+        assert!(!m.vm.is_final_marking(&vec![0, 0]));
+        assert!(!m.vm.is_final_marking(&vec![0, 0, 1, 0]));
+        assert!(!Model::new(coffee_machine).vm.is_final_marking(&vec![0; 7]));
+
+        let mut petri_net = m.vm.as_ref().clone();
+        petri_net.model_type = ModelType::PetriNet;
+        assert!(!petri_net.is_final_marking(&state));
+    }
+
+    #[test]
+    fn test_cycle_is_state_machine_and_marked_graph() {
+        let m = Model::new(|p| {
+            p.cell("idle", Option::from(1), None, 100, 100);
+            p.cell("busy", None, None, 200, 100);
+            p.func("start", "default", 150, 50);
+            p.func("stop", "default", 150, 150);
+            p.arrow("idle", "start", 1);
+            p.arrow("start", "busy", 1);
+            p.arrow("busy", "stop", 1);
+            p.arrow("stop", "idle", 1);
+        });
+        assert!(m.vm.is_state_machine());
+        assert!(m.vm.is_marked_graph());
+        assert!(m.vm.is_free_choice());
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::reachability::Truncated;
+use crate::vasm::{StateMachine, Vector};
+
+/// `Permutation` is a relabelling of places and transitions that leaves the net unchanged.
+///
+/// Place `i` maps to place `places[i]` and action `j` (by offset) to action `transitions[j]`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Permutation {
+    /// The image of each place offset.
+    pub places: Vec<usize>,
+    /// The image of each transition offset.
+    pub transitions: Vec<usize>,
+}
+
+impl Permutation {
+    /// Checks that every place and transition maps to itself.
+    pub fn is_identity(&self) -> bool {
+        self.places.iter().enumerate().all(|(i, &p)| i == p)
+            && self.transitions.iter().enumerate().all(|(j, &t)| j == t)
+    }
+
+    /// Moves each place's tokens to its image, giving the symmetric marking.
+    pub fn apply(&self, state: &Vector) -> Vector {
+        let mut out = vec![0; state.len()];
+        for (i, &p) in self.places.iter().enumerate() {
+            out[p] = state[i];
+        }
+        out
+    }
+}
+
+/// How a place and a transition are connected: delta, consumed tokens, inhibitor and read guard weights.
+type Edge = (i32, i32, i32, i32);
+
+impl StateMachine {
+    /// Finds every permutation of places and transitions that preserves the net, identity first.
+    ///
+    /// A permutation must keep each place's initial marking and capacity, and map every
+    /// place/transition pair to a pair with the same delta, consumed tokens, and guard weights.
+    /// Guard groups are compared by weight only. Candidates are narrowed by colour refinement
+    /// and then found by backtracking in breadth-first order, so each choice is pinned by its
+    /// already mapped neighbours. Nets built from many interchangeable parts have factorially
+    /// many symmetries, so the search stops once more than `limit` are found.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Err(Truncated)` if the net has more than `limit` symmetries.
+    pub fn detect_symmetries(&self, limit: usize) -> Result<Vec<Permutation>, Truncated> {
+        let places = self.places.len();
+        let nodes = places + self.actions.len();
+        let edges = self.incidence_edges();
+        let mut neighbours: Vec<Vec<(usize, Edge)>> = vec![Vec::new(); nodes];
+        for (&(p, t), &edge) in &edges {
+            neighbours[p].push((places + t, edge));
+            neighbours[places + t].push((p, edge));
+        }
+
+        let initial: Vec<Vec<i32>> = (0..nodes)
+            .map(|v| {
+                if v < places {
+                    let capacity = self.place_capacity(v);
+                    vec![
+                        0,
+                        self.initial[v],
+                        i32::from(capacity.is_some()),
+                        capacity.unwrap_or(0),
+                    ]
+                } else {
+                    vec![1]
+                }
+            })
+            .collect();
+        let colours = refine(&initial, &neighbours);
+
+        let order = breadth_first_order(&neighbours);
+        let mut search = Search {
+            places,
+            edges: &edges,
+            colours: &colours,
+            order: &order,
+            image: vec![None; nodes],
+            used: vec![false; nodes],
+            found: Vec::new(),
+            limit,
+        };
+        search.extend(0)?;
+        let mut found = search.found;
+        found.sort_by_key(|perm| !perm.is_identity());
+        Ok(found)
+    }
+
+    /// Collects the non-zero connections between places and transitions, keyed by offsets.
+    fn incidence_edges(&self) -> HashMap<(usize, usize), Edge> {
+        let mut edges = HashMap::new();
+        for (t, action) in self.actions.iter().enumerate() {
+            let transition = &self.transitions[action];
+            for p in 0..self.places.len() {
+                let consume = transition.consume.get(p).copied().unwrap_or(0);
+                let mut edge = (transition.delta[p], consume, 0, 0);
+                let guards = transition.guards.values().chain(
+                    transition
+                        .guard_groups
+                        .values()
+                        .flat_map(|g| g.guards.values()),
+                );
+                for guard in guards {
+                    if guard.read {
+                        edge.3 += guard.delta[p];
+                    } else {
+                        edge.2 += guard.delta[p];
+                    }
+                }
+                if edge != (0, 0, 0, 0) {
+                    edges.insert((p, t), edge);
+                }
+            }
+        }
+        edges
+    }
+}
+
+/// Refines node colours by their neighbours' colours until the partition stops splitting.
+fn refine(initial: &[Vec<i32>], neighbours: &[Vec<(usize, Edge)>]) -> Vec<usize> {
+    let (mut colours, mut count) = renumber(initial);
+    loop {
+        let keys: Vec<(usize, Vec<(Edge, usize)>)> = neighbours
+            .iter()
+            .enumerate()
+            .map(|(v, adjacent)| {
+                let mut signature: Vec<(Edge, usize)> = adjacent
+                    .iter()
+                    .map(|&(u, edge)| (edge, colours[u]))
+                    .collect();
+                signature.sort_unstable();
+                (colours[v], signature)
+            })
+            .collect();
+        let (refined, refined_count) = renumber(&keys);
+        if refined_count == count {
+            return colours;
+        }
+        colours = refined;
+        count = refined_count;
+    }
+}
+
+/// Numbers each distinct key in sorted order, returning each key's number and how many there are.
+fn renumber<K: Ord>(keys: &[K]) -> (Vec<usize>, usize) {
+    let mut ids: BTreeMap<&K, usize> = keys.iter().map(|k| (k, 0)).collect();
+    for (i, id) in ids.values_mut().enumerate() {
+        *id = i;
+    }
+    (keys.iter().map(|k| ids[k]).collect(), ids.len())
+}
+
+/// Orders nodes so that, within each connected component, every node after the first has an earlier neighbour.
+fn breadth_first_order(neighbours: &[Vec<(usize, Edge)>]) -> Vec<usize> {
+    let mut seen = vec![false; neighbours.len()];
+    let mut order = Vec::with_capacity(neighbours.len());
+    for start in 0..neighbours.len() {
+        if seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut queue = VecDeque::from([start]);
+        while let Some(v) = queue.pop_front() {
+            order.push(v);
+            for &(u, _) in &neighbours[v] {
+                if !seen[u] {
+                    seen[u] = true;
+                    queue.push_back(u);
+                }
+            }
+        }
+    }
+    order
+}
+
+/// The state of the backtracking search for automorphisms.
+struct Search<'a> {
+    places: usize,
+    edges: &'a HashMap<(usize, usize), Edge>,
+    colours: &'a [usize],
+    order: &'a [usize],
+    image: Vec<Option<usize>>,
+    used: Vec<bool>,
+    found: Vec<Permutation>,
+    limit: usize,
+}
+
+impl Search<'_> {
+    /// Returns the connection between two nodes, if one is a place and the other a transition.
+    fn edge(&self, a: usize, b: usize) -> Option<Edge> {
+        let key = match (a < self.places, b < self.places) {
+            (true, false) => (a, b - self.places),
+            (false, true) => (b, a - self.places),
+            _ => return None,
+        };
+        self.edges.get(&key).copied()
+    }
+
+    /// Maps the nodes from position `depth` of the search order on, recording each complete mapping.
+    fn extend(&mut self, depth: usize) -> Result<(), Truncated> {
+        let Some(&v) = self.order.get(depth) else {
+            let image: Vec<usize> = self
+                .image
+                .iter()
+                .map(|i| i.expect("every node mapped"))
+                .collect();
+            let (places, transitions) = image.split_at(self.places);
+            self.found.push(Permutation {
+                places: places.to_vec(),
+                transitions: transitions.iter().map(|t| t - self.places).collect(),
+            });
+            if self.found.len() > self.limit {
+                return Err(Truncated { limit: self.limit });
+            }
+            return Ok(());
+        };
+        for w in 0..self.colours.len() {
+            if self.used[w] || self.colours[w] != self.colours[v] {
+                continue;
+            }
+            let consistent = self.order[..depth].iter().all(|&u| {
+                let image = self.image[u].expect("earlier nodes are mapped");
+                self.edge(v, u) == self.edge(w, image)
+            });
+            if !consistent {
+                continue;
+            }
+            self.image[v] = Some(w);
+            self.used[w] = true;
+            self.extend(depth + 1)?;
+            self.image[v] = None;
+            self.used[w] = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::petri_net::PetriNet;
+    use crate::reachability::{Truncated, DEFAULT_STATE_LIMIT};
+    use crate::vasm::StateMachine;
+
+    #[test]
+    fn test_dining_philosophers_rotation() {
+        let vm = StateMachine::from_model(&mut PetriNet::dining_philosophers(5));
+        let symmetries = vm.detect_symmetries(DEFAULT_STATE_LIMIT).expect("bounded");
+        assert!(symmetries[0].is_identity());
+        // the rotations and reflections of a five-seat table
+        assert_eq!(symmetries.len(), 10);
+
+        let offset = |label: String| vm.places.iter().position(|p| *p == label).expect("place");
+        let rotation = symmetries
+            .iter()
+            .find(|perm| {
+                (0..5).all(|i| {
+                    perm.places[offset(format!("chopstick{i}"))]
+                        == offset(format!("chopstick{}", (i + 1) % 5))
+                })
+            })
+            .expect("rotation by one seat");
+        let eat = |i: usize| {
+            vm.actions
+                .iter()
+                .position(|a| *a == format!("eat{i}"))
+                .expect("eat")
+        };
+        assert_eq!(rotation.transitions[eat(0)], eat(1));
+
+        let graph = vm.reachability_graph(DEFAULT_STATE_LIMIT).expect("finite");
+        let reachable: HashSet<_> = graph.states.iter().collect();
+        for perm in &symmetries {
+            assert!(graph
+                .states
+                .iter()
+                .all(|s| reachable.contains(&perm.apply(s))));
+        }
+    }
+
+    #[test]
+    fn test_coffee_machine_swaps_its_two_preparation_branches() {
+        let vm = StateMachine::new(crate::petri_net::tests::coffee_machine);
+        let symmetries = vm.detect_symmetries(DEFAULT_STATE_LIMIT).expect("bounded");
+        assert_eq!(symmetries.len(), 2);
+        assert!(symmetries[0].is_identity());
+
+        let offset = |label: &str| vm.places.iter().position(|p| p == label).expect(label);
+        let swap = &symmetries[1];
+        assert_eq!(swap.places[offset("Water")], offset("CoffeeBeans"));
+        assert_eq!(swap.places[offset("BoiledWater")], offset("GroundCoffee"));
+        assert_eq!(swap.places[offset("Cup")], offset("Cup"));
+    }
+
+    #[test]
+    fn test_detect_symmetries_truncated() {
+        let vm = StateMachine::from_model(&mut PetriNet::dining_philosophers(5));
+        assert_eq!(vm.detect_symmetries(9), Err(Truncated { limit: 9 }));
+    }
+}
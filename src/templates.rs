@@ -0,0 +1,154 @@
+use crate::dsl::{Builder, Dsl};
+use crate::petri_net::PetriNet;
+
+impl PetriNet {
+    /// Builds the classic producer/consumer net around a buffer of `buffer_capacity` slots.
+    ///
+    /// The buffer is bounded structurally by a complementary `slots` place rather than by a place
+    /// capacity, so `deliver` blocks while every slot is full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buffer_capacity` is not positive.
+    pub fn producer_consumer(buffer_capacity: i32) -> Self {
+        assert!(buffer_capacity > 0, "buffer capacity must be positive");
+        let mut net = Self::new();
+        let mut p = Builder::new(&mut net);
+        p.model_type("petriNet");
+        let idle = p.cell("producer_idle", Option::from(1), None, 100, 100);
+        let ready = p.cell("producer_ready", None, None, 100, 300);
+        let buffer = p.cell("buffer", None, None, 340, 160);
+        let slots = p.cell("slots", Option::from(buffer_capacity), None, 340, 300);
+        let waiting = p.cell("consumer_idle", Option::from(1), None, 580, 100);
+        let holding = p.cell("consumer_ready", None, None, 580, 300);
+        let produce = p.func("produce", "producer", 20, 200);
+        let deliver = p.func("deliver", "producer", 220, 200);
+        let take = p.func("take", "consumer", 460, 200);
+        let consume = p.func("consume", "consumer", 660, 200);
+        p.arrow(idle, produce, 1);
+        p.arrow(produce, ready, 1);
+        p.arrow(ready, deliver, 1);
+        p.arrow(slots, deliver, 1);
+        p.arrow(deliver, idle, 1);
+        p.arrow(deliver, buffer, 1);
+        p.arrow(waiting, take, 1);
+        p.arrow(buffer, take, 1);
+        p.arrow(take, holding, 1);
+        p.arrow(take, slots, 1);
+        p.arrow(holding, consume, 1);
+        p.arrow(consume, waiting, 1);
+        net
+    }
+
+    /// Builds `n_processes` processes sharing one critical section guarded by a `mutex` place.
+    ///
+    /// Process `i` cycles through `idle{i}`, `enter{i}`, `critical{i}`, and `leave{i}`, laid out
+    /// in columns either side of the mutex.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are too many processes to lay out in `i32` coordinates.
+    pub fn mutex(n_processes: usize) -> Self {
+        let mut net = Self::new();
+        let mut p = Builder::new(&mut net);
+        p.model_type("petriNet");
+        let mutex = p.cell("mutex", Option::from(1), None, 100, 260);
+        for i in 0..n_processes {
+            let x = 220 + 160 * i32::try_from(i).expect("too many processes");
+            let (idle, enter) = (format!("idle{i}"), format!("enter{i}"));
+            let (critical, leave) = (format!("critical{i}"), format!("leave{i}"));
+            p.cell(&idle, Option::from(1), None, x, 100);
+            p.func(&enter, "default", x - 40, 180);
+            p.cell(&critical, None, None, x, 260);
+            p.func(&leave, "default", x + 40, 180);
+            p.arrow(&idle, &enter, 1);
+            p.arrow(mutex, &enter, 1);
+            p.arrow(&enter, &critical, 1);
+            p.arrow(&critical, &leave, 1);
+            p.arrow(&leave, &idle, 1);
+            p.arrow(&leave, mutex, 1);
+        }
+        net
+    }
+
+    /// Builds `n` dining philosophers around a table of `n` chopsticks.
+    ///
+    /// Philosopher `i` picks up `chopstick{i}` and the next chopstick together in `eat{i}`,
+    /// holds them in `eating{i}`, and puts both down in `think{i}`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are too many philosophers to lay out in `i32` coordinates.
+    pub fn dining_philosophers(n: usize) -> Self {
+        let mut net = Self::new();
+        let mut p = Builder::new(&mut net);
+        p.model_type("petriNet");
+        for i in 0..n {
+            let x = 100 + 160 * i32::try_from(i).expect("too many philosophers");
+            p.cell(&format!("chopstick{i}"), Option::from(1), None, x, 100);
+        }
+        for i in 0..n {
+            let x = 180 + 160 * i32::try_from(i).expect("too many philosophers");
+            let (eat, eating, think) =
+                (format!("eat{i}"), format!("eating{i}"), format!("think{i}"));
+            let (left, right) = (format!("chopstick{i}"), format!("chopstick{}", (i + 1) % n));
+            p.func(&eat, "default", x - 40, 200);
+            p.cell(&eating, None, None, x, 300);
+            p.func(&think, "default", x + 40, 200);
+            p.arrow(&left, &eat, 1);
+            p.arrow(&right, &eat, 1);
+            p.arrow(&eat, &eating, 1);
+            p.arrow(&eating, &think, 1);
+            p.arrow(&think, &left, 1);
+            p.arrow(&think, &right, 1);
+        }
+        net
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::petri_net::PetriNet;
+    use crate::vasm::StateMachine;
+
+    fn offset(vm: &StateMachine, label: &str) -> usize {
+        vm.places.iter().position(|p| p == label).expect(label)
+    }
+
+    #[test]
+    fn test_producer_consumer_is_bounded() {
+        let vm = StateMachine::from_model(&mut PetriNet::producer_consumer(3));
+        let buffer = offset(&vm, "buffer");
+        assert_eq!(vm.can_exceed(buffer, 3), Ok(true));
+        assert_eq!(vm.can_exceed(buffer, 4), Ok(false));
+    }
+
+    #[test]
+    fn test_mutex_is_mutually_exclusive() {
+        let vm = StateMachine::from_model(&mut PetriNet::mutex(3));
+        for i in 0..3 {
+            for j in (i + 1)..3 {
+                let (a, b) = (format!("critical{i}"), format!("critical{j}"));
+                assert_eq!(
+                    vm.mutual_exclusion_holds(offset(&vm, &a), offset(&vm, &b)),
+                    Ok(true)
+                );
+            }
+        }
+        assert_eq!(vm.can_exceed(offset(&vm, "critical2"), 1), Ok(true));
+    }
+
+    #[test]
+    fn test_neighbouring_philosophers_never_eat_together() {
+        let vm = StateMachine::from_model(&mut PetriNet::dining_philosophers(5));
+        for i in 0..5 {
+            let (a, b) = (format!("eating{i}"), format!("eating{}", (i + 1) % 5));
+            assert_eq!(
+                vm.mutual_exclusion_holds(offset(&vm, &a), offset(&vm, &b)),
+                Ok(true)
+            );
+        }
+        let (a, b) = (offset(&vm, "eating0"), offset(&vm, "eating2"));
+        assert_eq!(vm.mutual_exclusion_holds(a, b), Ok(false));
+    }
+}
@@ -0,0 +1,225 @@
+use crate::dsl::{Builder, Dsl};
+use crate::petri_net::PetriNet;
+use crate::simulate::XorShift64;
+
+/// `NetGenerator` builds random valid `PetriNet`s, for property testing analyses.
+///
+/// Every generated net passes `PetriNet::validate`: arcs join a place and a transition, offsets
+/// run from zero without gaps, and every node has at least one arc. Nets are drawn from an
+/// `XorShift64`, so a property test framework only needs to generate the seed; `shrink` then
+/// proposes smaller nets when a property fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetGenerator {
+    /// The most places a net may have, at least one.
+    pub max_places: usize,
+    /// The most transitions a net may have, at least one.
+    pub max_transitions: usize,
+    /// The largest arc weight, at least one.
+    pub max_weight: i32,
+    /// The largest initial token count of a place.
+    pub max_initial: i32,
+}
+
+impl Default for NetGenerator {
+    fn default() -> Self {
+        Self {
+            max_places: 5,
+            max_transitions: 5,
+            max_weight: 3,
+            max_initial: 3,
+        }
+    }
+}
+
+/// Draws a value in `0..=max`.
+fn up_to(rng: &mut XorShift64, max: i32) -> i32 {
+    let n = usize::try_from(max.max(0)).expect("max is not negative") + 1;
+    i32::try_from(rng.below(n)).expect("value fits in i32")
+}
+
+/// Lays nodes out on a grid so generated nets render legibly.
+fn grid(i: usize, row: i32) -> (i32, i32) {
+    (100 + 80 * i32::try_from(i).expect("too many nodes"), row)
+}
+
+impl NetGenerator {
+    /// Generates a random net from `rng`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_places`, `max_transitions`, or `max_weight` is less than one.
+    pub fn generate(&self, rng: &mut XorShift64) -> PetriNet {
+        assert!(self.max_places > 0 && self.max_transitions > 0 && self.max_weight > 0);
+        let places = 1 + rng.below(self.max_places);
+        let transitions = 1 + rng.below(self.max_transitions);
+        let mut net = PetriNet::new();
+        let mut p = Builder::new(&mut net);
+        p.model_type("petriNet");
+        for i in 0..places {
+            let initial = up_to(rng, self.max_initial);
+            let (x, y) = grid(i, 100);
+            p.cell(&format!("p{i}"), Some(initial), None, x, y);
+        }
+        for j in 0..transitions {
+            let (x, y) = grid(j, 200);
+            p.func(&format!("t{j}"), "default", x, y);
+        }
+
+        for j in 0..transitions {
+            let place = rng.below(places);
+            self.random_arc(&mut p, rng, place, j);
+        }
+        for i in 0..places {
+            let transition = rng.below(transitions);
+            self.random_arc(&mut p, rng, i, transition);
+        }
+        for _ in 0..rng.below(places * transitions + 1) {
+            let (place, transition) = (rng.below(places), rng.below(transitions));
+            self.random_arc(&mut p, rng, place, transition);
+        }
+        net
+    }
+
+    /// Joins a place and a transition by a consuming, producing, or inhibitor arc of random weight.
+    fn random_arc(
+        &self,
+        p: &mut Builder<'_>,
+        rng: &mut XorShift64,
+        place: usize,
+        transition: usize,
+    ) {
+        let (place, transition) = (format!("p{place}"), format!("t{transition}"));
+        let weight = 1 + up_to(rng, self.max_weight - 1);
+        match rng.below(5) {
+            0 | 1 => p.arrow(&place, &transition, weight),
+            2 | 3 => p.arrow(&transition, &place, weight),
+            _ => p.guard(&place, &transition, weight),
+        }
+    }
+
+    /// Proposes smaller variants of a valid net, each still valid, simplest changes last.
+    ///
+    /// Candidates drop one place together with its arcs and any transition left without arcs,
+    /// drop one arc whose endpoints keep another arc, lower an arc weight to one, or empty a
+    /// place's initial marking.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the net has more nodes than fit in an `i32` offset.
+    pub fn shrink(net: &PetriNet) -> Vec<PetriNet> {
+        let mut candidates = Vec::new();
+        let mut places: Vec<&String> = net.places.keys().collect();
+        places.sort_by_key(|label| net.places[*label].offset);
+
+        if places.len() > 1 {
+            for label in &places {
+                candidates.push(without_place(net, label));
+            }
+        }
+        let degree = |node: &String| {
+            net.arcs
+                .iter()
+                .filter(|a| a.source == *node || a.target == *node)
+                .count()
+        };
+        for (i, arc) in net.arcs.iter().enumerate() {
+            if degree(&arc.source) > 1 && degree(&arc.target) > 1 {
+                let mut smaller = net.clone();
+                smaller.arcs.remove(i);
+                candidates.push(smaller);
+            }
+        }
+        for (i, arc) in net.arcs.iter().enumerate() {
+            if arc.weight.is_some_and(|w| w > 1) {
+                let mut smaller = net.clone();
+                smaller.arcs[i].weight = Some(1);
+                candidates.push(smaller);
+            }
+        }
+        for label in &places {
+            if net.places[*label].initial.is_some_and(|t| t > 0) {
+                let mut smaller = net.clone();
+                if let Some(place) = smaller.places.get_mut(*label) {
+                    place.initial = Some(0);
+                }
+                candidates.push(smaller);
+            }
+        }
+        candidates
+    }
+}
+
+/// Removes a place, its arcs, and any transition left without arcs, renumbering offsets.
+fn without_place(net: &PetriNet, label: &str) -> PetriNet {
+    let mut smaller = net.clone();
+    smaller.places.retain(|place, _| place != label);
+    smaller
+        .arcs
+        .retain(|a| a.source != label && a.target != label);
+    let arcs = &smaller.arcs;
+    smaller
+        .transitions
+        .retain(|t, _| arcs.iter().any(|a| a.source == *t || a.target == *t));
+
+    let mut places: Vec<(i32, String)> = smaller
+        .places
+        .iter()
+        .map(|(k, v)| (v.offset, k.clone()))
+        .collect();
+    places.sort();
+    for (offset, (_, label)) in places.into_iter().enumerate() {
+        smaller.places.get_mut(&label).expect("place").offset =
+            i32::try_from(offset).expect("offset fits in i32");
+    }
+    let mut transitions: Vec<(i32, String)> = smaller
+        .transitions
+        .iter()
+        .map(|(k, v)| (v.offset, k.clone()))
+        .collect();
+    transitions.sort();
+    for (offset, (_, label)) in transitions.into_iter().enumerate() {
+        smaller
+            .transitions
+            .get_mut(&label)
+            .expect("transition")
+            .offset = i32::try_from(offset).expect("offset fits in i32");
+    }
+    smaller
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vasm::StateMachine;
+
+    #[test]
+    fn test_generated_nets_are_valid() {
+        let generator = NetGenerator::default();
+        let mut rng = XorShift64::new(2024);
+        for _ in 0..1000 {
+            let mut net = generator.generate(&mut rng);
+            assert_eq!(net.validate(), Vec::new(), "{net:?}");
+            assert!(StateMachine::try_from_model(&mut net).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_shrunk_nets_are_valid_and_smaller() {
+        let generator = NetGenerator {
+            max_places: 3,
+            max_transitions: 3,
+            max_weight: 4,
+            max_initial: 2,
+        };
+        let mut rng = XorShift64::new(7);
+        for _ in 0..100 {
+            let net = generator.generate(&mut rng);
+            let size = |n: &PetriNet| n.places.len() + n.transitions.len() + n.arcs.len();
+            for smaller in NetGenerator::shrink(&net) {
+                assert_eq!(smaller.validate(), Vec::new(), "{smaller:?}");
+                assert!(size(&smaller) <= size(&net));
+                assert_ne!(smaller, net);
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use crate::vasm::{StateMachine, Vasm, Vector};
+
+/// The most steps `example_trace` will take before stopping.
+pub const EXAMPLE_TRACE_LIMIT: usize = 100;
+
+/// The most firing sequences `all_traces` will return.
+pub const MAX_TRACES: usize = 10_000;
+
+impl StateMachine {
+    /// Runs a short deterministic simulation from the initial marking.
+    ///
+    /// At each step the enabled action with the lowest offset fires, until no action is
+    /// enabled or `EXAMPLE_TRACE_LIMIT` steps have been taken. Each step is returned as the
+    /// action fired and the named marking it produced.
+    pub fn example_trace(&self) -> Vec<(String, HashMap<String, i32>)> {
+        let mut state = self.initial_vector();
+        let mut trace = Vec::new();
+        while trace.len() < EXAMPLE_TRACE_LIMIT {
+            let Some(action) = self.enabled_actions(&state).into_iter().next() else {
+                break;
+            };
+            state = self.transform(&state, &action, 1).output;
+            trace.push((action, self.marking_map(&state)));
+        }
+        trace
+    }
+
+    /// Fires actions from `start` until `max_steps` have been taken or no action is enabled.
+    ///
+    /// Like `example_trace`, the enabled action with the lowest offset fires at each step, so
+    /// this is a safe default driver for cyclic nets that a `Process` loop would run forever.
+    /// Each step is returned as the action fired and the marking it produced.
+    pub fn run_bounded(&self, start: &Vector, max_steps: usize) -> Vec<(String, Vector)> {
+        let mut state = start.clone();
+        let mut steps = Vec::new();
+        while steps.len() < max_steps {
+            let Some(action) = self.enabled_actions(&state).into_iter().next() else {
+                break;
+            };
+            state = self.transform(&state, &action, 1).output;
+            steps.push((action, state.clone()));
+        }
+        steps
+    }
+
+    /// Lists every firing sequence of length 1 to `max_len` starting from the initial marking.
+    ///
+    /// Sequences are produced depth first, trying actions in offset order, and stop growing at a
+    /// deadlock. The number of sequences grows exponentially with `max_len`, so at most
+    /// `MAX_TRACES` are returned.
+    pub fn all_traces(&self, max_len: usize) -> Vec<Vec<String>> {
+        let mut traces = Vec::new();
+        self.extend_traces(
+            &self.initial_vector(),
+            &mut Vec::new(),
+            max_len,
+            &mut traces,
+        );
+        traces
+    }
+
+    fn extend_traces(
+        &self,
+        state: &Vector,
+        prefix: &mut Vec<String>,
+        max_len: usize,
+        traces: &mut Vec<Vec<String>>,
+    ) {
+        if prefix.len() >= max_len {
+            return;
+        }
+        for action in self.enabled_actions(state) {
+            if traces.len() >= MAX_TRACES {
+                return;
+            }
+            let next = self.transform(state, &action, 1).output;
+            prefix.push(action);
+            traces.push(prefix.clone());
+            self.extend_traces(&next, prefix, max_len, traces);
+            prefix.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::Model;
+    use crate::petri_net::tests::coffee_machine;
+    use crate::vasm::Vasm;
+
+    #[test]
+    fn test_coffee_machine_trace() {
+        let m = Model::new(coffee_machine);
+
+        let trace = m.vm.example_trace();
+        let actions: Vec<&str> = trace.iter().map(|(a, _)| a.as_str()).collect();
+        assert_eq!(
+            actions,
+            vec!["boil_water", "grind_beans", "brew_coffee", "pour_coffee"]
+        );
+
+        let (_, last) = trace.last().expect("non-empty trace");
+        assert_eq!(last["CoffeeInPot"], 0);
+        assert_eq!(last["Cup"], 0);
+        let final_state: Vec<i32> = m.vm.places.iter().map(|p| last[p]).collect();
+        assert!(m.vm.enabled_actions(&final_state).is_empty());
+    }
+
+    #[test]
+    fn test_all_traces() {
+        let m = Model::new(|p| {
+            p.cell("A", Option::from(1), None, 100, 100);
+            p.cell("B", None, None, 200, 50);
+            p.cell("C", None, None, 200, 150);
+            p.func("a_to_b", "default", 150, 50);
+            p.func("a_to_c", "default", 150, 150);
+            p.func("b_to_a", "default", 250, 50);
+            p.arrow("A", "a_to_b", 1);
+            p.arrow("a_to_b", "B", 1);
+            p.arrow("A", "a_to_c", 1);
+            p.arrow("a_to_c", "C", 1);
+            p.arrow("B", "b_to_a", 1);
+            p.arrow("b_to_a", "A", 1);
+        });
+        assert_eq!(
+            m.vm.all_traces(2),
+            vec![
+                vec!["a_to_b".to_string()],
+                vec!["a_to_b".to_string(), "b_to_a".to_string()],
+                vec!["a_to_c".to_string()],
+            ]
+        );
+        assert!(m.vm.all_traces(0).is_empty());
+        assert_eq!(m.vm.all_traces(3).len(), 5);
+    }
+
+    #[test]
+    fn test_run_bounded() {
+        let cyclic = Model::new(|p| {
+            p.cell("idle", Option::from(1), None, 100, 100);
+            p.cell("busy", None, None, 200, 100);
+            p.func("start", "default", 150, 50);
+            p.func("stop", "default", 150, 150);
+            p.arrow("idle", "start", 1);
+            p.arrow("start", "busy", 1);
+            p.arrow("busy", "stop", 1);
+            p.arrow("stop", "idle", 1);
+        });
+        let steps = cyclic.vm.run_bounded(&cyclic.vm.initial_vector(), 5);
+        assert_eq!(steps.len(), 5);
+        assert_eq!(steps[0], ("start".to_string(), vec![0, 1]));
+        assert_eq!(steps[4], ("start".to_string(), vec![0, 1]));
+
+        let coffee = Model::new(coffee_machine);
+        let steps = coffee.vm.run_bounded(&coffee.vm.initial_vector(), 50);
+        assert_eq!(steps.len(), 4);
+    }
+}
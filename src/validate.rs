@@ -0,0 +1,280 @@
+use std::fmt;
+
+use crate::error::ModelError;
+use crate::model::Model;
+use crate::petri_net::PetriNet;
+use crate::vasm::offset_errors;
+
+/// `ModelIssue` describes one structural problem found by `PetriNet::validate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelIssue {
+    /// A place with no arcs.
+    OrphanPlace(String),
+    /// A transition with no arcs.
+    OrphanTransition(String),
+    /// An arc that does not join a place and a transition of the net.
+    BadArcEndpoint {
+        index: usize,
+        source: String,
+        target: String,
+    },
+    /// An arc with contradictory consume, produce, inhibit, or read flags.
+    ContradictoryArc { index: usize, error: ModelError },
+    /// A place offset outside `0..places.len()`, or repeating another place's offset.
+    PlaceOffset { label: String, offset: i32 },
+    /// A transition offset outside `0..transitions.len()`, or repeating another one's offset.
+    TransitionOffset { label: String, offset: i32 },
+    /// A negative initial token count.
+    NegativeInitial { label: String, initial: i32 },
+    /// An initial token count above a bounded capacity.
+    InitialExceedsCapacity {
+        label: String,
+        initial: i32,
+        capacity: i32,
+    },
+}
+
+impl fmt::Display for ModelIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OrphanPlace(label) => write!(f, "place {label} has no arcs"),
+            Self::OrphanTransition(label) => write!(f, "transition {label} has no arcs"),
+            Self::BadArcEndpoint {
+                index,
+                source,
+                target,
+            } => write!(
+                f,
+                "arc {index} ({source} -> {target}) must join a place and a transition"
+            ),
+            Self::ContradictoryArc { index, error } => write!(f, "arc {index}: {error}"),
+            Self::PlaceOffset { label, offset } => {
+                write!(
+                    f,
+                    "place {label} has duplicate or out of range offset {offset}"
+                )
+            }
+            Self::TransitionOffset { label, offset } => write!(
+                f,
+                "transition {label} has duplicate or out of range offset {offset}"
+            ),
+            Self::NegativeInitial { label, initial } => {
+                write!(f, "place {label} has negative initial {initial}")
+            }
+            Self::InitialExceedsCapacity {
+                label,
+                initial,
+                capacity,
+            } => write!(
+                f,
+                "place {label} starts with {initial} tokens but has capacity {capacity}"
+            ),
+        }
+    }
+}
+
+/// Lists the labels and offsets `StateMachine::try_from_model` would reject, in offset order.
+fn bad_offsets(nodes: Vec<(&String, i32)>) -> Vec<(String, i32)> {
+    offset_errors(nodes)
+        .into_iter()
+        .filter_map(|error| match error {
+            ModelError::InvalidOffset { label, offset }
+            | ModelError::DuplicateOffset { label, offset, .. } => Some((label, offset)),
+            _ => None,
+        })
+        .collect()
+}
+
+impl PetriNet {
+    /// Runs every structural check and returns all issues found.
+    ///
+    /// Issues are grouped by check, in the order the variants of `ModelIssue` are declared,
+    /// and sorted by label, offset or arc index within each group, so the output is stable
+    /// across runs.
+    pub fn validate(&self) -> Vec<ModelIssue> {
+        let mut issues = Vec::new();
+
+        let mut places: Vec<&String> = self.places.keys().collect();
+        places.sort();
+        let mut transitions: Vec<&String> = self.transitions.keys().collect();
+        transitions.sort();
+        let connected = |label: &String| {
+            self.arcs
+                .iter()
+                .any(|arc| arc.source == *label || arc.target == *label)
+        };
+        for label in places.iter().filter(|l| !connected(l)) {
+            issues.push(ModelIssue::OrphanPlace((*label).clone()));
+        }
+        for label in transitions.iter().filter(|l| !connected(l)) {
+            issues.push(ModelIssue::OrphanTransition((*label).clone()));
+        }
+
+        for (index, arc) in self.arcs.iter().enumerate() {
+            if self.check_arc_endpoints(arc).is_err() {
+                issues.push(ModelIssue::BadArcEndpoint {
+                    index,
+                    source: arc.source.clone(),
+                    target: arc.target.clone(),
+                });
+            }
+        }
+
+        let mut populated = self.clone();
+        populated.populate_arc_attributes();
+        for (index, arc) in populated.arcs.iter().enumerate() {
+            if let Err(error) = arc.validate_flags() {
+                issues.push(ModelIssue::ContradictoryArc { index, error });
+            }
+        }
+
+        for (label, offset) in bad_offsets(self.places.iter().map(|(k, v)| (k, v.offset)).collect())
+        {
+            issues.push(ModelIssue::PlaceOffset { label, offset });
+        }
+        for (label, offset) in bad_offsets(
+            self.transitions
+                .iter()
+                .map(|(k, v)| (k, v.offset))
+                .collect(),
+        ) {
+            issues.push(ModelIssue::TransitionOffset { label, offset });
+        }
+
+        for label in &places {
+            let place = &self.places[*label];
+            let initial = place.initial.unwrap_or(0);
+            if initial < 0 {
+                issues.push(ModelIssue::NegativeInitial {
+                    label: (*label).clone(),
+                    initial,
+                });
+            } else if let Some(capacity) = place
+                .capacity
+                .filter(|&c| initial > c && (c > 0 || place.zero_capacity))
+            {
+                issues.push(ModelIssue::InitialExceedsCapacity {
+                    label: (*label).clone(),
+                    initial,
+                    capacity,
+                });
+            }
+        }
+        issues
+    }
+
+    /// Lists the pairs of places and transitions drawn closer together than `min_dist`.
+    ///
+    /// Distance is measured between node centres. Each pair is ordered by label, and the pairs
+    /// are sorted, so overlapping nodes can be caught in a test before the net is rendered.
+    pub fn coordinate_collisions(&self, min_dist: i32) -> Vec<(String, String)> {
+        let mut nodes: Vec<(&String, i32, i32)> = self
+            .places
+            .iter()
+            .map(|(label, p)| (label, p.x, p.y))
+            .chain(self.transitions.iter().map(|(label, t)| (label, t.x, t.y)))
+            .collect();
+        nodes.sort();
+        let limit = i64::from(min_dist.max(0)).pow(2);
+        let mut collisions = Vec::new();
+        for (i, &(a, ax, ay)) in nodes.iter().enumerate() {
+            for &(b, bx, by) in &nodes[i + 1..] {
+                let (dx, dy) = (i64::from(bx) - i64::from(ax), i64::from(by) - i64::from(ay));
+                if dx * dx + dy * dy < limit {
+                    collisions.push((a.clone(), b.clone()));
+                }
+            }
+        }
+        collisions
+    }
+}
+
+impl Model {
+    /// Validates the model's net, returning every issue at once.
+    ///
+    /// This is the one call to make before trusting a model loaded from outside the program.
+    pub fn validate(&self) -> Result<(), Vec<ModelIssue>> {
+        let issues = self.net.validate();
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::petri_net::tests::DINING_PHILOSOPHERS;
+    use crate::petri_net::Arrow;
+
+    #[test]
+    fn test_valid_model() {
+        let m = Model::from_json_str(DINING_PHILOSOPHERS);
+        assert_eq!(m.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_reports_every_issue() {
+        let mut m = Model::new(|p| {
+            p.cell("full", Option::from(3), Option::from(1), 100, 100);
+            p.cell("empty", None, None, 200, 100);
+            p.cell("unused", None, None, 300, 100);
+            p.func("move", "default", 150, 100);
+            p.arrow("full", "move", 1);
+            p.arrow("move", "empty", 1);
+        });
+        m.net.arcs.push(Arrow {
+            source: "move".to_string(),
+            target: "missing".to_string(),
+            weight: Some(1),
+            consume: None,
+            produce: None,
+            inhibit: None,
+            read: None,
+            group: None,
+        });
+
+        let issues = m.validate().expect_err("model has problems");
+        assert_eq!(
+            issues,
+            vec![
+                ModelIssue::OrphanPlace("unused".to_string()),
+                ModelIssue::BadArcEndpoint {
+                    index: 2,
+                    source: "move".to_string(),
+                    target: "missing".to_string(),
+                },
+                ModelIssue::InitialExceedsCapacity {
+                    label: "full".to_string(),
+                    initial: 3,
+                    capacity: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_coordinate_collisions() {
+        let m = Model::new(|p| {
+            p.cell("a", None, None, 100, 100);
+            p.cell("b", None, None, 100, 100);
+            p.cell("c", None, None, 300, 100);
+            p.func("t", "default", 305, 100);
+            p.func("u", "default", 200, 300);
+        });
+        assert_eq!(
+            m.net.coordinate_collisions(10),
+            vec![
+                ("a".to_string(), "b".to_string()),
+                ("c".to_string(), "t".to_string())
+            ]
+        );
+        assert_eq!(
+            m.net.coordinate_collisions(1),
+            vec![("a".to_string(), "b".to_string())]
+        );
+        assert!(m.net.coordinate_collisions(0).is_empty());
+    }
+}
@@ -1,19 +1,114 @@
 use std::collections::HashMap;
 use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::str::FromStr;
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
 use crate::dsl::Dsl;
+use crate::error::ModelError;
 use crate::petri_net::PetriNet;
 
 /// RoleMap is a type alias for a HashMap that maps a string to a boolean.
 pub type RoleMap = HashMap<String, bool>;
 
-/// Vector is a type alias for a vector of 32-bit integers.
+/// Vector is a type alias for a vector of token counts, 32-bit integers by default.
+///
 /// It is used to represent the state of a state machine and the delta of each transition or inhibitor.
-pub type Vector = Vec<i32>;
+pub type Vector<T = i32> = Vec<T>;
+
+/// `Int` is the integer type used to count tokens in a `StateMachine`.
+///
+/// It is implemented for `i32` (the default) and `i64`, for long simulations where markings
+/// may grow past `i32::MAX`.
+pub trait Int: Copy + Ord + Hash + Default + fmt::Debug + From<i32> {
+    /// The empty token count.
+    const ZERO: Self;
+
+    /// Adds two counts, returning `None` if the result does not fit.
+    fn checked_add(self, other: Self) -> Option<Self>;
+
+    /// Multiplies two counts, returning `None` if the result does not fit.
+    fn checked_mul(self, other: Self) -> Option<Self>;
+}
+
+impl Int for i32 {
+    const ZERO: Self = 0;
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        self.checked_add(other)
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        self.checked_mul(other)
+    }
+}
+
+impl Int for i64 {
+    const ZERO: Self = 0;
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        self.checked_add(other)
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        self.checked_mul(other)
+    }
+}
+
+/// `Marking` is the storage behind a state, read and written one place offset at a time.
+///
+/// `Vector` is the dense default, suited to small nets. `HashMap<usize, T>` is sparse and only
+/// stores the places holding tokens, which suits huge nets where most places are empty.
+pub trait Marking<T: Int = i32>: Clone {
+    /// Returns the token count at a place offset.
+    fn tokens(&self, i: usize) -> T;
+
+    /// Sets the token count at a place offset.
+    fn set_tokens(&mut self, i: usize, v: T);
+
+    /// Adds `v` tokens to a place offset, returning `false` and leaving the count unchanged if
+    /// the sum does not fit in `T`.
+    fn add(&mut self, i: usize, v: T) -> bool {
+        self.tokens(i)
+            .checked_add(v)
+            .map(|sum| self.set_tokens(i, sum))
+            .is_some()
+    }
+}
+
+/// A vector shorter than the net reads its missing places as empty, like a sparse marking,
+/// and grows when one of them is set.
+impl<T: Int> Marking<T> for Vec<T> {
+    fn tokens(&self, i: usize) -> T {
+        self.get(i).copied().unwrap_or(T::ZERO)
+    }
+
+    fn set_tokens(&mut self, i: usize, v: T) {
+        if i >= self.len() {
+            self.resize(i + 1, T::ZERO);
+        }
+        self[i] = v;
+    }
+}
+
+impl<T: Int, S: BuildHasher + Clone> Marking<T> for HashMap<usize, T, S> {
+    fn tokens(&self, i: usize) -> T {
+        self.get(&i).copied().unwrap_or(T::ZERO)
+    }
+
+    fn set_tokens(&mut self, i: usize, v: T) {
+        if v == T::ZERO {
+            self.remove(&i);
+        } else {
+            self.insert(i, v);
+        }
+    }
+}
 
 /// ModelType is an enum that represents the type of model.
+///
 /// It is used to determine the type of state machine to use.
 /// The possible values are `PetriNet`, `Elementary`, and `Workflow`.
 /// The default value is `PetriNet`.
@@ -38,69 +133,263 @@ impl fmt::Display for ModelType {
     }
 }
 
+/// `ModelTypeError` is returned when a string does not name a `ModelType`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelTypeError {
+    /// The string that failed to parse.
+    pub input: String,
+}
+
+impl fmt::Display for ModelTypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown model type: {}, must be one of petriNet, workflow, or elementary",
+            self.input
+        )
+    }
+}
+
+impl std::error::Error for ModelTypeError {}
+
+/// `MarkingError` is returned when a marking does not fit the places of a `StateMachine`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MarkingError {
+    /// The marking has the wrong number of places.
+    Length { expected: usize, actual: usize },
+    /// A place that may not go negative is given a negative token count.
+    Negative { place: String },
+    /// The machine does not support replacing its initial marking.
+    Unsupported,
+}
+
+impl fmt::Display for MarkingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Length { expected, actual } => {
+                write!(f, "marking has {actual} places, expected {expected}")
+            }
+            Self::Negative { place } => write!(f, "place {place} has a negative marking"),
+            Self::Unsupported => write!(f, "initial marking cannot be replaced"),
+        }
+    }
+}
+
+impl std::error::Error for MarkingError {}
+
+impl FromStr for ModelType {
+    type Err = ModelTypeError;
+
+    /// Parses `petriNet`, `workflow`, or `elementary`, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "petrinet" => Ok(Self::PetriNet),
+            "workflow" => Ok(Self::Workflow),
+            "elementary" => Ok(Self::Elementary),
+            _ => Err(ModelTypeError {
+                input: s.to_string(),
+            }),
+        }
+    }
+}
+
 /// Guard is a struct that represents a guard in a state machine.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Guard {
-    delta: Vector,
-    read: bool,
+pub struct Guard<T = i32> {
+    pub(crate) delta: Vector<T>,
+    pub(crate) read: bool,
 }
 
 /// GuardMap is a type alias for a HashMap that maps a string to a `Guard`.
-pub type GuardMap = HashMap<String, Guard>;
+pub type GuardMap<T = i32> = HashMap<String, Guard<T>>;
+
+/// `GuardGroup` is a set of guards of which at least one must be satisfied.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuardGroup<T = i32> {
+    pub(crate) guards: GuardMap<T>,
+}
 
 /// Transition is a struct that represents a transition in a state machine.
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
-pub struct Transition {
-    label: String,
-    role: String,
-    delta: Vector,
-    guards: GuardMap,
-    allow_reentry: bool,
-    offset: i32,
+pub struct Transition<T = i32> {
+    pub(crate) label: String,
+    pub(crate) role: String,
+    pub(crate) delta: Vector<T>,
+    /// The tokens taken from each place, which must be present to fire even when a self-loop
+    /// puts them straight back and leaves no trace in `delta`.
+    #[serde(default = "Vec::new")]
+    pub(crate) consume: Vector<T>,
+    /// Ungrouped guards, which must all be satisfied.
+    pub(crate) guards: GuardMap<T>,
+    /// Named guard groups, keyed by the arc `group`.
+    pub(crate) guard_groups: HashMap<String, GuardGroup<T>>,
+    pub(crate) allow_reentry: bool,
+    pub(crate) offset: i32,
+    /// The relative chance of being picked by `simulate_weighted`, `None` counts as 1.
+    #[serde(default)]
+    pub(crate) weight: Option<u32>,
+}
+
+impl<T> Transition<T> {
+    /// The name of the transition.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// The role allowed to fire the transition.
+    pub fn role(&self) -> &str {
+        &self.role
+    }
+
+    /// The change in tokens at each place when the transition fires once.
+    pub const fn delta(&self) -> &Vector<T> {
+        &self.delta
+    }
+
+    /// The tokens each place must hold for the transition to fire once.
+    pub const fn consume(&self) -> &Vector<T> {
+        &self.consume
+    }
+}
+
+/// `ActionId` is a validated handle to a transition, obtained from `StateMachine::action_handle`.
+///
+/// It indexes into `StateMachine::actions`, so it is only meaningful for the machine that issued it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ActionId(usize);
+
+impl ActionId {
+    /// Returns the position of the action in `StateMachine::actions`.
+    pub const fn index(self) -> usize {
+        self.0
+    }
 }
 
 /// TransitionMap is a type alias for a HashMap that maps a string to a `Transition`.
-pub type TransitionMap = HashMap<String, Transition>;
+pub type TransitionMap<T = i32> = HashMap<String, Transition<T>>;
 
 /// StateMachine is a struct that holds the vectorized / executable form of a Petri-net.
+///
+/// Token counts are `i32` unless the machine is converted with `widen`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct StateMachine {
+pub struct StateMachine<T = i32> {
     pub model_type: ModelType,
-    pub initial: Vector,
-    pub capacity: Vector,
+    pub initial: Vector<T>,
+    pub capacity: Vector<T>,
+    /// Places whose capacity of 0 is enforced rather than read as unbounded, indexed by offset.
+    #[serde(default)]
+    pub zero_capacity: Vec<bool>,
+    /// Places that may go negative without underflowing, indexed by offset.
+    #[serde(default)]
+    pub allow_negative: Vec<bool>,
     pub places: Vec<String>,
-    pub transitions: TransitionMap,
+    pub transitions: TransitionMap<T>,
     pub roles: RoleMap,
     pub actions: Vec<String>,
+    /// Replaces the firing rule chosen by `model_type`, see `with_semantics`.
+    #[serde(skip)]
+    pub semantics: Option<Arc<dyn FireSemantics<T>>>,
 }
 
-fn model_type_from_string(model_type: &str) -> ModelType {
-    match model_type.to_lowercase().as_str() {
-        "elementary" => ModelType::Elementary,
-        "workflow" => ModelType::Workflow,
-        "petrinet" => ModelType::PetriNet,
-        _ => {
-            panic!("unknown model type: {model_type}");
-        }
+/// `FireSemantics` is a firing rule, deciding how a transition changes a marking.
+///
+/// The built-in rules are `PetriNetSemantics`, `ElementarySemantics`, and `WorkflowSemantics`;
+/// a `StateMachine` picks one by its `model_type` unless another is set with `with_semantics`.
+pub trait FireSemantics<T: Int = i32>: fmt::Debug + Send + Sync {
+    /// Fires `transition` against `state`, `multiple` times over.
+    fn fire(
+        &self,
+        sm: &StateMachine<T>,
+        state: &Vector<T>,
+        transition: &Transition<T>,
+        multiple: i32,
+    ) -> Tx<T>;
+}
+
+/// `PetriNetSemantics` fires by `StateMachine::petri_net_fire`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PetriNetSemantics;
+
+/// `ElementarySemantics` fires by `StateMachine::elementary_fire`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ElementarySemantics;
+
+/// `WorkflowSemantics` fires by `StateMachine::workflow_fire`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WorkflowSemantics;
+
+impl<T: Int> FireSemantics<T> for PetriNetSemantics {
+    fn fire(
+        &self,
+        sm: &StateMachine<T>,
+        state: &Vector<T>,
+        transition: &Transition<T>,
+        multiple: i32,
+    ) -> Tx<T> {
+        sm.petri_net_fire(state, transition, multiple)
+    }
+}
+
+impl<T: Int> FireSemantics<T> for ElementarySemantics {
+    fn fire(
+        &self,
+        sm: &StateMachine<T>,
+        state: &Vector<T>,
+        transition: &Transition<T>,
+        multiple: i32,
+    ) -> Tx<T> {
+        sm.elementary_fire(state, transition, multiple)
+    }
+}
+
+impl<T: Int> FireSemantics<T> for WorkflowSemantics {
+    fn fire(
+        &self,
+        sm: &StateMachine<T>,
+        state: &Vector<T>,
+        transition: &Transition<T>,
+        multiple: i32,
+    ) -> Tx<T> {
+        sm.workflow_fire(state, transition, multiple)
     }
 }
 
-fn vector_add(
-    capacity: &Vector,
-    state: &Vector,
-    delta: &Vector,
+/// Returns the capacity at offset `i`, or `None` if the place is unbounded.
+///
+/// A capacity of 0 is unbounded unless `zero_capacity` marks it as enforced.
+fn bounded_capacity<T: Int>(capacity: &[T], zero_capacity: &[bool], i: usize) -> Option<T> {
+    let capacity = *capacity.get(i)?;
+    (capacity > T::ZERO || zero_capacity.get(i).copied().unwrap_or(false)).then_some(capacity)
+}
+
+/// Adds `delta * multiple` to every place of `state`, one entry per `capacity`.
+fn vector_add<T: Int, M: Marking<T>>(
+    capacity: &[T],
+    zero_capacity: &[bool],
+    allow_negative: &[bool],
+    state: &M,
+    delta: &Vector<T>,
     multiple: i32,
-) -> (Vector, bool, bool, bool) {
+) -> (M, bool, bool, bool) {
     let mut overflow = false;
     let mut underflow = false;
-    let mut output: Vector = Vec::new();
+    let mut output = state.clone();
     let mut ok = true;
-    for i in 0..state.len() {
-        output.push(state[i] + delta.get(i).unwrap_or(&0) * multiple);
-        if output[i] < 0 {
+    for i in 0..capacity.len() {
+        let Some(value) = delta
+            .get(i)
+            .map_or(Some(T::ZERO), |d| d.checked_mul(T::from(multiple)))
+            .and_then(|d| state.tokens(i).checked_add(d))
+        else {
+            overflow = true;
+            ok = false; // overflow: exceeds the integer type
+            continue;
+        };
+        output.set_tokens(i, value);
+        if value < T::ZERO && !allow_negative.get(i).copied().unwrap_or(false) {
             underflow = true;
             ok = false; // underflow: contains negative
-        } else if capacity[i] > 0 && capacity[i] - output[i] < 0 {
+        } else if bounded_capacity(capacity, zero_capacity, i).is_some_and(|c| value > c) {
             overflow = true;
             ok = false; // overflow: exceeds capacity
         }
@@ -108,29 +397,268 @@ fn vector_add(
     (output, ok, overflow, underflow)
 }
 
+/// Only `petriNet` models have finite-server transitions; elementary and workflow nets hold at
+/// most one token per place, so their server counts are ignored.
+const fn model_type_has_servers(model_type: ModelType) -> bool {
+    matches!(model_type, ModelType::PetriNet)
+}
+
+/// Checks that `state` holds the tokens a transition consumes, `multiple` times over.
+///
+/// `vector_add` already catches a consume that would underflow, except where a self-loop
+/// produces the tokens straight back. Places that may go negative are exempt.
+fn covers<T: Int, M: Marking<T>>(
+    allow_negative: &[bool],
+    state: &M,
+    consume: &[T],
+    multiple: i32,
+) -> bool {
+    consume.iter().enumerate().all(|(i, &c)| {
+        c == T::ZERO
+            || allow_negative.get(i).copied().unwrap_or(false)
+            || c.checked_mul(T::from(multiple))
+                .is_some_and(|need| state.tokens(i) >= need)
+    })
+}
+
+/// Checks that a set of offsets is exactly `0..n`, with no gaps or duplicates.
+fn check_offsets(nodes: Vec<(&String, i32)>) -> Result<(), ModelError> {
+    offset_errors(nodes).into_iter().next().map_or(Ok(()), Err)
+}
+
+/// Lists every offset that is outside `0..n` or repeats an earlier one, in offset order.
+pub(crate) fn offset_errors(mut nodes: Vec<(&String, i32)>) -> Vec<ModelError> {
+    nodes.sort_by_key(|&(label, offset)| (offset, label));
+    let n = nodes.len();
+    let mut errors = Vec::new();
+    for (i, &(label, offset)) in nodes.iter().enumerate() {
+        if usize::try_from(offset).map_or(true, |o| o >= n) {
+            errors.push(ModelError::InvalidOffset {
+                label: label.clone(),
+                offset,
+            });
+        } else if let Some(&(other, _)) = i
+            .checked_sub(1)
+            .map(|j| &nodes[j])
+            .filter(|p| p.1 == offset)
+        {
+            errors.push(ModelError::DuplicateOffset {
+                label: label.clone(),
+                other: other.clone(),
+                offset,
+            });
+        }
+    }
+    errors
+}
+
+/// The per-place vectors of a `StateMachine`, filled in while compiling a `PetriNet`.
+struct PlaceVectors {
+    initial: Vector,
+    capacity: Vector,
+    zero_capacity: Vec<bool>,
+    allow_negative: Vec<bool>,
+    places: Vec<String>,
+}
+
+/// Converts a place offset into an index, rejecting offsets outside the net's places.
+fn place_offset(label: &str, offset: i32, net_places: usize) -> Result<usize, ModelError> {
+    usize::try_from(offset)
+        .ok()
+        .filter(|&o| o < net_places)
+        .ok_or_else(|| ModelError::InvalidOffset {
+            label: label.to_string(),
+            offset,
+        })
+}
+
+/// Adds one arc of the net to the delta, consume vector or guards of its transition.
+fn compile_arc(
+    model: &PetriNet,
+    arc: &crate::petri_net::Arrow,
+    transitions: &mut TransitionMap,
+    vector_size: usize,
+) -> Result<(), ModelError> {
+    let weight = arc.weight.unwrap_or(1);
+    let consume = arc.consume.unwrap_or(false);
+    let produce = arc.produce.unwrap_or(false);
+    let inhibit = arc.inhibit.unwrap_or(false);
+    let read = arc.read.unwrap_or(false);
+
+    let (place_label, transition_label) = if read || produce {
+        (&arc.target, &arc.source)
+    } else {
+        (&arc.source, &arc.target)
+    };
+    model.check_arc_endpoints(arc)?;
+    let (Some(p), Some(t)) = (
+        model.places.get(place_label),
+        transitions.get_mut(transition_label),
+    ) else {
+        // the flags point the arc the other way round
+        return Err(ModelError::InvalidArc {
+            source: arc.source.clone(),
+            target: arc.target.clone(),
+            reason: "must connect a place and a transition".to_string(),
+        });
+    };
+    let negated = weight.checked_neg().ok_or_else(|| ModelError::InvalidArc {
+        source: arc.source.clone(),
+        target: arc.target.clone(),
+        reason: format!("weight {weight} is out of range"),
+    })?;
+
+    let offset = place_offset(place_label, p.offset, model.places.len())?;
+    if inhibit {
+        let mut delta = vec![0; vector_size];
+        delta[offset] = negated;
+        let guard = Guard { delta, read };
+        if let Some(group) = &arc.group {
+            t.guard_groups
+                .entry(group.clone())
+                .or_insert_with(|| GuardGroup {
+                    guards: GuardMap::new(),
+                })
+                .guards
+                .insert(place_label.clone(), guard);
+        } else {
+            t.guards.insert(arc.target.clone(), guard);
+        }
+    } else {
+        // arcs to the same place add up, so a self-loop consumes and produces
+        let change = if consume { negated } else { weight };
+        let out_of_range = || ModelError::InvalidArc {
+            source: arc.source.clone(),
+            target: arc.target.clone(),
+            reason: "combined weight is out of range".to_string(),
+        };
+        t.delta[offset] = t.delta[offset]
+            .checked_add(change)
+            .ok_or_else(out_of_range)?;
+        if consume {
+            t.consume[offset] = t.consume[offset]
+                .checked_add(weight)
+                .ok_or_else(out_of_range)?;
+        }
+    }
+    Ok(())
+}
+
+/// Builds the initial marking, capacities and labels of the net's own places.
+fn compile_places(
+    model: &PetriNet,
+    model_type: ModelType,
+    vector_size: usize,
+) -> Result<PlaceVectors, ModelError> {
+    let mut vectors = PlaceVectors {
+        initial: vec![0; vector_size],
+        capacity: vec![0; vector_size],
+        zero_capacity: vec![false; vector_size],
+        allow_negative: vec![false; vector_size],
+        places: vec![String::new(); vector_size],
+    };
+
+    for (k, v) in &model.places {
+        let i = v.initial.unwrap_or(0);
+        if i < 0 {
+            return Err(ModelError::NegativeInitial {
+                label: k.clone(),
+                initial: i,
+            });
+        }
+
+        let offset = place_offset(k, v.offset, model.places.len())?;
+        vectors.initial[offset] = match model_type {
+            ModelType::PetriNet => i,
+            ModelType::Workflow | ModelType::Elementary => match i {
+                0 => 0,
+                _ => 1,
+            },
+        };
+        vectors.capacity[offset] = match model_type {
+            ModelType::PetriNet => v.capacity.unwrap_or(0),
+            ModelType::Elementary | ModelType::Workflow => 1,
+        };
+        vectors.zero_capacity[offset] =
+            matches!(model_type, ModelType::PetriNet) && v.zero_capacity && v.capacity == Some(0);
+        vectors.allow_negative[offset] = v.allow_negative;
+        vectors.places[offset].clone_from(k);
+    }
+    Ok(vectors)
+}
+
+/// Adds the implicit server places of finite-server transitions after the net's own places.
+fn compile_servers(
+    servers: Vec<(&String, &crate::petri_net::Transition)>,
+    net_places: usize,
+    transitions: &mut TransitionMap,
+    vectors: &mut PlaceVectors,
+) -> Result<(), ModelError> {
+    for (i, (label, t)) in servers.into_iter().enumerate() {
+        let offset = net_places + i;
+        let count = t
+            .server_count
+            .map_or(0, |c| i32::try_from(c).unwrap_or(i32::MAX));
+        vectors.initial[offset] = count;
+        vectors.capacity[offset] = count;
+        vectors.zero_capacity[offset] = count == 0;
+        vectors.places[offset] = format!("{label}.servers");
+        let server = transitions
+            .get_mut(label)
+            .expect("server transition is declared");
+        server.delta[offset] -= 1;
+        server.consume[offset] += 1;
+        if let Some(completion) = &t.completed_by {
+            let Some(completion) = transitions.get_mut(completion) else {
+                return Err(ModelError::UndeclaredNode {
+                    source: (*label).clone(),
+                    target: completion.clone(),
+                    missing: completion.clone(),
+                });
+            };
+            completion.delta[offset] += 1;
+        }
+    }
+    Ok(())
+}
+
 impl StateMachine {
     /// Creates a new `StateMachine` object from the given `PetriNet`.
     pub fn new(declaration: fn(&mut dyn Dsl)) -> Self {
         let net = &mut PetriNet::new();
         let mut sm = net.declare(declaration).as_vasm();
-        sm.model_type = model_type_from_string(&net.model_type);
         let mut transitions: Vec<_> = net.transitions.iter().collect();
         transitions.sort_by_key(|(_, v)| v.offset);
         sm.actions = transitions.into_iter().map(|(k, _)| k.clone()).collect();
         sm
     }
+
+    /// Creates a new `StateMachine` object from the given `PetriNet`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the petri net is not valid, see `try_from_model`.
     pub fn from_model(model: &mut PetriNet) -> Self {
-        Self::from_model_impl(model, None)
+        Self::try_from_model(model).unwrap_or_else(|err| panic!("{err}"))
     }
 
     /// Creates a new `StateMachine` object from the given `PetriNet`.
     ///
-    /// # Panics
+    /// Returns a `ModelError` if the model type is unknown, an arc is malformed, the place or
+    /// transition offsets are not exactly `0..n`, or a place has a negative initial marking.
+    pub fn try_from_model(model: &mut PetriNet) -> Result<Self, ModelError> {
+        Self::from_model_impl(model, None)
+    }
+
+    /// Creates a new `StateMachine` object from the given `PetriNet`.
     ///
-    /// This function will panic if petri net is not valid.
-    fn from_model_impl(model: &mut PetriNet, re_entry: Option<bool>) -> Self {
-        let model_type = model_type_from_string(&model.model_type);
-        model.populate_arc_attributes();
+    /// Every malformed net is reported as a `ModelError` rather than a panic.
+    fn from_model_impl(model: &mut PetriNet, re_entry: Option<bool>) -> Result<Self, ModelError> {
+        let model_type: ModelType = model.model_type.parse()?;
+        model.normalize();
+        for arc in &model.arcs {
+            arc.validate_flags()?;
+        }
         let mut roles = RoleMap::new();
         model.transitions.iter().for_each(|(_, v)| {
             roles.insert(
@@ -139,7 +667,23 @@ impl StateMachine {
             );
         });
 
-        let vector_size = model.places.len();
+        // servers of finite-server transitions are counted in implicit places after the net's own
+        let mut servers: Vec<(&String, &crate::petri_net::Transition)> = model
+            .transitions
+            .iter()
+            .filter(|(_, t)| t.server_count.is_some() && model_type_has_servers(model_type))
+            .collect();
+        servers.sort_by_key(|(_, t)| t.offset);
+        let net_places = model.places.len();
+        let vector_size = net_places + servers.len();
+        check_offsets(model.places.iter().map(|(k, v)| (k, v.offset)).collect())?;
+        check_offsets(
+            model
+                .transitions
+                .iter()
+                .map(|(k, v)| (k, v.offset))
+                .collect(),
+        )?;
 
         let mut transitions: TransitionMap = model
             .transitions
@@ -151,85 +695,22 @@ impl StateMachine {
                         label: k.clone(),
                         role: v.role.clone().unwrap_or_else(|| "default".to_string()),
                         delta: vec![0; vector_size],
+                        consume: vec![0; vector_size],
                         guards: GuardMap::new(),
+                        guard_groups: HashMap::new(),
                         allow_reentry: re_entry.unwrap_or(false),
                         offset: v.offset,
+                        weight: v.weight,
                     },
                 )
             })
             .collect();
 
-        model.arcs.iter().for_each(|arc| {
-            let source = arc.source.clone();
-            let target = arc.target.clone();
-            let weight = arc.weight.unwrap_or(1);
-            let consume = arc.consume.unwrap_or(false);
-            let produce = arc.produce.unwrap_or(false);
-            let inhibit = arc.inhibit.unwrap_or(false);
-            let read = arc.read.unwrap_or(false);
-
-            let p = if read || produce {
-                model.places.get(&target)
-            } else if consume || inhibit {
-                model.places.get(&source)
-            } else {
-                panic!("unexpected arc type");
-            }
-            .expect("place not found");
-
-            let t = if read || produce {
-                transitions.get_mut(&source)
-            } else if consume || inhibit {
-                transitions.get_mut(&target)
-            } else {
-                panic!("unexpected arc type");
-            }
-            .expect("transition not found");
-
-            let delta = &mut vec![0; vector_size];
-            let offset_result: usize = p.offset.try_into().expect("invalid offset");
-            delta[offset_result] = 0 - weight;
-            if inhibit {
-                t.guards.insert(
-                    target.clone(),
-                    Guard {
-                        delta: delta.clone(),
-                        read,
-                    },
-                );
-            } else if consume {
-                let offset: usize = p.offset.try_into().expect("invalid offset");
-                t.delta[offset] = 0 - weight;
-            } else {
-                let offset: usize = p.offset.try_into().expect("invalid offset");
-                t.delta[offset] = weight;
-            }
-        });
-
-        let mut initial = vec![0; vector_size];
-        let mut capacity = vec![0; vector_size];
-        let mut places = vec![String::new(); vector_size];
-
-        model.places.iter().for_each(|(k, v)| {
-            let i = v.initial.unwrap_or(0);
-            assert!(i >= 0, "initial must be non-negative");
-
-            let offset_result: usize = v.offset.try_into().expect("invalid offset");
-            initial[offset_result] = match model_type {
-                ModelType::PetriNet => i,
-                ModelType::Workflow | ModelType::Elementary => match i {
-                    0 => 0,
-                    _ => 1,
-                },
-            };
-
-            let offset_result: usize = v.offset.try_into().expect("invalid offset");
-            capacity[offset_result] = match model_type {
-                ModelType::PetriNet => v.capacity.unwrap_or(0),
-                ModelType::Elementary | ModelType::Workflow => 1,
-            };
-            places[offset_result].clone_from(k);
-        });
+        for arc in &model.arcs {
+            compile_arc(model, arc, &mut transitions, vector_size)?;
+        }
+        let mut vectors = compile_places(model, model_type, vector_size)?;
+        compile_servers(servers, net_places, &mut transitions, &mut vectors)?;
         let mut sorted_transitions: Vec<_> = transitions.iter().collect();
         sorted_transitions.sort_by_key(|(_, v)| v.offset);
         let actions = sorted_transitions
@@ -237,115 +718,542 @@ impl StateMachine {
             .map(|(k, _)| k.clone())
             .collect();
 
-        Self {
-            model_type: model_type_from_string(&model.model_type),
-            initial,
-            capacity,
-            places,
+        Ok(Self {
+            model_type,
+            initial: vectors.initial,
+            capacity: vectors.capacity,
+            zero_capacity: vectors.zero_capacity,
+            allow_negative: vectors.allow_negative,
+            places: vectors.places,
             transitions,
             roles,
             actions,
-        }
+            semantics: None,
+        })
     }
 
-    fn guard_fails(&self, state: &Vector, transition: &Transition, multiple: i32) -> bool {
-        for guard in transition.guards.values() {
-            let (_, threshold_met, _, _) =
-                vector_add(&self.capacity, state, &guard.delta, multiple);
-            if guard.read {
-                if !threshold_met {
-                    return true; // read arc enables after a threshold
+    /// Lists the compiled machine as text, for reading it or comparing it in snapshot tests.
+    ///
+    /// Places are listed by offset with their initial marking and capacity. Each transition
+    /// follows by offset on one line, `action: place: ±k, ...`, naming its non-zero deltas
+    /// and role, then one indented line per self-loop and guard. Guards are sorted, so the
+    /// dump is the same on every run.
+    ///
+    /// # Panics
+    ///
+    /// Panics if writing to a `String` fails, which it does not.
+    pub fn debug_dump(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+        writeln!(out, "model {}", self.model_type).expect("write failed");
+        writeln!(out, "places:").expect("write failed");
+        for (i, place) in self.places.iter().enumerate() {
+            let capacity = self
+                .place_capacity(i)
+                .map_or_else(|| "unbounded".to_string(), |c| c.to_string());
+            writeln!(
+                out,
+                "  {i} {place} initial={} capacity={capacity}",
+                self.initial[i]
+            )
+            .expect("write failed");
+        }
+        writeln!(out, "transitions:").expect("write failed");
+        let name = |delta: &Vector| {
+            delta
+                .iter()
+                .position(|&w| w != 0)
+                .map_or(("?", 0), |i| (self.places[i].as_str(), -delta[i]))
+        };
+        for action in &self.actions {
+            let transition = &self.transitions[action];
+            let deltas: Vec<String> = transition
+                .delta
+                .iter()
+                .enumerate()
+                .filter(|(_, &k)| k != 0)
+                .map(|(i, k)| format!("{}: {k:+}", self.places[i]))
+                .collect();
+            writeln!(
+                out,
+                "  {action}: {} ({})",
+                deltas.join(", "),
+                transition.role
+            )
+            .expect("write failed");
+            for (i, &consumed) in transition.consume.iter().enumerate() {
+                if consumed > 0 && transition.delta[i] > -consumed {
+                    writeln!(out, "    loop {}: {consumed}", self.places[i]).expect("write failed");
                 }
-            } else if threshold_met {
-                return true; // guard inhibits until a threshold
+            }
+            let mut guards: Vec<String> = transition
+                .guards
+                .values()
+                .map(|g| (None, g))
+                .chain(transition.guard_groups.iter().flat_map(|(group, g)| {
+                    g.guards.values().map(move |guard| (Some(group), guard))
+                }))
+                .map(|(group, guard)| {
+                    let (place, weight) = name(&guard.delta);
+                    let kind = if guard.read { "read" } else { "inhibit" };
+                    let group = group.map_or_else(String::new, |g| format!(" group={g}"));
+                    format!("    {kind} {place}: {weight}{group}")
+                })
+                .collect();
+            guards.sort();
+            for guard in guards {
+                writeln!(out, "{guard}").expect("write failed");
             }
         }
-        false
+        out
     }
-    pub fn petri_net_fire(&self, state: &Vector, transition: &Transition, multiple: i32) -> Tx {
-        let role = transition.role.clone();
-        let (output, ok, overflow, underflow) =
-            vector_add(&self.capacity, state, &transition.delta, multiple);
-        let inhibited = self.guard_fails(state, transition, multiple);
 
-        Tx {
+    /// Serializes a marking as a JSON object of token counts keyed by place label.
+    ///
+    /// Unlike the raw vector, the object does not depend on place offsets, so a saved marking
+    /// can still be loaded after the net's places are reordered.
+    pub fn marking_to_json(&self, state: &Vector) -> serde_json::Value {
+        let object: serde_json::Map<String, serde_json::Value> = self
+            .places
+            .iter()
+            .zip(state)
+            .map(|(place, &tokens)| (place.clone(), tokens.into()))
+            .collect();
+        serde_json::Value::Object(object)
+    }
+
+    /// Parses a marking written by `marking_to_json`, placing each count by its label.
+    ///
+    /// Places missing from the object hold no tokens.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ModelError::Parse` if the value is not an object, a count is not an `i32`, or
+    /// a label names no place in this machine.
+    pub fn marking_from_json(&self, value: &serde_json::Value) -> Result<Vector, ModelError> {
+        let object = value
+            .as_object()
+            .ok_or_else(|| ModelError::Parse("marking must be a JSON object".to_string()))?;
+        let mut state = vec![0; self.places.len()];
+        for (label, count) in object {
+            let offset = self.places.iter().position(|p| p == label).ok_or_else(|| {
+                ModelError::Parse(format!("marking names unknown place: {label}"))
+            })?;
+            state[offset] = count
+                .as_i64()
+                .and_then(|c| i32::try_from(c).ok())
+                .ok_or_else(|| {
+                    ModelError::Parse(format!("marking of {label} is not a token count: {count}"))
+                })?;
+        }
+        Ok(state)
+    }
+
+    /// Converts the token counts to a wider integer type, such as `i64`.
+    ///
+    /// Custom semantics are typed by the token count, so the widened machine uses the built-in
+    /// rule for its `model_type`.
+    pub fn widen<U: Int>(&self) -> StateMachine<U> {
+        let widen_vector = |v: &Vector| v.iter().map(|&x| U::from(x)).collect();
+        let widen_guards = |guards: &GuardMap| {
+            guards
+                .iter()
+                .map(|(g, guard)| {
+                    let guard = Guard {
+                        delta: widen_vector(&guard.delta),
+                        read: guard.read,
+                    };
+                    (g.clone(), guard)
+                })
+                .collect()
+        };
+        StateMachine {
+            model_type: self.model_type,
+            initial: widen_vector(&self.initial),
+            capacity: widen_vector(&self.capacity),
+            zero_capacity: self.zero_capacity.clone(),
+            allow_negative: self.allow_negative.clone(),
+            places: self.places.clone(),
+            transitions: self
+                .transitions
+                .iter()
+                .map(|(k, t)| {
+                    let transition = Transition {
+                        label: t.label.clone(),
+                        role: t.role.clone(),
+                        delta: widen_vector(&t.delta),
+                        consume: widen_vector(&t.consume),
+                        guards: widen_guards(&t.guards),
+                        guard_groups: t
+                            .guard_groups
+                            .iter()
+                            .map(|(name, group)| {
+                                let group = GuardGroup {
+                                    guards: widen_guards(&group.guards),
+                                };
+                                (name.clone(), group)
+                            })
+                            .collect(),
+                        allow_reentry: t.allow_reentry,
+                        offset: t.offset,
+                        weight: t.weight,
+                    };
+                    (k.clone(), transition)
+                })
+                .collect(),
+            roles: self.roles.clone(),
+            actions: self.actions.clone(),
+            semantics: None,
+        }
+    }
+}
+
+/// A `StateMachine` only reads itself while firing, so one machine can be shared across threads.
+const _: () = {
+    const fn assert_send_sync<S: Send + Sync>() {}
+    assert_send_sync::<StateMachine>();
+    assert_send_sync::<StateMachine<i64>>();
+};
+
+impl<T: Int> StateMachine<T> {
+    /// Wraps the machine for sharing, so many threads can each fire against their own marking.
+    ///
+    /// `transform` takes `&self` and the marking is passed in, so clones of the `Arc` need no
+    /// lock; only a marking that several threads update needs one, as in `Model::fire`.
+    pub fn shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    /// Returns the most tokens the place at `offset` may hold, or `None` if it is unbounded.
+    ///
+    /// A capacity of 0 is unbounded unless the place is marked in `zero_capacity`.
+    pub fn place_capacity(&self, offset: usize) -> Option<T> {
+        bounded_capacity(&self.capacity, &self.zero_capacity, offset)
+    }
+
+    /// Fires transitions with `semantics` instead of the built-in rule for `model_type`.
+    ///
+    /// This applies to `transform` and everything built on it. `transform_marking` always uses
+    /// the built-in rule, as custom semantics only handle dense vectors.
+    #[must_use]
+    pub fn with_semantics(mut self, semantics: impl FireSemantics<T> + 'static) -> Self {
+        self.semantics = Some(Arc::new(semantics));
+        self
+    }
+
+    /// Names each token count in the state by its place label.
+    pub fn marking_map(&self, state: &Vector<T>) -> HashMap<String, T> {
+        self.places
+            .iter()
+            .cloned()
+            .zip(state.iter().copied())
+            .collect()
+    }
+
+    /// Reads the token count of the place labelled `place_label`, or `None` if there is no such place.
+    pub fn tokens(&self, state: &Vector<T>, place_label: &str) -> Option<T> {
+        self.places
+            .iter()
+            .position(|p| p == place_label)
+            .and_then(|offset| state.get(offset).copied())
+    }
+
+    /// Looks up an action by name, returning `None` if the net has no such transition.
+    pub fn action_handle(&self, name: &str) -> Option<ActionId> {
+        self.actions.iter().position(|a| a == name).map(ActionId)
+    }
+
+    /// Transforms the state by the action behind a handle from `action_handle`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the handle was issued by a different `StateMachine`.
+    pub fn transform_by_id(&self, state: &Vector<T>, id: ActionId, multiple: i32) -> Tx<T> {
+        let transition = &self.transitions[&self.actions[id.0]];
+        self.fire(state, transition, multiple)
+    }
+
+    /// Fires the first enabled transition, by offset, that belongs to `role`.
+    ///
+    /// Returns the name of the transition that fired with its result, or `None` if no
+    /// transition of that role is enabled.
+    pub fn transform_role(
+        &self,
+        state: &Vector<T>,
+        role: &str,
+        multiple: i32,
+    ) -> Option<(String, Tx<T>)> {
+        self.actions
+            .iter()
+            .filter(|action| self.transitions[*action].role == role)
+            .map(|action| {
+                let tx = self.fire(state, &self.transitions[action], multiple);
+                (action.clone(), tx)
+            })
+            .find(|(_, tx)| tx.is_ok())
+    }
+
+    /// Transforms a marking of any storage by an action, returning `None` if it is not enabled.
+    ///
+    /// This follows the same semantics as `transform` for the machine's `model_type`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the net has no transition named `action`.
+    pub fn transform_marking<M: Marking<T>>(
+        &self,
+        state: &M,
+        action: &str,
+        multiple: i32,
+    ) -> Option<M> {
+        let transition = self
+            .transitions
+            .get(action)
+            .unwrap_or_else(|| panic!("no transition for {action}"));
+        let fired = match self.model_type {
+            ModelType::Elementary => self.elementary_step(state, transition, multiple),
+            ModelType::Workflow => self.workflow_step(state, transition, multiple),
+            ModelType::PetriNet => self.petri_net_step(state, transition, multiple),
+        };
+        fired.ok.then_some(fired.output)
+    }
+
+    fn fire(&self, state: &Vector<T>, transition: &Transition<T>, multiple: i32) -> Tx<T> {
+        if let Some(semantics) = &self.semantics {
+            return semantics.fire(self, state, transition, multiple);
+        }
+        match self.model_type {
+            ModelType::Elementary => self.elementary_fire(state, transition, multiple),
+            ModelType::Workflow => self.workflow_fire(state, transition, multiple),
+            ModelType::PetriNet => self.petri_net_fire(state, transition, multiple),
+        }
+    }
+
+    fn guard_passes<M: Marking<T>>(&self, state: &M, guard: &Guard<T>, multiple: i32) -> bool {
+        // guards compare token counts against thresholds, so debit places get no allowance
+        let (_, threshold_met, _, _) = vector_add(
+            &self.capacity,
+            &self.zero_capacity,
+            &[],
+            state,
+            &guard.delta,
+            multiple,
+        );
+        // a read arc enables after a threshold, an inhibitor blocks until one
+        threshold_met == guard.read
+    }
+
+    fn guard_fails<M: Marking<T>>(
+        &self,
+        state: &M,
+        transition: &Transition<T>,
+        multiple: i32,
+    ) -> bool {
+        let passes = |guard: &Guard<T>| self.guard_passes(state, guard, multiple);
+        if !transition.guards.values().all(passes) {
+            return true;
+        }
+        transition
+            .guard_groups
+            .values()
+            .any(|group| !group.guards.values().any(passes))
+    }
+
+    fn petri_net_step<M: Marking<T>>(
+        &self,
+        state: &M,
+        transition: &Transition<T>,
+        multiple: i32,
+    ) -> Step<M> {
+        let (output, ok, overflow, underflow) = vector_add(
+            &self.capacity,
+            &self.zero_capacity,
+            &self.allow_negative,
+            state,
+            &transition.delta,
+            multiple,
+        );
+        let covered = covers(&self.allow_negative, state, &transition.consume, multiple);
+        let underflow = underflow || !covered;
+        let inhibited = self.guard_fails(state, transition, multiple);
+        Step {
             output,
-            ok: ok && !inhibited,
-            role,
+            ok: ok && covered && !inhibited,
             inhibited,
             overflow,
             underflow,
         }
     }
 
-    pub fn elementary_fire(&self, state: &Vector, transition: &Transition, multiple: i32) -> Tx {
-        let role = transition.role.clone();
-        let (output, ok, overflow, underflow) =
-            vector_add(&self.capacity, state, &transition.delta, multiple);
+    fn elementary_step<M: Marking<T>>(
+        &self,
+        state: &M,
+        transition: &Transition<T>,
+        multiple: i32,
+    ) -> Step<M> {
+        let (output, ok, overflow, underflow) = vector_add(
+            &self.capacity,
+            &self.zero_capacity,
+            &self.allow_negative,
+            state,
+            &transition.delta,
+            multiple,
+        );
+        let covered = covers(&self.allow_negative, state, &transition.consume, multiple);
+        let (ok, underflow) = (ok && covered, underflow || !covered);
         let inhibited = self.guard_fails(state, transition, multiple);
-        let output_state_count = output.iter().filter(|&x| *x > 0).count();
-        let elementary_ok = ok && output_state_count == 1 && !inhibited;
-        Tx {
+        let output_state_count = (0..self.places.len())
+            .filter(|&i| output.tokens(i) > T::ZERO)
+            .count();
+        Step {
             output,
-            ok: elementary_ok,
-            role,
+            ok: ok && output_state_count == 1 && !inhibited,
             inhibited,
             overflow,
             underflow,
         }
     }
 
-    pub fn workflow_fire(&self, state: &Vector, transition: &Transition, multiple: i32) -> Tx {
-        let role = transition.role.clone();
-        let (output, _, mut overflow, underflow) =
-            vector_add(&self.capacity, state, &transition.delta, multiple);
+    fn workflow_step<M: Marking<T>>(
+        &self,
+        state: &M,
+        transition: &Transition<T>,
+        multiple: i32,
+    ) -> Step<M> {
+        let (mut output, _, mut overflow, underflow) = vector_add(
+            &self.capacity,
+            &self.zero_capacity,
+            &self.allow_negative,
+            state,
+            &transition.delta,
+            multiple,
+        );
         let inhibited = self.guard_fails(state, transition, multiple);
-        let workflow_output = output
-            .iter()
-            .map(|x| {
-                match x {
-                    0 | -1 => 0, // allow retry / reentry
-                    2 => {
-                        overflow = true;
-                        1
-                    } // allow transition
-                    1 => 1,      // no other values allowed
-                    _ => -1,     // no other values allowed
-                }
-            })
-            .collect::<Vec<i32>>();
-        let output_state_count = workflow_output.iter().filter(|&x| *x > 0).count();
+        let one = T::from(1);
+        let mut output_state_count = 0;
+        for i in 0..self.places.len() {
+            let x = output.tokens(i);
+            let value = if x == T::ZERO || x == T::from(-1) {
+                T::ZERO // allow retry / reentry
+            } else if x == T::from(2) {
+                overflow = true;
+                one // allow transition
+            } else if x == one {
+                one
+            } else {
+                T::from(-1) // no other values allowed
+            };
+            if value > T::ZERO {
+                output_state_count += 1;
+            }
+            output.set_tokens(i, value);
+        }
         let ok = !overflow && output_state_count == 1 && !inhibited;
         if transition.allow_reentry && !ok && overflow {
-            Tx {
-                output: workflow_output,
+            Step {
+                output,
                 ok: true,
-                role,
                 inhibited,
                 overflow: false,
                 underflow,
             }
         } else {
-            Tx {
-                output: workflow_output,
+            Step {
+                output,
                 ok,
-                role,
                 inhibited,
                 overflow,
                 underflow,
             }
         }
     }
+
+    /// Rejects a state with fewer entries than the machine has places, as an underflow.
+    ///
+    /// The missing places hold no tokens to consume, and firing would index past the end.
+    fn short_state(&self, state: &Vector<T>, transition: &Transition<T>) -> Option<Tx<T>> {
+        (state.len() < self.places.len()).then(|| Tx {
+            ok: false,
+            output: state.clone(),
+            role: transition.role.clone(),
+            inhibited: false,
+            overflow: false,
+            underflow: true,
+        })
+    }
+
+    pub fn petri_net_fire(
+        &self,
+        state: &Vector<T>,
+        transition: &Transition<T>,
+        multiple: i32,
+    ) -> Tx<T> {
+        if let Some(tx) = self.short_state(state, transition) {
+            return tx;
+        }
+        self.petri_net_step(state, transition, multiple)
+            .into_tx(transition)
+    }
+
+    pub fn elementary_fire(
+        &self,
+        state: &Vector<T>,
+        transition: &Transition<T>,
+        multiple: i32,
+    ) -> Tx<T> {
+        if let Some(tx) = self.short_state(state, transition) {
+            return tx;
+        }
+        self.elementary_step(state, transition, multiple)
+            .into_tx(transition)
+    }
+
+    pub fn workflow_fire(
+        &self,
+        state: &Vector<T>,
+        transition: &Transition<T>,
+        multiple: i32,
+    ) -> Tx<T> {
+        if let Some(tx) = self.short_state(state, transition) {
+            return tx;
+        }
+        self.workflow_step(state, transition, multiple)
+            .into_tx(transition)
+    }
+}
+
+/// The outcome of firing a transition against a marking of any storage.
+struct Step<M> {
+    output: M,
+    ok: bool,
+    inhibited: bool,
+    overflow: bool,
+    underflow: bool,
+}
+
+impl<T> Step<Vector<T>> {
+    fn into_tx(self, transition: &Transition<T>) -> Tx<T> {
+        Tx {
+            ok: self.ok,
+            output: self.output,
+            role: transition.role.clone(),
+            inhibited: self.inhibited,
+            overflow: self.overflow,
+            underflow: self.underflow,
+        }
+    }
 }
 
 /// `Tx` is a struct that represents the result of a transformation in a state machine.
+///
 /// It provides information about the success of the transformation, the resulting state, the role that performed the transformation, and any errors that occurred.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Tx {
+pub struct Tx<T = i32> {
     /// A boolean indicating whether the transformation was successful.
     pub ok: bool,
     /// The resulting state after the transformation.
-    pub output: Vector,
+    pub output: Vector<T>,
     /// The role that performed the transformation.
     pub role: String,
     /// An optional boolean indicating whether the transformation was inhibited.
@@ -356,7 +1264,7 @@ pub struct Tx {
     pub underflow: bool,
 }
 
-impl Tx {
+impl<T> Tx<T> {
     /// Checks if the transaction was successful.
     ///
     /// # Returns
@@ -373,14 +1281,15 @@ impl Tx {
 }
 
 /// `Vasm` is a trait that represents a [vector addition state machine](https://en.wikipedia.org/wiki/Vector_addition_system).
+///
 /// It provides methods to create an empty vector, get the initial Vector, and transform the state.
-pub trait Vasm {
+pub trait Vasm<T = i32> {
     /// Creates an empty vector with the same length as the number of places in the state machine.
     ///
     /// # Returns
     ///
     /// * A vector of zeros with the same length as the number of places in the state machine.
-    fn empty_vector(&self) -> Vector;
+    fn empty_vector(&self) -> Vector<T>;
 
     /// Gets the initial vector of the state machine.
     ///
@@ -388,7 +1297,27 @@ pub trait Vasm {
     ///
     /// * The initial vector of the state machine.
     ///
-    fn initial_vector(&self) -> Vector;
+    fn initial_vector(&self) -> Vector<T>;
+
+    /// Overwrites `buf` with the initial vector, reusing its allocation where it is large enough.
+    ///
+    /// The default still builds the vector with `initial_vector` before copying it in.
+    fn write_initial_into(&self, buf: &mut Vector<T>) {
+        buf.clear();
+        buf.extend(self.initial_vector());
+    }
+
+    /// Replaces the initial vector, leaving the net it was built from unchanged.
+    ///
+    /// The default leaves the machine as it is and returns `MarkingError::Unsupported`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `MarkingError` if the marking's length is not the number of places, or it puts
+    /// a negative count on a place that does not allow one.
+    fn set_initial(&mut self, _marking: Vector<T>) -> Result<(), MarkingError> {
+        Err(MarkingError::Unsupported)
+    }
 
     /// Transforms the state of the state machine according to the given action and multiple.
     ///
@@ -402,7 +1331,28 @@ pub trait Vasm {
     ///
     /// * A `Transaction` object that represents the result of the transformation.
     ///
-    fn transform(&self, state: &Vector, action: &str, multiple: i32) -> Tx;
+    fn transform(&self, state: &Vector<T>, action: &str, multiple: i32) -> Tx<T>;
+
+    /// Fires every enabled action once from the given state.
+    ///
+    /// Each action is fired with `transform`, so inhibitor and read guards, capacities, and
+    /// the model type's semantics all decide which actions are enabled.
+    ///
+    /// # Returns
+    ///
+    /// * Each enabled action, ordered by offset, paired with the marking it produces.
+    fn successors(&self, state: &Vector<T>) -> Vec<(String, Vector<T>)> {
+        self.action_labels()
+            .into_iter()
+            .filter_map(|action| {
+                let tx = self.transform(state, &action, 1);
+                tx.is_ok().then_some((action, tx.output))
+            })
+            .collect()
+    }
+
+    /// Lists the actions `successors` tries, ordered by offset.
+    fn action_labels(&self) -> Vec<String>;
 }
 
 impl dyn Vasm {
@@ -411,27 +1361,60 @@ impl dyn Vasm {
     }
 }
 
-impl Vasm for StateMachine {
-    fn empty_vector(&self) -> Vector {
-        vec![0; self.places.len()]
+impl<T: Int> Vasm<T> for StateMachine<T> {
+    fn empty_vector(&self) -> Vector<T> {
+        vec![T::ZERO; self.places.len()]
     }
 
-    fn initial_vector(&self) -> Vector {
+    fn initial_vector(&self) -> Vector<T> {
         self.initial.clone()
     }
 
+    fn write_initial_into(&self, buf: &mut Vector<T>) {
+        buf.clear();
+        buf.extend_from_slice(&self.initial);
+    }
+
+    fn set_initial(&mut self, marking: Vector<T>) -> Result<(), MarkingError> {
+        if marking.len() != self.places.len() {
+            return Err(MarkingError::Length {
+                expected: self.places.len(),
+                actual: marking.len(),
+            });
+        }
+        if let Some(i) = (0..marking.len()).find(|&i| {
+            marking[i] < T::ZERO && !self.allow_negative.get(i).copied().unwrap_or(false)
+        }) {
+            return Err(MarkingError::Negative {
+                place: self.places[i].clone(),
+            });
+        }
+        self.initial = marking;
+        Ok(())
+    }
+
     // REVIEW: test that this works properly
-    fn transform(&self, state: &Vector, action: &str, multiple: i32) -> Tx {
+    fn transform(&self, state: &Vector<T>, action: &str, multiple: i32) -> Tx<T> {
         let transition = self
             .transitions
             .get(action)
             .unwrap_or_else(|| panic!("no transition for {action}"));
+        self.fire(state, transition, multiple)
+    }
 
-        match self.model_type {
-            ModelType::Elementary => self.elementary_fire(state, transition, multiple),
-            ModelType::Workflow => self.workflow_fire(state, transition, multiple),
-            ModelType::PetriNet => self.petri_net_fire(state, transition, multiple),
-        }
+    fn successors(&self, state: &Vector<T>) -> Vec<(String, Vector<T>)> {
+        // walks `actions` in place rather than cloning it through `action_labels`
+        self.actions
+            .iter()
+            .filter_map(|action| {
+                let tx = self.transform(state, action, 1);
+                tx.is_ok().then(|| (action.clone(), tx.output))
+            })
+            .collect()
+    }
+
+    fn action_labels(&self) -> Vec<String> {
+        self.actions.clone()
     }
 }
 
@@ -439,6 +1422,200 @@ impl Vasm for StateMachine {
 mod tests {
     use super::*;
 
+    /// Fires like a petri net but lets places exceed their capacity.
+    #[derive(Debug)]
+    struct Uncapped;
+
+    impl FireSemantics for Uncapped {
+        fn fire(
+            &self,
+            sm: &StateMachine,
+            state: &Vector,
+            transition: &Transition,
+            multiple: i32,
+        ) -> Tx {
+            let mut tx = PetriNetSemantics.fire(sm, state, transition, multiple);
+            if tx.overflow && !tx.underflow && !tx.inhibited {
+                tx.overflow = false;
+                tx.ok = true;
+            }
+            tx
+        }
+    }
+
+    #[test]
+    fn test_custom_semantics() {
+        let capped = StateMachine::new(|p| {
+            p.cell("p", None, Option::from(1), 0, 0);
+            p.func("fill", "default", 0, 0);
+            p.arrow("fill", "p", 1);
+        });
+        let full = capped.transform(&capped.initial_vector(), "fill", 1).output;
+        assert!(capped.transform(&full, "fill", 1).overflow);
+
+        let uncapped = capped.clone().with_semantics(Uncapped);
+        let tx = uncapped.transform(&full, "fill", 1);
+        assert!(tx.is_ok());
+        assert_eq!(tx.output, vec![2]);
+        let cloned = uncapped.clone();
+        assert_eq!(cloned.transform(&full, "fill", 1).output, vec![2]);
+        assert!(uncapped.widen::<i64>().semantics.is_none());
+
+        let builtin = capped.with_semantics(PetriNetSemantics);
+        assert!(builtin.transform(&full, "fill", 1).overflow);
+    }
+
+    #[test]
+    fn test_shared_across_threads() {
+        let sm = StateMachine::new(crate::petri_net::tests::coffee_machine).shared();
+        let sequence = ["boil_water", "grind_beans", "brew_coffee", "pour_coffee"];
+        let expected = sequence.iter().fold(sm.initial_vector(), |state, action| {
+            sm.transform(&state, action, 1).output
+        });
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let sm = Arc::clone(&sm);
+                std::thread::spawn(move || {
+                    let mut state = sm.initial_vector();
+                    for action in sequence {
+                        let tx = sm.transform(&state, action, 1);
+                        assert!(tx.is_ok(), "{action}");
+                        state = tx.output;
+                    }
+                    state
+                })
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().expect("thread panicked"), expected);
+        }
+    }
+
+    #[test]
+    fn test_successors() {
+        let sm = StateMachine::new(crate::petri_net::tests::coffee_machine);
+        let initial = sm.initial_vector();
+        let successors = sm.successors(&initial);
+        let actions: Vec<&str> = successors.iter().map(|(a, _)| a.as_str()).collect();
+        assert_eq!(actions.len(), 2);
+        assert!(actions.contains(&"boil_water"));
+        assert!(actions.contains(&"grind_beans"));
+        for (action, marking) in &successors {
+            assert_eq!(marking, &sm.transform(&initial, action, 1).output);
+        }
+        assert!(sm.successors(&sm.empty_vector()).is_empty());
+    }
+
+    #[test]
+    fn test_debug_dump() {
+        let dump = || {
+            StateMachine::new(|p| {
+                crate::petri_net::tests::coffee_machine(p);
+                p.cell("Spill", None, None, 0, 0);
+                p.guard("Spill", "pour_coffee", 1);
+            })
+            .debug_dump()
+        };
+        let first = dump();
+        for _ in 0..4 {
+            assert_eq!(dump(), first);
+        }
+        assert!(first.starts_with("model petriNet\nplaces:\n  0 Water initial=1 capacity=1\n"));
+        assert!(first.contains(
+            "  brew_coffee: BoiledWater: -1, GroundCoffee: -1, Filter: -1, CoffeeInPot: +1 (default)\n"
+        ));
+        assert!(first.contains("  7 Spill initial=0 capacity=unbounded\n"));
+        assert!(first.ends_with("(default)\n    inhibit Spill: 1\n"));
+    }
+
+    #[test]
+    fn test_set_initial() {
+        let mut vm = <dyn Vasm>::new(crate::petri_net::tests::coffee_machine);
+        let mut marking = vm.empty_vector();
+        marking[0] = 1;
+        vm.set_initial(marking.clone()).expect("valid marking");
+        assert_eq!(vm.initial_vector(), marking);
+
+        assert_eq!(
+            vm.set_initial(vec![1]),
+            Err(MarkingError::Length {
+                expected: marking.len(),
+                actual: 1
+            })
+        );
+        marking[1] = -1;
+        assert!(matches!(
+            vm.set_initial(marking),
+            Err(MarkingError::Negative { .. })
+        ));
+        assert_eq!(vm.initial_vector()[1], 0);
+
+        let mut model = crate::model::Model::new(crate::petri_net::tests::coffee_machine);
+        let water = model
+            .vm
+            .places
+            .iter()
+            .position(|p| p == "Water")
+            .expect("Water");
+        let mut refilled = model.vm.initial_vector();
+        refilled[water] = 0;
+        model.vm.set_initial(refilled).expect("valid marking");
+        assert_eq!(model.reset_state()[water], 0);
+        assert_eq!(model.net.places["Water"].initial, Some(1));
+    }
+
+    #[test]
+    fn test_tokens_by_label() {
+        let sm = StateMachine::new(crate::petri_net::tests::coffee_machine);
+        let initial = sm.initial_vector();
+        assert_eq!(sm.tokens(&initial, "Water"), Some(1));
+        assert_eq!(sm.tokens(&initial, "BoiledWater"), Some(0));
+        assert_eq!(sm.tokens(&initial, "Tea"), None);
+    }
+
+    #[test]
+    fn test_marking_json_round_trip() {
+        let sm = StateMachine::new(crate::petri_net::tests::coffee_machine);
+        let state = sm.transform(&sm.initial_vector(), "boil_water", 1).output;
+        let json = sm.marking_to_json(&state);
+        assert_eq!(json["BoiledWater"], 1);
+        assert_eq!(json["Water"], 0);
+        assert_eq!(sm.marking_from_json(&json).expect("marking loads"), state);
+
+        assert!(sm.marking_from_json(&serde_json::json!([1, 0])).is_err());
+        assert!(sm
+            .marking_from_json(&serde_json::json!({ "Tea": 1 }))
+            .is_err());
+        assert!(sm
+            .marking_from_json(&serde_json::json!({ "Water": "one" }))
+            .is_err());
+    }
+
+    #[test]
+    fn test_marking_json_survives_edits() {
+        let before = StateMachine::new(|p| {
+            p.cell("a", Option::from(2), None, 0, 0);
+            p.cell("b", None, None, 0, 0);
+        });
+        let saved = before.marking_to_json(&vec![2, 5]);
+
+        // "a" is renamed to "c" and moved after "b"
+        let after = StateMachine::new(|p| {
+            p.cell("b", None, None, 0, 0);
+            p.cell("c", Option::from(2), None, 0, 0);
+        });
+        let mut renamed = saved.clone();
+        let object = renamed.as_object_mut().expect("object");
+        let tokens = object.remove("a").expect("a is saved");
+        object.insert("c".to_string(), tokens);
+        assert_eq!(
+            after.marking_from_json(&renamed).expect("loads"),
+            vec![5, 2]
+        );
+        assert!(after.marking_from_json(&saved).is_err());
+    }
+
     #[test]
     fn test_default_net() {
         let net = &mut PetriNet::new();
@@ -450,6 +1627,28 @@ mod tests {
         assert!(state.is_empty());
     }
 
+    #[test]
+    fn test_write_initial_into() {
+        let net = &mut PetriNet::new();
+        let mut mm = net.declare(|p| {
+            p.cell("a", Option::from(2), None, 0, 0);
+            p.cell("b", None, None, 0, 0);
+            p.cell("c", Option::from(1), None, 0, 0);
+        });
+        let vm = mm.as_vasm();
+
+        let mut buf = vec![9; 8];
+        let capacity = buf.capacity();
+        vm.write_initial_into(&mut buf);
+        assert_eq!(buf, vm.initial_vector());
+        assert_eq!(buf.capacity(), capacity);
+
+        buf[0] = 7;
+        buf.push(5);
+        vm.write_initial_into(&mut buf);
+        assert_eq!(buf, vec![2, 0, 1]);
+    }
+
     #[test]
     fn test_state_machine_diagram() {
         let contents = r"
@@ -485,4 +1684,338 @@ mod tests {
         let zblob = net.to_zblob();
         println!("https://pflow.dev/?z={}", zblob.base64_zipped);
     }
+
+    #[test]
+    fn test_model_type_from_str() {
+        assert!(matches!("petriNet".parse(), Ok(ModelType::PetriNet)));
+        assert!(matches!("workflow".parse(), Ok(ModelType::Workflow)));
+        assert!(matches!("elementary".parse(), Ok(ModelType::Elementary)));
+        assert!(matches!("PETRINET".parse(), Ok(ModelType::PetriNet)));
+        assert!(matches!("WorkFlow".parse(), Ok(ModelType::Workflow)));
+        assert_eq!(
+            "dfa".parse::<ModelType>().expect_err("unknown type"),
+            ModelTypeError {
+                input: "dfa".to_string()
+            }
+        );
+
+        let mut net = PetriNet::new();
+        net.model_type = "dfa".to_string();
+        assert!(matches!(
+            StateMachine::try_from_model(&mut net),
+            Err(ModelError::UnknownModelType(_))
+        ));
+    }
+
+    #[test]
+    fn test_action_handle() {
+        let mut net = PetriNet::new();
+        let mut mm = net.declare(|p| {
+            p.cell("a", Option::from(1), None, 100, 100);
+            p.cell("b", None, None, 200, 100);
+            p.func("move", "default", 150, 100);
+            p.arrow("a", "move", 1);
+            p.arrow("move", "b", 1);
+        });
+        let vm = mm.as_vasm();
+        assert_eq!(vm.action_handle("mvoe"), None);
+
+        let id = vm.action_handle("move").expect("known action");
+        assert_eq!(vm.actions[id.index()], "move");
+        let res = vm.transform_by_id(&vm.initial_vector(), id, 1);
+        assert!(res.is_ok());
+        assert_eq!(res.output, vec![0, 1]);
+    }
+
+    #[test]
+    fn test_transform_role() {
+        let mut net = PetriNet::new();
+        let mut mm = net.declare(|p| {
+            p.cell("orders", Option::from(1), None, 100, 100);
+            p.cell("meals", None, None, 200, 100);
+            p.cell("tables", Option::from(1), None, 100, 200);
+            p.cell("served", None, None, 300, 100);
+            p.func("cook", "cook", 150, 100);
+            p.func("serve", "waiter", 250, 100);
+            p.func("set_table", "waiter", 150, 200);
+            p.arrow("orders", "cook", 1);
+            p.arrow("cook", "meals", 1);
+            p.arrow("meals", "serve", 1);
+            p.arrow("serve", "served", 1);
+            p.arrow("tables", "set_table", 1);
+        });
+        let vm = mm.as_vasm();
+        let state = vm.initial_vector();
+
+        let (action, res) = vm.transform_role(&state, "cook", 1).expect("cook can act");
+        assert_eq!(action, "cook");
+        assert_eq!(res.output, vec![0, 1, 1, 0]);
+
+        let (action, res) = vm
+            .transform_role(&state, "waiter", 1)
+            .expect("waiter can act");
+        assert_eq!(action, "set_table");
+        assert_eq!(res.output, vec![1, 0, 0, 0]);
+
+        assert!(vm.transform_role(&res.output, "waiter", 1).is_none());
+        assert!(vm.transform_role(&state, "manager", 1).is_none());
+    }
+
+    #[test]
+    fn test_dense_and_sparse_markings_agree() {
+        let mut net = PetriNet::from_json_str(crate::petri_net::tests::DINING_PHILOSOPHERS)
+            .expect("failed to parse");
+        let sm = StateMachine::from_model(&mut net);
+        let to_sparse = |dense: &Vector| -> HashMap<usize, i32> {
+            (0..dense.len()).fold(HashMap::new(), |mut m, i| {
+                m.set_tokens(i, dense[i]);
+                m
+            })
+        };
+        let mut frontier = vec![sm.initial_vector()];
+        for _ in 0..3 {
+            let mut next = Vec::new();
+            for dense in &frontier {
+                let sparse = to_sparse(dense);
+                for action in &sm.actions {
+                    let from_dense = sm.transform_marking(dense, action, 1);
+                    let from_sparse = sm.transform_marking(&sparse, action, 1);
+                    assert_eq!(from_dense.as_ref().map(to_sparse), from_sparse, "{action}");
+                    assert_eq!(
+                        from_dense.is_some(),
+                        sm.transform(dense, action, 1).is_ok(),
+                        "{action}"
+                    );
+                    next.extend(from_dense);
+                }
+            }
+            assert!(!next.is_empty());
+            frontier = next;
+        }
+    }
+
+    #[test]
+    fn test_marking_add_reports_overflow() {
+        let mut dense: Vector = vec![i32::MAX - 1, 0];
+        let mut sparse: HashMap<usize, i32> = HashMap::from([(0, i32::MAX - 1)]);
+        assert!(dense.add(0, 1));
+        assert!(sparse.add(0, 1));
+        assert!(!dense.add(0, 1));
+        assert!(!sparse.add(0, 1));
+        assert_eq!((dense.tokens(0), sparse.tokens(0)), (i32::MAX, i32::MAX));
+        assert!(dense.add(1, i32::MAX));
+        assert!(sparse.add(1, i32::MAX));
+        assert_eq!((dense.tokens(1), sparse.tokens(1)), (i32::MAX, i32::MAX));
+    }
+
+    #[test]
+    fn test_short_state_does_not_panic() {
+        let sm = StateMachine::new(crate::petri_net::tests::coffee_machine);
+        let action = &sm.actions[0];
+        let short = sm.initial_vector()[..2].to_vec();
+        let tx = sm.transform(&short, action, 1);
+        assert!(tx.is_err());
+        assert!(tx.underflow);
+        assert_eq!(tx.output, short);
+        assert!(sm.transform(&Vec::new(), action, 1).is_err());
+
+        // a short dense marking reads like a sparse one with the same entries
+        let sparse: HashMap<usize, i32> = HashMap::from([(0, short[0]), (1, short[1])]);
+        for action in &sm.actions {
+            assert_eq!(
+                sm.transform_marking(&short, action, 1).map(|m| m.tokens(0)),
+                sm.transform_marking(&sparse, action, 1)
+                    .map(|m| m.tokens(0)),
+                "{action}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_finite_server_transition() {
+        let mut net = PetriNet::new();
+        let mut sm = net.declare(|p| {
+            p.cell("jobs", Option::from(5), None, 100, 100);
+            p.cell("done", None, None, 300, 100);
+            p.func("start", "default", 200, 50);
+            p.func("finish", "default", 200, 150);
+            p.arrow("jobs", "start", 1);
+            p.arrow("finish", "done", 1);
+            p.servers("start", 2, "finish");
+        });
+        let vm = sm.as_vasm();
+        assert_eq!(vm.places, vec!["jobs", "done", "start.servers"]);
+
+        let mut state = vm.initial_vector();
+        for _ in 0..2 {
+            state = vm.transform(&state, "start", 1).output;
+        }
+        assert_eq!(state, vec![3, 0, 0]);
+        let blocked = vm.transform(&state, "start", 1);
+        assert!(blocked.is_err() && blocked.underflow);
+
+        state = vm.transform(&state, "finish", 1).output;
+        assert_eq!(state, vec![3, 1, 1]);
+        assert!(vm.transform(&state, "start", 1).is_ok());
+    }
+
+    #[test]
+    fn test_self_loop_needs_its_token() {
+        let check = |vm: StateMachine| {
+            let t = &vm.transitions["check"];
+            assert_eq!(t.delta, vec![0, 1]);
+            assert_eq!(t.consume, vec![1, 0]);
+
+            let res = vm.transform(&vec![1, 0], "check", 1);
+            assert!(res.is_ok());
+            assert_eq!(res.output, vec![1, 1]);
+            let res = vm.transform(&vec![0, 0], "check", 1);
+            assert!(res.is_err() && res.underflow);
+        };
+        // the result must not depend on which of the loop's arcs comes first
+        check(StateMachine::new(|p| {
+            p.cell("token", Option::from(1), None, 100, 100);
+            p.cell("count", None, None, 300, 100);
+            p.func("check", "default", 200, 100);
+            p.arrow("token", "check", 1);
+            p.arrow("check", "token", 1);
+            p.arrow("check", "count", 1);
+        }));
+        check(StateMachine::new(|p| {
+            p.cell("token", Option::from(1), None, 100, 100);
+            p.cell("count", None, None, 300, 100);
+            p.func("check", "default", 200, 100);
+            p.arrow("check", "token", 1);
+            p.arrow("token", "check", 1);
+            p.arrow("check", "count", 1);
+        }));
+    }
+
+    #[test]
+    fn test_debit_place_goes_negative() {
+        let mut net = PetriNet::from_json_str(
+            r#"{
+                "modelType": "petriNet",
+                "version": "v0",
+                "places": {
+                    "account": { "offset": 0, "initial": 0, "x": 100, "y": 100, "allowNegative": true },
+                    "stock": { "offset": 1, "initial": 1, "x": 100, "y": 200 }
+                },
+                "transitions": {
+                    "spend": { "offset": 0, "x": 200, "y": 100 },
+                    "ship": { "offset": 1, "x": 200, "y": 200 }
+                },
+                "arcs": [
+                    { "source": "account", "target": "spend", "weight": 1 },
+                    { "source": "stock", "target": "ship", "weight": 1 }
+                ]
+            }"#,
+        )
+        .expect("valid json");
+        let vm = StateMachine::from_model(&mut net);
+        assert_eq!(vm.allow_negative, vec![true, false]);
+
+        let res = vm.transform(&vm.initial_vector(), "spend", 2);
+        assert!(res.is_ok());
+        assert_eq!(res.output, vec![-2, 1]);
+
+        let res = vm.transform(&res.output, "ship", 2);
+        assert!(res.is_err());
+        assert!(res.underflow);
+        assert_eq!(res.output, vec![-2, -1]);
+    }
+
+    #[test]
+    fn test_widen_to_i64_avoids_overflow() {
+        let mut net = PetriNet::new();
+        let mut mm = net.declare(|p| {
+            p.cell("counter", None, None, 100, 100);
+            p.func("add", "default", 200, 100);
+            p.arrow("add", "counter", 1_000_000_000);
+        });
+        let narrow = mm.as_vasm();
+        let wide: StateMachine<i64> = narrow.widen();
+
+        let mut state = narrow.initial_vector();
+        let mut wide_state = wide.initial_vector();
+        for _ in 0..2 {
+            state = narrow.transform(&state, "add", 1).output;
+            wide_state = wide.transform(&wide_state, "add", 1).output;
+        }
+        let res = narrow.transform(&state, "add", 1);
+        assert!(res.is_err());
+        assert!(res.overflow);
+        assert_eq!(res.output, vec![2_000_000_000]);
+
+        let res = wide.transform(&wide_state, "add", 1);
+        assert!(res.is_ok());
+        assert_eq!(res.output, vec![3_000_000_000_i64]);
+    }
+
+    #[test]
+    fn test_contradictory_arc_flags() {
+        let cases = [
+            (
+                Some(true),
+                Some(true),
+                None,
+                None,
+                "cannot both consume and produce",
+            ),
+            (
+                Some(false),
+                Some(false),
+                None,
+                None,
+                "must consume, produce, or inhibit",
+            ),
+            (
+                None,
+                None,
+                None,
+                Some(true),
+                "read is only allowed on an inhibitor arc",
+            ),
+        ];
+        for (consume, produce, inhibit, read, reason) in cases {
+            let mut net = PetriNet::new();
+            net.add_place("p", 0, Some(1), None, 0, 0);
+            net.add_transition("t", "default", 0, 0);
+            net.add_arc(crate::dsl::ArcParams {
+                source: "p",
+                target: "t",
+                weight: Some(1),
+                consume,
+                produce,
+                inhibit,
+                read,
+                group: None,
+            });
+            let err = StateMachine::try_from_model(&mut net).expect_err("flags should be rejected");
+            assert_eq!(
+                err,
+                ModelError::InvalidArc {
+                    source: "p".to_string(),
+                    target: "t".to_string(),
+                    reason: reason.to_string(),
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_legacy_consuming_guard() {
+        // older JSON and `Dsl::guard` mark guards as consuming too
+        let mut net = PetriNet::from_json_value(serde_json::json!({
+            "modelType": "petriNet",
+            "places": { "p": { "offset": 0, "initial": 1 } },
+            "transitions": { "t": { "offset": 0 } },
+            "arcs": [ { "source": "p", "target": "t", "consume": true, "inhibit": true } ]
+        }))
+        .expect("legacy net loads");
+        let vm = StateMachine::try_from_model(&mut net).expect("legacy guard compiles");
+        let tx = vm.transform(&vm.initial_vector(), "t", 1);
+        assert!(tx.inhibited);
+        assert_eq!(tx.output, vec![1]);
+    }
 }
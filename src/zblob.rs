@@ -25,15 +25,16 @@ pub struct Zblob {
     pub created_at: String,
 }
 
+/// The IPFS CID of the canonical empty net held by `Zblob::empty()`.
+pub const EMPTY_NET_CID: &str = "zb2rhgH99on7UZUjgobsBmXdmMdZnD8HnET2GTtYBkLv4cT51";
+
 const EMPTY_NET: &str = "UEsDBAoAAAAAAER3WVjjbbhPbAAAAGwAAAAKAAAAbW9kZWwuanNvbnsKICAibW9kZWxUeXBlIjogInBldHJpTmV0IiwKICAidmVyc2lvbiI6ICJ2MCIsCiAgInBsYWNlcyI6IHsKICB9LAogICJ0cmFuc2l0aW9ucyI6IHsKICB9LAogICJhcmNzIjogWwogIF0KfVBLAQIUAAoAAAAAAER3WVjjbbhPbAAAAGwAAAAKAAAAAAAAAAAAAAAAAAAAAABtb2RlbC5qc29uUEsFBgAAAAABAAEAOAAAAJQAAAAAAA==";
 
 impl Default for Zblob {
     fn default() -> Self {
         Self {
             id: 0,
-            ipfs_cid: Oid::new(EMPTY_NET.as_bytes())
-                .expect("oid fault")
-                .to_string(),
+            ipfs_cid: EMPTY_NET_CID.to_string(),
             base64_zipped: EMPTY_NET.to_string(),
             title: "default".to_string(),
             description: String::new(),
@@ -50,6 +51,11 @@ const FAILED_TO_CONVERT: &str = "failed to convert to json";
 const FAILED_TO_DECOMPRESS: &str = "failed to decompress";
 
 impl Zblob {
+    /// Returns the blob of the canonical empty net, the same as `Zblob::default()`.
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
     /// Creates a new `Zblob` from a base64 encoded string.
     ///
     /// # Arguments
@@ -61,9 +67,9 @@ impl Zblob {
     /// This function will panic if the given string is not a valid base64 encoded string.
     pub fn from_string(encoded_zip: Option<&str>) -> Self {
         let mut zblob = Zblob::default();
-        if encoded_zip.is_some() {
-            zblob.base64_zipped = encoded_zip.expect(INVALID_ZIP).to_string();
-            zblob.ipfs_cid = Oid::new(encoded_zip.expect(INVALID_ZIP).as_bytes())
+        if let Some(encoded_zip) = encoded_zip {
+            zblob.base64_zipped = encoded_zip.to_string();
+            zblob.ipfs_cid = Oid::new(encoded_zip.as_bytes())
                 .expect(INVALID_ZIP)
                 .to_string();
             zblob.keywords = String::new();
@@ -82,6 +88,11 @@ impl Zblob {
         Self::from_string(Some(&data))
     }
 
+    /// Checks that the base64 zipped content hashes to `ipfs_cid`.
+    pub fn verify_cid(&self) -> bool {
+        Oid::new(self.base64_zipped.as_bytes()).is_ok_and(|oid| oid.to_string() == self.ipfs_cid)
+    }
+
     /// Converts the `Zblob` into a `PetriNet`.
     ///
     /// # Panics
@@ -92,3 +103,27 @@ impl Zblob {
         serde_json::from_str(&decoded).expect(FAILED_TO_DECOMPRESS)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_net_cid() {
+        let empty = Zblob::empty();
+        assert_eq!(empty.ipfs_cid, EMPTY_NET_CID);
+        assert_eq!(
+            Oid::new(empty.base64_zipped.as_bytes())
+                .expect("oid fault")
+                .to_string(),
+            EMPTY_NET_CID
+        );
+        assert!(empty.verify_cid());
+
+        let tampered = Zblob {
+            ipfs_cid: "zb2rhZTUivNkdVe6qCEQ3oFe4xEbhSbVhfRj1kdZhKrTcw2Nk".to_string(),
+            ..Zblob::empty()
+        };
+        assert!(!tampered.verify_cid());
+    }
+}